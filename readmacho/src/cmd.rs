@@ -1,10 +1,13 @@
 use atom_macho::load_command::{
     build_version::{BuildToolVersion, BuildVersionCommand, Platform, Tool},
-    segment64::{Section64, SectionAttr, SectionType, SegmentCommand64},
-    unix_thread::{Flavor, ThreadState},
-    DysymtabCommand, LoadCommand, SourceVersionCommand, SymtabCommand, UnixThreadCommand,
-    UuidCommand,
+    segment32::{Section32, SegmentCommand32},
+    segment64::{Section64, SectionAttr, SectionAttrs, SectionType, SegmentCommand64},
+    unix_thread::ThreadState,
+    DyldInfoCommand, DylibCommand, DylinkerCommand, DysymtabCommand, EntryPointCommand,
+    LinkeditDataCommand, LoadCommand, RpathCommand, SourceVersionCommand, SymtabCommand,
+    UnixThreadCommand, UuidCommand,
 };
+use std::borrow::Cow;
 
 pub fn print_cmd(cmds: &[LoadCommand], idx: usize) {
     let cmd = match cmds.get(idx) {
@@ -18,6 +21,15 @@ pub fn print_cmd(cmds: &[LoadCommand], idx: usize) {
     println!("------------------------");
 
     match cmd {
+        LoadCommand::Segment(seg, sects) => {
+            print_segment32(seg);
+            let n_prev_sects = count_prev_sects(cmds, idx);
+            for (i, sect) in sects.iter().enumerate() {
+                println!("-- section");
+                println!("{:<10} : {}", "index", i + n_prev_sects + 1);
+                print_section32(sect);
+            }
+        }
         LoadCommand::Segment64(seg, sects) => {
             print_segment64(seg);
             let n_prev_sects = count_prev_sects(cmds, idx);
@@ -48,10 +60,45 @@ pub fn print_cmd(cmds: &[LoadCommand], idx: usize) {
         LoadCommand::SourceVersion(source_ver) => {
             print_source_version(source_ver);
         }
-        LoadCommand::Unsupported(_, _) => {}
+        LoadCommand::Dylib(dylib) => {
+            print_dylib(dylib);
+        }
+        LoadCommand::DyldInfo(dyld_info) => {
+            print_dyld_info(dyld_info);
+        }
+        LoadCommand::LinkeditData(linkedit_data) => {
+            print_linkedit_data(linkedit_data);
+        }
+        LoadCommand::EntryPoint(entry_point) => {
+            print_entry_point(entry_point);
+        }
+        LoadCommand::Dylinker(dylinker) => {
+            print_dylinker(dylinker);
+        }
+        LoadCommand::Rpath(rpath) => {
+            print_rpath(rpath);
+        }
+        LoadCommand::Unsupported(cmd_n, _) => {
+            println!("{:<10} : 0x{:x} (unknown)", "cmd", cmd_n);
+            println!("{:<10} : {}", "cmdsize", cmd.cmd_size());
+        }
     }
 }
 
+fn print_segment32(seg: &SegmentCommand32) {
+    println!("{:<10} : {}", "cmd", "LC_SEGMENT");
+    println!("{:<10} : {}", "cmdsize", seg.cmdsize);
+    println!("{:<10} : \"{}\"", "segname", seg.segname.as_str());
+    println!("{:<10} : 0x{:x}", "vmaddr", seg.vmaddr);
+    println!("{:<10} : 0x{:x}", "vmsize", seg.vmsize);
+    println!("{:<10} : {}", "fileoff", seg.fileoff);
+    println!("{:<10} : {}", "filesize", seg.filesize);
+    println!("{:<10} : 0b{:03b}", "maxprot", seg.maxprot);
+    println!("{:<10} : 0b{:03b}", "initprot", seg.initprot);
+    println!("{:<10} : {}", "nsects", seg.nsects);
+    println!("{:<10} : 0x{:x}", "flags", seg.flags);
+}
+
 fn print_segment64(seg: &SegmentCommand64) {
     println!("{:<10} : {}", "cmd", "LC_SECGMENT64");
     println!("{:<10} : {}", "cmdsize", seg.cmdsize);
@@ -74,24 +121,31 @@ fn print_section(sect: &Section64) {
     println!("{:<10} : 2^{}", "align", sect.align);
     println!("{:<10} : {}", "reloff", sect.reloff);
     println!("{:<10} : {}", "nreloc", sect.nreloc);
+    print_section_flags(&sect.flags);
+}
 
-    let sect_ty = match sect.flags.1 {
-        SectionType::Regular => "regular",
-        SectionType::Zerofill => "zerofill",
-        SectionType::CstringLiterals => "cstring literals",
-        SectionType::FourByteLiterals => "4 byte literals",
-        SectionType::EightByteLiterals => "8 byte literals",
-        SectionType::LiteralPointers => "literals pointers",
-        SectionType::Coalesced => "coalesced",
-    };
-    println!("{:<10} : {}", "type", sect_ty);
+fn print_section32(sect: &Section32) {
+    println!("{:<10} : \"{}\"", "sectname", sect.sectname.as_str());
+    println!("{:<10} : 0x{:x}", "addr", sect.addr);
+    println!("{:<10} : 0x{:x}", "size", sect.size);
+    println!("{:<10} : {}", "fileoff", sect.offset);
+    println!("{:<10} : 2^{}", "align", sect.align);
+    println!("{:<10} : {}", "reloff", sect.reloff);
+    println!("{:<10} : {}", "nreloc", sect.nreloc);
+    print_section_flags(&sect.flags);
+}
 
-    for (i, attr) in sect.flags.0.iter().enumerate() {
+fn print_section_flags(flags: &(SectionAttrs, SectionType)) {
+    println!("{:<10} : {}", "type", format_section_type(flags.1));
+
+    for (i, attr) in flags.0.iter().enumerate() {
         let attr_str = match attr {
             SectionAttr::PureInstructions => "pure instructions",
             SectionAttr::NoToc => "no toc",
             SectionAttr::StripStaticSyms => "strip static syms",
+            SectionAttr::NoDeadStrip => "no dead strip",
             SectionAttr::LiveSupport => "live support",
+            SectionAttr::SelfModifyingCode => "self modifying code",
             SectionAttr::Debug => "debug",
             SectionAttr::SomeInstructions => "some instructions",
             SectionAttr::ExtReloc => "ext reloc",
@@ -100,15 +154,49 @@ fn print_section(sect: &Section64) {
         let key_str = if i == 0 { "attr" } else { "" };
         println!("{:<10} : {}", key_str, attr_str);
     }
+    if flags.0.unknown() != 0 {
+        println!("{:<10} : unknown ({:#x})", "attr", flags.0.unknown());
+    }
+}
+
+fn format_section_type(ty: SectionType) -> Cow<'static, str> {
+    Cow::Borrowed(match ty {
+        SectionType::Regular => "regular",
+        SectionType::Zerofill => "zerofill",
+        SectionType::CstringLiterals => "cstring literals",
+        SectionType::FourByteLiterals => "4 byte literals",
+        SectionType::EightByteLiterals => "8 byte literals",
+        SectionType::LiteralPointers => "literals pointers",
+        SectionType::NonLazySymbolPointers => "non lazy symbol pointers",
+        SectionType::LazySymbolPointers => "lazy symbol pointers",
+        SectionType::SymbolStubs => "symbol stubs",
+        SectionType::ModInitFuncPointers => "mod init func pointers",
+        SectionType::ModTermFuncPointers => "mod term func pointers",
+        SectionType::Coalesced => "coalesced",
+        SectionType::GbZerofill => "gb zerofill",
+        SectionType::Interposing => "interposing",
+        SectionType::SixteenByteLiterals => "16 byte literals",
+        SectionType::DtraceDof => "dtrace dof",
+        SectionType::LazyDylibSymbolPointers => "lazy dylib symbol pointers",
+        SectionType::ThreadLocalRegular => "thread local regular",
+        SectionType::ThreadLocalZerofill => "thread local zerofill",
+        SectionType::ThreadLocalVariables => "thread local variables",
+        SectionType::ThreadLocalVariablePointers => "thread local variable pointers",
+        SectionType::ThreadLocalInitFunctionPointers => "thread local init function pointers",
+        SectionType::InitFuncOffsets => "init func offsets",
+        SectionType::Unknown(n) => return Cow::Owned(format!("unknown ({:#x})", n)),
+    })
 }
 
 // returns a number of sections placed before segment `seg_idx`.
 fn count_prev_sects(cmds: &[LoadCommand], seg_idx: usize) -> usize {
     let mut n = 0;
 
-    for i in 0..seg_idx {
-        if let LoadCommand::Segment64(_, sects) = &cmds[i] {
-            n += sects.len();
+    for cmd in &cmds[..seg_idx] {
+        match cmd {
+            LoadCommand::Segment(_, sects) => n += sects.len(),
+            LoadCommand::Segment64(_, sects) => n += sects.len(),
+            _ => {}
         }
     }
 
@@ -125,41 +213,60 @@ fn print_symtab(cmd: &SymtabCommand) {
 }
 
 fn print_unixthread(cmd: &UnixThreadCommand) {
-    println!("{:<10} : {}", "cmd", "LC_UNIXTHREAD");
+    let name = if cmd.cmd == UnixThreadCommand::TYPE_THREAD {
+        "LC_THREAD"
+    } else {
+        "LC_UNIXTHREAD"
+    };
+    println!("{:<10} : {}", "cmd", name);
     println!("{:<10} : {}", "cmdsize", cmd.cmdsize);
 
-    match cmd.flavor {
-        Flavor::ThreadStateX86_64 => println!("{:<10} : x86_THREAD_STATE64", "flavor"),
-        Flavor::Unknown(n) => println!("{:<10} : unknown {}", "flavor", n),
-    };
+    for state in cmd.states.iter() {
+        let flavor = match state {
+            ThreadState::X86_64(_) => Cow::Borrowed("x86_THREAD_STATE64"),
+            ThreadState::Arm64(_) => Cow::Borrowed("ARM_THREAD_STATE64"),
+            ThreadState::Unknown { flavor, .. } => Cow::Owned(format!("unknown {}", flavor)),
+        };
+        println!("{:<10} : {}", "flavor", flavor);
+        println!("{:<10} : {}", "count", state.count());
 
-    println!("{:<10} : {}", "count", cmd.count);
-
-    match &cmd.state {
-        ThreadState::X86_64(state) => {
-            println!("{:<10} : {:<7} 0x{:016x}", "state", "rax", state.__rax);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "rbx", state.__rbx);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "rcx", state.__rcx);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "rdx", state.__rdx);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "rdi", state.__rdi);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "rsi", state.__rsi);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "rbp", state.__rbp);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "rsp", state.__rsp);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "r8", state.__r8);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "r9", state.__r9);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "r10", state.__r10);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "r11", state.__r11);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "r12", state.__r12);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "r13", state.__r13);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "r14", state.__r14);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "r15", state.__r15);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "rip", state.__rip);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "rflags", state.__rflags);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "cs", state.__cs);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "fs", state.__fs);
-            println!("{:<10} : {:<7} 0x{:016x}", "", "gs", state.__gs);
-        }
-        ThreadState::Unknown(_) => {}
+        match state {
+            ThreadState::X86_64(state) => {
+                println!("{:<10} : {:<7} 0x{:016x}", "state", "rax", state.__rax);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "rbx", state.__rbx);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "rcx", state.__rcx);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "rdx", state.__rdx);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "rdi", state.__rdi);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "rsi", state.__rsi);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "rbp", state.__rbp);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "rsp", state.__rsp);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "r8", state.__r8);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "r9", state.__r9);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "r10", state.__r10);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "r11", state.__r11);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "r12", state.__r12);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "r13", state.__r13);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "r14", state.__r14);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "r15", state.__r15);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "rip", state.__rip);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "rflags", state.__rflags);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "cs", state.__cs);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "fs", state.__fs);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "gs", state.__gs);
+            }
+            ThreadState::Arm64(state) => {
+                for (i, x) in state.__x.iter().enumerate() {
+                    let key = if i == 0 { "state" } else { "" };
+                    println!("{:<10} : {:<7} 0x{:016x}", key, format!("x{}", i), x);
+                }
+                println!("{:<10} : {:<7} 0x{:016x}", "", "fp", state.__fp);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "lr", state.__lr);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "sp", state.__sp);
+                println!("{:<10} : {:<7} 0x{:016x}", "", "pc", state.__pc);
+                println!("{:<10} : {:<7} 0x{:08x}", "", "cpsr", state.__cpsr);
+            }
+            ThreadState::Unknown { .. } => {}
+        }
     }
 }
 
@@ -213,15 +320,84 @@ fn print_tool_version(ver: &BuildToolVersion) {
     println!("--- tool version");
 
     let tool_str = match ver.tool {
-        Tool::Clang => "clang",
-        Tool::Swift => "swift",
-        Tool::LD => "ld",
+        Tool::Clang => Cow::Borrowed("clang"),
+        Tool::Swift => Cow::Borrowed("swift"),
+        Tool::LD => Cow::Borrowed("ld"),
+        Tool::LLD => Cow::Borrowed("lld"),
+        Tool::Unknown(n) => Cow::Owned(format!("unknown ({})", n)),
     };
     println!("{:<10} : {}", "tool", tool_str);
 
     println!("{:<10} : {}", "version", ver.version);
 }
 
+fn print_dylib(cmd: &DylibCommand) {
+    let name = crate::cmds::dylib_cmd_name(cmd.cmd).to_uppercase();
+    println!("{:<10} : LC_{}", "cmd", name);
+    println!("{:<10} : {}", "cmdsize", cmd.cmdsize);
+    println!(
+        "{:<10} : {} (offset {})",
+        "name", cmd.name.string, cmd.name.offset
+    );
+    println!("{:<10} : {}", "timestamp", cmd.timestamp);
+    println!("{:<10} : {}", "current", cmd.current_version);
+    println!("{:<10} : {}", "compat", cmd.compatibility_version);
+}
+
+fn print_dylinker(cmd: &DylinkerCommand) {
+    let name = crate::cmds::dylinker_cmd_name(cmd.cmd).to_uppercase();
+    println!("{:<10} : LC_{}", "cmd", name);
+    println!("{:<10} : {}", "cmdsize", cmd.cmdsize);
+    println!(
+        "{:<10} : {} (offset {})",
+        "name", cmd.name.string, cmd.name.offset
+    );
+}
+
+fn print_rpath(cmd: &RpathCommand) {
+    println!("{:<10} : {}", "cmd", "LC_RPATH");
+    println!("{:<10} : {}", "cmdsize", cmd.cmdsize);
+    println!(
+        "{:<10} : {} (offset {})",
+        "path", cmd.path.string, cmd.path.offset
+    );
+}
+
+fn print_dyld_info(cmd: &DyldInfoCommand) {
+    let name = if cmd.cmd == DyldInfoCommand::TYPE_ONLY {
+        "LC_DYLD_INFO_ONLY"
+    } else {
+        "LC_DYLD_INFO"
+    };
+    println!("{:<10} : {}", "cmd", name);
+    println!("{:<10} : {}", "cmdsize", cmd.cmdsize);
+    println!("{:<10} : {}", "rebase_off", cmd.rebase_off);
+    println!("{:<10} : {}", "rebase_size", cmd.rebase_size);
+    println!("{:<10} : {}", "bind_off", cmd.bind_off);
+    println!("{:<10} : {}", "bind_size", cmd.bind_size);
+    println!("{:<10} : {}", "weak_bind_off", cmd.weak_bind_off);
+    println!("{:<10} : {}", "weak_bind_size", cmd.weak_bind_size);
+    println!("{:<10} : {}", "lazy_bind_off", cmd.lazy_bind_off);
+    println!("{:<10} : {}", "lazy_bind_size", cmd.lazy_bind_size);
+    println!("{:<10} : {}", "export_off", cmd.export_off);
+    println!("{:<10} : {}", "export_size", cmd.export_size);
+}
+
+fn print_linkedit_data(cmd: &LinkeditDataCommand) {
+    let name = crate::cmds::linkedit_data_cmd_name(cmd.cmd).to_uppercase();
+    println!("{:<10} : LC_{}", "cmd", name);
+    println!("{:<10} : {}", "cmdsize", cmd.cmdsize);
+    println!("{:<10} : {}", "dataoff", cmd.dataoff);
+    println!("{:<10} : {}", "datasize", cmd.datasize);
+}
+
+fn print_entry_point(cmd: &EntryPointCommand) {
+    println!("{:<10} : {}", "cmd", "LC_MAIN");
+    println!("{:<10} : {}", "cmdsize", cmd.cmdsize);
+    println!("{:<10} : {}", "entryoff", cmd.entryoff);
+    println!("{:<10} : {}", "stacksize", cmd.stacksize);
+}
+
 fn print_source_version(cmd: &SourceVersionCommand) {
     println!("{:<10} : {}", "cmd", "LC_SOURCE_VERSION");
     println!("{:<10} : {}", "cmdsize", cmd.cmdsize);
@@ -246,5 +422,5 @@ fn print_uuid(cmd: &UuidCommand) {
     for b in cmd.uuid {
         print!("{:02x}", b);
     }
-    println!("");
+    println!();
 }
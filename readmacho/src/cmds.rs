@@ -1,4 +1,7 @@
-use atom_macho::load_command::LoadCommand;
+use atom_macho::load_command::{
+    DyldInfoCommand, DylibCommand, DylinkerCommand, LinkeditDataCommand, LoadCommand,
+    UnixThreadCommand,
+};
 use std::borrow::Cow;
 
 pub fn print_cmds(cmds: &[LoadCommand]) {
@@ -11,13 +14,80 @@ pub fn print_cmds(cmds: &[LoadCommand]) {
 
 fn command_name(cmd: &LoadCommand) -> Cow<'static, str> {
     match cmd {
+        LoadCommand::Segment(seg, _) => format!("segment \"{}\"", seg.segname).into(),
         LoadCommand::Segment64(seg, _) => format!("segment64 \"{}\"", seg.segname).into(),
         LoadCommand::Symtab(_) => "symtab".into(),
+        LoadCommand::UnixThread(cmd) if cmd.cmd == UnixThreadCommand::TYPE_THREAD => {
+            "thread".into()
+        }
         LoadCommand::UnixThread(_) => "unixthread".into(),
         LoadCommand::Dysymtab(_) => "dysymtab".into(),
         LoadCommand::Uuid(_) => "uuid".into(),
         LoadCommand::BuildVersion(_, _) => "buildversion".into(),
         LoadCommand::SourceVersion(_) => "sourceversion".into(),
+        LoadCommand::Dylib(cmd) => {
+            format!("{} \"{}\"", dylib_cmd_name(cmd.cmd), cmd.name.string).into()
+        }
+        LoadCommand::DyldInfo(cmd) if cmd.cmd == DyldInfoCommand::TYPE_ONLY => {
+            "dyld_info_only".into()
+        }
+        LoadCommand::DyldInfo(_) => "dyld_info".into(),
+        LoadCommand::LinkeditData(cmd) => linkedit_data_cmd_name(cmd.cmd).into(),
+        LoadCommand::EntryPoint(_) => "main".into(),
+        LoadCommand::Dylinker(cmd) => {
+            format!("{} \"{}\"", dylinker_cmd_name(cmd.cmd), cmd.name.string).into()
+        }
+        LoadCommand::Rpath(cmd) => format!("rpath \"{}\"", cmd.path.string).into(),
         LoadCommand::Unsupported(cmd, _) => format!("unknown cmd [0x{:x}]", cmd).into(),
     }
 }
+
+pub fn dylib_cmd_name(cmd: u32) -> &'static str {
+    match cmd {
+        DylibCommand::ID_DYLIB => "id_dylib",
+        DylibCommand::LOAD_DYLIB => "load_dylib",
+        DylibCommand::LOAD_WEAK_DYLIB => "load_weak_dylib",
+        DylibCommand::REEXPORT_DYLIB => "reexport_dylib",
+        DylibCommand::LAZY_LOAD_DYLIB => "lazy_load_dylib",
+        DylibCommand::LOAD_UPWARD_DYLIB => "load_upward_dylib",
+        _ => "dylib",
+    }
+}
+
+pub fn dylinker_cmd_name(cmd: u32) -> &'static str {
+    match cmd {
+        DylinkerCommand::LOAD_DYLINKER => "load_dylinker",
+        DylinkerCommand::ID_DYLINKER => "id_dylinker",
+        DylinkerCommand::DYLD_ENVIRONMENT => "dyld_environment",
+        _ => "dylinker",
+    }
+}
+
+pub fn linkedit_data_cmd_name(cmd: u32) -> &'static str {
+    match cmd {
+        LinkeditDataCommand::CODE_SIGNATURE => "code_signature",
+        LinkeditDataCommand::FUNCTION_STARTS => "function_starts",
+        LinkeditDataCommand::DYLD_EXPORTS_TRIE => "dyld_exports_trie",
+        LinkeditDataCommand::DYLD_CHAINED_FIXUPS => "dyld_chained_fixups",
+        _ => "linkedit_data",
+    }
+}
+
+/// Prints the install name of the file and the dylibs it depends on, like `otool -L`.
+pub fn print_dylibs(cmds: &[LoadCommand]) {
+    println!("Shared Libraries");
+    println!("--------------------");
+    for cmd in cmds.iter() {
+        if let LoadCommand::Dylib(dylib) = cmd {
+            let weak = if dylib.cmd == DylibCommand::LOAD_WEAK_DYLIB {
+                ", weak"
+            } else {
+                ""
+            };
+            println!(
+                "{} (compatibility version {}, current version {}{})",
+                dylib.name.string, dylib.compatibility_version, dylib.current_version, weak
+            );
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use atom_macho::code_signature::{
+    Blob, CodeDirectory, HashType, SuperBlob, Verification, CSSLOT_ALTERNATE_CODEDIRECTORIES,
+    CSSLOT_APPLICATION, CSSLOT_CODEDIRECTORY, CSSLOT_DER_ENTITLEMENTS, CSSLOT_ENTITLEMENTS,
+    CSSLOT_INFOSLOT, CSSLOT_REQUIREMENTS, CSSLOT_RESOURCEDIR, CSSLOT_SIGNATURESLOT, CS_ADHOC,
+    CS_EXECSEG_MAIN_BINARY, CS_HARD, CS_KILL, CS_LINKER_SIGNED, CS_RESTRICT, CS_RUNTIME,
+    REQUIREMENT_DESIGNATED, REQUIREMENT_GUEST, REQUIREMENT_HOST, REQUIREMENT_LIBRARY,
+    REQUIREMENT_PLUGIN,
+};
+use std::borrow::Cow;
+
+/// Prints the signature of LC_CODE_SIGNATURE like `codesign -d -vvvvvv`.
+pub fn print_code_signature(sig: &SuperBlob) {
+    println!("Code Signature");
+    println!("--------------------");
+    println!("{:<10} : 0x{:08x}", "magic", sig.magic);
+    println!("{:<10} : {}", "length", sig.length);
+    println!("{:<10} : {}", "blobs", sig.index.len());
+
+    for index in sig.index.iter() {
+        println!("-- blob");
+        println!(
+            "{:<10} : 0x{:x} ({})",
+            "slot",
+            index.slot,
+            format_slot(index.slot)
+        );
+        println!("{:<10} : {}", "offset", index.offset);
+        println!("{:<10} : 0x{:08x}", "magic", index.blob.magic());
+
+        match &index.blob {
+            Blob::CodeDirectory(cd) => print_code_directory(cd),
+            Blob::Requirements(reqs) => {
+                println!("{:<10} : {}", "count", reqs.len());
+                for req in reqs.iter() {
+                    println!(
+                        "{:<10} : {} ({} bytes)",
+                        "",
+                        format_requirement_type(req.req_type),
+                        req.data.len()
+                    );
+                }
+            }
+            Blob::Entitlements(xml) => {
+                for line in xml.lines() {
+                    println!("{:<10} : {}", "", line);
+                }
+            }
+            Blob::DerEntitlements(der) => print_bytes(der),
+            Blob::Cms(cms) if cms.is_empty() => println!("{:<10} : (empty, ad-hoc)", "cms"),
+            Blob::Cms(cms) => print_bytes(cms),
+            Blob::Unknown { data, .. } => print_bytes(data),
+        }
+    }
+}
+
+/// Prints the result of verifying the code signature.
+pub fn print_verification(result: &Verification) {
+    println!("Code Signature Verification");
+    println!("--------------------");
+    println!(
+        "{:<10} : 0x{:x} ({})",
+        "codedir",
+        result.code_directory_slot,
+        format_slot(result.code_directory_slot)
+    );
+    println!(
+        "{:<10} : {}",
+        "pages",
+        format_mismatches(&result.mismatched_pages)
+    );
+    println!(
+        "{:<10} : {}",
+        "slots",
+        format_mismatches(&result.mismatched_slots)
+    );
+    for slot in result.unchecked_slots.iter() {
+        println!(
+            "{:<10} : 0x{:x} ({}) not checked",
+            "",
+            slot,
+            format_slot(*slot)
+        );
+    }
+    let verdict = if result.is_valid() {
+        "valid"
+    } else {
+        "invalid"
+    };
+    println!("{:<10} : {}", "result", verdict);
+}
+
+fn format_mismatches<T: std::fmt::Display>(items: &[T]) -> String {
+    if items.is_empty() {
+        return "ok".to_string();
+    }
+    let items = items.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+    format!("mismatch at {}", items.join(", "))
+}
+
+fn print_code_directory(cd: &CodeDirectory) {
+    println!("{:<10} : 0x{:x}", "version", cd.version);
+    println!(
+        "{:<10} : 0x{:x} ({})",
+        "flags",
+        cd.flags,
+        format_cd_flags(cd.flags)
+    );
+    println!("{:<10} : {}", "hashType", format_hash_type(cd.hash_type));
+    println!("{:<10} : {}", "identifier", cd.identifier);
+    println!(
+        "{:<10} : {}",
+        "teamID",
+        cd.team_id.as_deref().unwrap_or("not set")
+    );
+    println!("{:<10} : {}", "platform", cd.platform);
+    match cd.page_size_log2 {
+        0 => println!("{:<10} : none", "pageSize"),
+        n => println!("{:<10} : {}", "pageSize", 1u64 << n),
+    }
+    println!("{:<10} : {}", "codeLimit", cd.code_limit);
+    println!("{:<10} : 0x{:x}", "execSegBase", cd.exec_seg_base);
+    println!("{:<10} : 0x{:x}", "execSegLimit", cd.exec_seg_limit);
+    let main_binary = if cd.exec_seg_flags & CS_EXECSEG_MAIN_BINARY != 0 {
+        " (main binary)"
+    } else {
+        ""
+    };
+    println!(
+        "{:<10} : 0x{:x}{}",
+        "execSegFlags", cd.exec_seg_flags, main_binary
+    );
+    if cd.runtime != 0 {
+        println!("{:<10} : 0x{:x}", "runtime", cd.runtime);
+    }
+
+    println!("{:<10} : {}", "special", cd.special_slots.len());
+    for (i, hash) in cd.special_slots.iter().enumerate().rev() {
+        println!("{:<10} : {:>4} {}", "", -(i as i64 + 1), to_hex(hash));
+    }
+    println!("{:<10} : {}", "code", cd.code_slots.len());
+    for (i, hash) in cd.code_slots.iter().enumerate() {
+        println!("{:<10} : {:>4} {}", "", i, to_hex(hash));
+    }
+}
+
+fn print_bytes(bytes: &[u8]) {
+    println!("{:<10} : {} bytes", "data", bytes.len());
+    for chunk in bytes.chunks(32) {
+        println!("{:<10} : {}", "", to_hex(chunk));
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn format_slot(slot: u32) -> Cow<'static, str> {
+    Cow::Borrowed(match slot {
+        CSSLOT_CODEDIRECTORY => "CodeDirectory",
+        CSSLOT_INFOSLOT => "Info.plist",
+        CSSLOT_REQUIREMENTS => "Requirements",
+        CSSLOT_RESOURCEDIR => "Resources",
+        CSSLOT_APPLICATION => "Application",
+        CSSLOT_ENTITLEMENTS => "Entitlements",
+        CSSLOT_DER_ENTITLEMENTS => "DER Entitlements",
+        CSSLOT_SIGNATURESLOT => "CMS Signature",
+        n if (CSSLOT_ALTERNATE_CODEDIRECTORIES..CSSLOT_ALTERNATE_CODEDIRECTORIES + 5)
+            .contains(&n) =>
+        {
+            return Cow::Owned(format!(
+                "Alternate CodeDirectory {}",
+                n - CSSLOT_ALTERNATE_CODEDIRECTORIES
+            ))
+        }
+        _ => "unknown",
+    })
+}
+
+fn format_requirement_type(req_type: u32) -> Cow<'static, str> {
+    Cow::Borrowed(match req_type {
+        REQUIREMENT_HOST => "host",
+        REQUIREMENT_GUEST => "guest",
+        REQUIREMENT_DESIGNATED => "designated",
+        REQUIREMENT_LIBRARY => "library",
+        REQUIREMENT_PLUGIN => "plugin",
+        n => return Cow::Owned(format!("unknown ({})", n)),
+    })
+}
+
+fn format_hash_type(hash_type: HashType) -> Cow<'static, str> {
+    Cow::Borrowed(match hash_type {
+        HashType::Sha1 => "SHA-1",
+        HashType::Sha256 => "SHA-256",
+        HashType::Sha256Truncated => "SHA-256 (truncated)",
+        HashType::Sha384 => "SHA-384",
+        HashType::Unknown(n) => return Cow::Owned(format!("unknown ({})", n)),
+    })
+}
+
+fn format_cd_flags(flags: u32) -> String {
+    let names = [
+        (CS_ADHOC, "adhoc"),
+        (CS_HARD, "hard"),
+        (CS_KILL, "kill"),
+        (CS_RESTRICT, "restrict"),
+        (CS_RUNTIME, "runtime"),
+        (CS_LINKER_SIGNED, "linker-signed"),
+    ];
+    let names = names
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(", ")
+    }
+}
@@ -0,0 +1,252 @@
+use atom_macho::{
+    chained_fixups::{ChainedFixup, ChainedFixups, ChainedTarget},
+    dyld_info::{
+        Bind, BindType, DyldInfo, Rebase, RebaseType, BIND_SPECIAL_DYLIB_FLAT_LOOKUP,
+        BIND_SPECIAL_DYLIB_MAIN_EXECUTABLE, BIND_SPECIAL_DYLIB_SELF,
+        BIND_SPECIAL_DYLIB_WEAK_LOOKUP, BIND_SYMBOL_FLAGS_WEAK_IMPORT,
+    },
+    export_trie::{Export, ExportData, ExportKind},
+    load_command::{DylibCommand, LoadCommand},
+    Error,
+};
+use std::borrow::Cow;
+
+/// Name, vmaddr and sections (name, addr, size) of a segment.
+struct Segment<'a> {
+    name: &'a str,
+    vmaddr: u64,
+    sections: Vec<(&'a str, u64, u64)>,
+}
+
+/// Prints the records of the dyld info like `dyldinfo -rebase -bind -weak_bind -lazy_bind`.
+pub struct DyldInfoPrinter<'a> {
+    segments: Vec<Segment<'a>>,
+    dylibs: Vec<&'a str>,
+}
+
+impl<'a> DyldInfoPrinter<'a> {
+    pub fn new(cmds: &'a [LoadCommand]) -> Self {
+        let mut segments = Vec::new();
+        let mut dylibs = Vec::new();
+        for cmd in cmds.iter() {
+            match cmd {
+                LoadCommand::Segment(seg, sects) => segments.push(Segment {
+                    name: seg.segname.as_str(),
+                    vmaddr: seg.vmaddr.into(),
+                    sections: sects
+                        .iter()
+                        .map(|s| (s.sectname.as_str(), s.addr.into(), s.size.into()))
+                        .collect(),
+                }),
+                LoadCommand::Segment64(seg, sects) => segments.push(Segment {
+                    name: seg.segname.as_str(),
+                    vmaddr: seg.vmaddr,
+                    sections: sects
+                        .iter()
+                        .map(|s| (s.sectname.as_str(), s.addr, s.size))
+                        .collect(),
+                }),
+                LoadCommand::Dylib(dylib) if dylib.cmd != DylibCommand::ID_DYLIB => {
+                    dylibs.push(dylib.name.string.as_str());
+                }
+                _ => {}
+            }
+        }
+        DyldInfoPrinter { segments, dylibs }
+    }
+
+    pub fn print_rebases(&self, dyld_info: &DyldInfo) -> Result<(), Error> {
+        println!("rebase information (from compressed dyld info):");
+        println!(
+            "{:<8} {:<18} {:<18} {}",
+            "segment", "section", "address", "type"
+        );
+        for rebase in dyld_info.rebases()? {
+            let Rebase {
+                segment_index,
+                offset,
+                rebase_type,
+            } = rebase;
+            let (segment, section, address) = self.locate(segment_index.into(), offset);
+            println!(
+                "{:<8} {:<18} 0x{:<16x} {}",
+                segment,
+                section,
+                address,
+                format_rebase_type(rebase_type)
+            );
+        }
+        Ok(())
+    }
+
+    pub fn print_binds(&self, binds: Vec<Bind>, title: &str) {
+        println!("{}:", title);
+        println!(
+            "{:<8} {:<18} {:<18} {:<8} {:<6} {:<16} {}",
+            "segment", "section", "address", "type", "addend", "dylib", "symbol"
+        );
+        for bind in binds.iter() {
+            let (segment, section, address) = self.locate(bind.segment_index.into(), bind.offset);
+            let weak_import = if bind.flags & BIND_SYMBOL_FLAGS_WEAK_IMPORT != 0 {
+                " (weak import)"
+            } else {
+                ""
+            };
+            println!(
+                "{:<8} {:<18} 0x{:<16x} {:<8} 0x{:<4x} {:<16} {}{}",
+                segment,
+                section,
+                address,
+                format_bind_type(bind.bind_type),
+                bind.addend,
+                self.dylib_name(bind.library_ordinal),
+                bind.symbol_name,
+                weak_import
+            );
+        }
+    }
+
+    pub fn print_chained_fixups(&self, fixups: &ChainedFixups, walked: &[ChainedFixup]) {
+        println!("chained fixups:");
+        println!(
+            "{:<8} {:<18} {:<18} {:<8} {}",
+            "segment", "section", "address", "type", "target"
+        );
+        for fixup in walked.iter() {
+            let (segment, section, address) =
+                self.locate(fixup.segment_index as usize, fixup.offset);
+            let (ty, target) = match fixup.target {
+                ChainedTarget::Rebase { target, high8 } => {
+                    let target = u64::from(high8) << 56 | target;
+                    ("rebase", format!("0x{:x}", target))
+                }
+                ChainedTarget::Bind { ordinal, addend } => {
+                    let target = match fixups.imports.get(ordinal as usize) {
+                        Some(import) => format!(
+                            "{}/{} + 0x{:x}",
+                            self.dylib_name(import.library_ordinal),
+                            import.name,
+                            import.addend + addend
+                        ),
+                        None => format!("import {} (out of range)", ordinal),
+                    };
+                    ("bind", target)
+                }
+            };
+            let auth = match fixup.auth {
+                Some(auth) => format!(
+                    " (auth key {:?}, diversity 0x{:x}{})",
+                    auth.key,
+                    auth.diversity,
+                    if auth.addr_div { ", addr" } else { "" }
+                ),
+                None => String::new(),
+            };
+            println!(
+                "{:<8} {:<18} 0x{:<16x} {:<8} {}{}",
+                segment, section, address, ty, target, auth
+            );
+        }
+    }
+
+    /// Returns the segment name, section name and address of a location.
+    fn locate(&self, segment_index: usize, offset: u64) -> (&str, &str, u64) {
+        let segment = match self.segments.get(segment_index) {
+            Some(segment) => segment,
+            None => return ("??", "??", offset),
+        };
+        let address = segment.vmaddr.wrapping_add(offset);
+        let section = segment
+            .sections
+            .iter()
+            .find(|(_, addr, size)| (*addr..addr + size).contains(&address))
+            .map(|(name, _, _)| *name)
+            .unwrap_or("??");
+        (segment.name, section, address)
+    }
+
+    fn dylib_name(&self, ordinal: i64) -> Cow<'a, str> {
+        match ordinal {
+            BIND_SPECIAL_DYLIB_SELF => Cow::Borrowed("this-image"),
+            BIND_SPECIAL_DYLIB_MAIN_EXECUTABLE => Cow::Borrowed("main-executable"),
+            BIND_SPECIAL_DYLIB_FLAT_LOOKUP => Cow::Borrowed("flat-namespace"),
+            BIND_SPECIAL_DYLIB_WEAK_LOOKUP => Cow::Borrowed("weak"),
+            n => match usize::try_from(n - 1).ok().and_then(|i| self.dylibs.get(i)) {
+                // "/usr/lib/libSystem.B.dylib" => "libSystem"
+                Some(path) => {
+                    let leaf = path.rsplit('/').next().unwrap_or(path);
+                    Cow::Borrowed(leaf.split('.').next().unwrap_or(leaf))
+                }
+                None => Cow::Owned(format!("ordinal {}", n)),
+            },
+        }
+    }
+}
+
+/// Prints exports like `dyldinfo -export`.
+pub fn print_exports(exports: &[Export], cmds: &[LoadCommand]) {
+    let printer = DyldInfoPrinter::new(cmds);
+    println!("export information (from trie):");
+    for export in exports.iter() {
+        let mut attrs = Vec::new();
+        match export.kind() {
+            ExportKind::Regular => {}
+            ExportKind::ThreadLocal => attrs.push(Cow::Borrowed("per-thread")),
+            ExportKind::Absolute => attrs.push(Cow::Borrowed("absolute")),
+            ExportKind::Unknown(n) => attrs.push(Cow::Owned(format!("kind {}", n))),
+        }
+        if export.is_weak() {
+            attrs.push(Cow::Borrowed("weak_def"));
+        }
+        let attrs = if attrs.is_empty() {
+            String::new()
+        } else {
+            format!("[{}] ", attrs.join(", "))
+        };
+
+        match &export.data {
+            ExportData::Regular { address } => {
+                println!("0x{:08x}  {}{}", address, attrs, export.name)
+            }
+            ExportData::StubAndResolver {
+                stub_address,
+                resolver_address,
+            } => println!(
+                "0x{:08x}  {}[resolver=0x{:08x}] {}",
+                stub_address, attrs, resolver_address, export.name
+            ),
+            ExportData::Reexport {
+                library_ordinal,
+                imported_name,
+            } => {
+                let dylib = printer.dylib_name(*library_ordinal as i64);
+                let imported_name = match imported_name.as_str() {
+                    "" => export.name.as_str(),
+                    name => name,
+                };
+                println!(
+                    "{:<10}  {}[re-export] {} ({} from {})",
+                    "", attrs, export.name, imported_name, dylib
+                );
+            }
+        }
+    }
+}
+
+fn format_rebase_type(rebase_type: RebaseType) -> Cow<'static, str> {
+    Cow::Borrowed(match rebase_type {
+        RebaseType::Pointer => "pointer",
+        RebaseType::TextAbsolute32 => "text abs32",
+        RebaseType::TextPcrel32 => "text rel32",
+        RebaseType::Unknown(n) => return Cow::Owned(format!("unknown ({})", n)),
+    })
+}
+
+fn format_bind_type(bind_type: BindType) -> Cow<'static, str> {
+    Cow::Borrowed(match bind_type {
+        BindType::Pointer => "pointer",
+        BindType::TextAbsolute32 => "text abs32",
+        BindType::TextPcrel32 => "text rel32",
+        BindType::Unknown(n) => return Cow::Owned(format!("unknown ({})", n)),
+    })
+}
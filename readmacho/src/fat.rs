@@ -0,0 +1,144 @@
+use crate::header::format_cpu_type;
+use atom_macho::{
+    fat,
+    header::{
+        CpuCapabilities, CpuSubTypeArm, CpuSubTypeArm64, CpuSubTypeArm64_32, CpuSubTypePowerPC,
+        CpuSubTypeX86, CpuSubTypeX86_64, CpuType,
+    },
+};
+use clap::Subcommand;
+use std::{fs, path::PathBuf};
+
+/// Operations on universal (fat) files, like `lipo`
+#[derive(Subcommand)]
+pub enum FatCommand {
+    /// Create a fat file from thin files
+    Create {
+        #[clap(required = true)]
+        inputs: Vec<PathBuf>,
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+    /// Write the thin file of ARCH
+    Thin {
+        input: PathBuf,
+        #[clap(short, long, parse(try_from_str = parse_arch))]
+        arch: CpuType,
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+    /// Write a fat file which only has the slices of ARCH
+    Extract {
+        input: PathBuf,
+        #[clap(short, long, required = true, parse(try_from_str = parse_arch))]
+        arch: Vec<CpuType>,
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+    /// Write a fat file without the slices of ARCH
+    Remove {
+        input: PathBuf,
+        #[clap(short, long, required = true, parse(try_from_str = parse_arch))]
+        arch: Vec<CpuType>,
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+    /// Write a fat file whose slice is replaced by a thin file of the same architecture
+    Replace {
+        input: PathBuf,
+        slice: PathBuf,
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+    /// Print the slices of a fat file
+    Info { input: PathBuf },
+}
+
+pub fn run(cmd: FatCommand) -> Result<(), atom_macho::Error> {
+    match cmd {
+        FatCommand::Create { inputs, output } => {
+            let slices = inputs.iter().map(fs::read).collect::<Result<Vec<_>, _>>()?;
+            let slices = slices.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            fs::write(output, fat::create(&slices)?)?;
+        }
+        FatCommand::Thin {
+            input,
+            arch,
+            output,
+        } => {
+            let data = fs::read(input)?;
+            fs::write(output, fat::thin(&data, arch)?)?;
+        }
+        FatCommand::Extract {
+            input,
+            arch,
+            output,
+        } => {
+            let data = fs::read(input)?;
+            fs::write(output, fat::extract(&data, &arch)?)?;
+        }
+        FatCommand::Remove {
+            input,
+            arch,
+            output,
+        } => {
+            let data = fs::read(input)?;
+            fs::write(output, fat::remove(&data, &arch)?)?;
+        }
+        FatCommand::Replace {
+            input,
+            slice,
+            output,
+        } => {
+            let data = fs::read(input)?;
+            let slice = fs::read(slice)?;
+            fs::write(output, fat::replace(&data, &slice)?)?;
+        }
+        FatCommand::Info { input } => {
+            let data = fs::read(input)?;
+            print_info(&data)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_info(data: &[u8]) -> Result<(), atom_macho::Error> {
+    println!("Fat Header");
+    println!("---------------------------");
+    println!(
+        "{:<20} | {:>10} | {:>10} | {:>5}",
+        "arch", "offset", "size", "align"
+    );
+    for (arch, _) in fat::slices(data)? {
+        println!(
+            "{:<20} | {:>10} | {:>10} | 2^{:<3}",
+            format_cpu_type(arch.cpu_type),
+            arch.offset,
+            arch.size,
+            arch.align
+        );
+    }
+    Ok(())
+}
+
+fn parse_arch(name: &str) -> Result<CpuType, String> {
+    let none = CpuCapabilities::NONE;
+    let cpu_type = match name {
+        "i386" => CpuType::X86(CpuSubTypeX86::All, none),
+        "x86_64" => CpuType::X86_64(CpuSubTypeX86_64::All, none),
+        "x86_64h" => CpuType::X86_64(CpuSubTypeX86_64::Haswell, none),
+        "arm" => CpuType::Arm(CpuSubTypeArm::All, none),
+        "armv6" => CpuType::Arm(CpuSubTypeArm::V6, none),
+        "armv7" => CpuType::Arm(CpuSubTypeArm::V7, none),
+        "armv7s" => CpuType::Arm(CpuSubTypeArm::V7S, none),
+        "armv7k" => CpuType::Arm(CpuSubTypeArm::V7K, none),
+        "arm64" => CpuType::Arm64(CpuSubTypeArm64::All, none),
+        "arm64e" => CpuType::Arm64(CpuSubTypeArm64::E, none),
+        "arm64_32" => CpuType::Arm64_32(CpuSubTypeArm64_32::V8, none),
+        "ppc" => CpuType::PowerPC(CpuSubTypePowerPC::All, none),
+        "ppc64" => CpuType::PowerPC64(CpuSubTypePowerPC::All, none),
+        _ => return Err(format!("unknown architecture {}", name)),
+    };
+    Ok(cpu_type)
+}
@@ -0,0 +1,38 @@
+use atom_macho::{
+    nlist::{NType, NTypeField},
+    view::MachOView,
+    Error,
+};
+
+/// Prints the addresses of LC_FUNCTION_STARTS like `dyldinfo -function_starts`, with the
+/// nearest preceding symbol in the symbol table.
+pub fn print_function_starts(starts: &[u64], view: &MachOView) -> Result<(), Error> {
+    // (address, name) of the symbols defined in sections, sorted by address.
+    let mut symbols = Vec::new();
+    if let Some(symtab) = view.symtab()? {
+        for sym in symtab.symbols() {
+            let sym = sym?;
+            if let NTypeField::Norm {
+                n_type: NType::Sect,
+                ..
+            } = sym.n_type
+            {
+                symbols.push((sym.n_value, sym.name));
+            }
+        }
+    }
+    symbols.sort_by_key(|(addr, _)| *addr);
+
+    println!("function starts:");
+    for addr in starts.iter() {
+        let idx = symbols.partition_point(|(sym_addr, _)| sym_addr <= addr);
+        match idx.checked_sub(1).map(|i| symbols[i]) {
+            Some((sym_addr, name)) if sym_addr == *addr => println!("0x{:08x}  {}", addr, name),
+            Some((sym_addr, name)) => {
+                println!("0x{:08x}  {}+0x{:x}", addr, name, addr - sym_addr)
+            }
+            None => println!("0x{:08x}", addr),
+        }
+    }
+    Ok(())
+}
@@ -1,21 +1,38 @@
 use atom_macho::header::*;
+use std::borrow::Cow;
 
-pub fn print_header(header: &Header64) {
+pub fn print_header(header: &Header) {
+    match header {
+        Header::Mach(h) => {
+            print_mach_header(h.cpu_type, h.file_type, h.n_cmds, h.size_of_cmds, &h.flags)
+        }
+        Header::Mach32(h) => {
+            print_mach_header(h.cpu_type, h.file_type, h.n_cmds, h.size_of_cmds, &h.flags)
+        }
+        Header::Fat(_) | Header::Fat64(_) => unreachable!(),
+    }
+}
+
+fn print_mach_header(
+    cpu_type: CpuType,
+    file_type: FileType,
+    n_cmds: u32,
+    size_of_cmds: u32,
+    flags: &Flags,
+) {
     println!("Header");
     println!("---------------------------");
-    print("cpu type", format_cpu_type(header.cpu_type));
-    print("file type", format_file_type(header.file_type));
-    print("num commands", header.n_cmds);
-    print("size of commands", header.size_of_cmds);
-    if !header.flags.is_empty() {
-        let mut flags = header.flags.iter();
-
-        let flag1 = flags.next().unwrap();
-        print("flags", format_flag(flag1));
-
-        for flag in flags {
-            print("", format_flag(flag));
-        }
+    print("cpu type", format_cpu_type(cpu_type));
+    print("file type", format_file_type(file_type));
+    print("num commands", n_cmds);
+    print("size of commands", size_of_cmds);
+    let mut key = "flags";
+    for flag in flags.iter() {
+        print(key, format_flag(flag));
+        key = "";
+    }
+    if flags.unknown() != 0 {
+        print(key, format!("unknown ({:#x})", flags.unknown()));
     }
 }
 
@@ -23,17 +40,44 @@ fn print(key: &str, val: impl std::fmt::Display) {
     println!("{:<20} |  {:<20}", key, val);
 }
 
-fn format_cpu_type(cpu: CpuType) -> &'static str {
+pub fn format_cpu_type(cpu: CpuType) -> String {
+    let name = match cpu {
+        CpuType::X86(CpuSubTypeX86::All, _) => "i386".to_string(),
+        CpuType::X86(sub, _) => format!("i386 ({:?})", sub),
+        CpuType::X86_64(CpuSubTypeX86_64::All, _) => "x86_64".to_string(),
+        CpuType::X86_64(CpuSubTypeX86_64::Haswell, _) => "x86_64h".to_string(),
+        CpuType::Arm(CpuSubTypeArm::All, _) => "arm".to_string(),
+        CpuType::Arm(sub, _) => format!("arm{:?}", sub).to_lowercase(),
+        CpuType::Arm64(CpuSubTypeArm64::E, caps) if caps.ptrauth_abi() => {
+            format!("arm64e (ptrauth abi v{})", caps.ptrauth_version())
+        }
+        CpuType::Arm64(CpuSubTypeArm64::E, _) => "arm64e".to_string(),
+        CpuType::Arm64(CpuSubTypeArm64::V8, _) => "arm64v8".to_string(),
+        CpuType::Arm64(CpuSubTypeArm64::All, _) => "arm64".to_string(),
+        CpuType::Arm64_32(_, _) => "arm64_32".to_string(),
+        CpuType::PowerPC(CpuSubTypePowerPC::All, _) => "ppc".to_string(),
+        CpuType::PowerPC(sub, _) => format!("ppc ({:?})", sub),
+        CpuType::PowerPC64(CpuSubTypePowerPC::All, _) => "ppc64".to_string(),
+        CpuType::PowerPC64(sub, _) => format!("ppc64 ({:?})", sub),
+        CpuType::Unknown(cpu_type, cpu_subtype) => {
+            return format!("unknown ({:#x}, {:#x})", cpu_type, cpu_subtype)
+        }
+    };
+
     match cpu {
-        CpuType::X86(CpuSubTypeX86::All) => "x86",
-        CpuType::X86_64(CpuSubTypeX86_64::All) => "x86_64",
+        CpuType::X86(_, caps) | CpuType::X86_64(_, caps) | CpuType::PowerPC64(_, caps)
+            if caps.lib64() =>
+        {
+            format!("{} (lib64)", name)
+        }
+        _ => name,
     }
 }
 
-fn format_file_type(file: FileType) -> &'static str {
+fn format_file_type(file: FileType) -> Cow<'static, str> {
     use FileType::*;
 
-    match file {
+    Cow::Borrowed(match file {
         Object => "object",
         Execute => "executable",
         FVMLib => "fvmlib",
@@ -42,8 +86,12 @@ fn format_file_type(file: FileType) -> &'static str {
         Dylib => "dylib",
         Dylinker => "dylinker",
         Bundle => "bundle",
+        DylibStub => "dylib stub",
         Dsym => "dsym",
-    }
+        KextBundle => "kext bundle",
+        Fileset => "fileset",
+        Unknown(n) => return Cow::Owned(format!("unknown ({:#x})", n)),
+    })
 }
 
 fn format_flag(flag: Flag) -> &'static str {
@@ -56,6 +104,7 @@ fn format_flag(flag: Flag) -> &'static str {
         BindAtLoad => "bind_at_load",
         PreBound => "pre_bound",
         SplitSegs => "split_segs",
+        LazyInit => "lazy_init",
         TwoLevel => "two_level",
         ForceFlat => "force_flat",
         NoMultiDefs => "no_multi_defs",
@@ -64,7 +113,19 @@ fn format_flag(flag: Flag) -> &'static str {
         AllModsBound => "all_mods_bound",
         SubsectionsViaSymbols => "subsections_via_symbols",
         Canonical => "canonical",
+        WeakDefines => "weak_defines",
+        BindsToWeak => "binds_to_weak",
+        AllowStackExecution => "allow_stack_execution",
+        RootSafe => "root_safe",
+        SetuidSafe => "setuid_safe",
+        NoReexportedDylibs => "no_reexported_dylibs",
         Pie => "pie",
+        DeadStrippableDylib => "dead_strippable_dylib",
         HasTlvDescriptors => "has_tlv_descriptors",
+        NoHeapExecution => "no_heap_execution",
+        AppExtensionSafe => "app_extension_safe",
+        NlistOutofsyncWithDyldinfo => "nlist_outofsync_with_dyldinfo",
+        SimSupport => "sim_support",
+        DylibInCache => "dylib_in_cache",
     }
 }
@@ -1,20 +1,29 @@
+#![allow(clippy::print_literal)]
+
 mod cmd;
 mod cmds;
+mod codesign;
+mod dyld_info;
+mod fat;
+mod function_starts;
 mod header;
+#[allow(dead_code)]
 mod hex;
-mod macho;
 
 use atom_macho::{
-    header::{Header, Header64, CpuType, CpuSubTypeX86_64},
+    header::{CpuCapabilities, CpuSubTypeArm64, CpuSubTypeX86_64, CpuType, Header},
     load_command::LoadCommand,
+    view::MachOView,
 };
 use clap::Parser;
 use std::fs::File;
 use std::io::{Cursor, Read as _};
 
 #[derive(Parser)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    file: std::path::PathBuf,
+    #[clap(required = true)]
+    file: Option<std::path::PathBuf>,
 
     /// Print header
     #[clap(short = 'H')]
@@ -24,55 +33,209 @@ struct Args {
     #[clap(short = 'L')]
     load_commands: bool,
 
+    /// Print shared libraries used, like `otool -L`
+    #[clap(short = 'D')]
+    dylibs: bool,
+
     /// Print overview of the load command.
     #[clap(short = 'l', name = "LOAD_COMMAND_IDX")]
     load_command: Vec<usize>,
+
+    /// Print rebase information of LC_DYLD_INFO
+    #[clap(long)]
+    rebase: bool,
+
+    /// Print bind information of LC_DYLD_INFO
+    #[clap(long)]
+    bind: bool,
+
+    /// Print weak bind information of LC_DYLD_INFO
+    #[clap(long)]
+    weak_bind: bool,
+
+    /// Print lazy bind information of LC_DYLD_INFO
+    #[clap(long)]
+    lazy_bind: bool,
+
+    /// Print exported symbols in the export trie
+    #[clap(long)]
+    exports: bool,
+
+    /// Print fixups in the chains of LC_DYLD_CHAINED_FIXUPS
+    #[clap(long)]
+    fixups: bool,
+
+    /// Print function start addresses of LC_FUNCTION_STARTS with the nearest symbols
+    #[clap(long)]
+    function_starts: bool,
+
+    /// Print the code signature of LC_CODE_SIGNATURE
+    #[clap(long)]
+    codesign: bool,
+
+    /// Verify page hashes and special slots of the code signature, and exit with 1 on mismatch
+    #[clap(long)]
+    verify: bool,
+
+    /// _CodeSignature/CodeResources of the bundle, to verify the resource dir slot
+    #[clap(long, requires = "verify")]
+    code_resources: Option<std::path::PathBuf>,
+
+    #[clap(subcommand)]
+    fat: Option<fat::FatCommand>,
 }
 
-fn main() {
+fn main() -> Result<(), atom_macho::Error> {
     let args = Args::parse();
 
+    if let Some(fat_cmd) = args.fat {
+        return fat::run(fat_cmd);
+    }
+
     let mut buf = {
-        let mut file = File::open(args.file.clone()).expect("file path is invalid");
+        let mut file = File::open(args.file.expect("FILE is required"))?;
         let mut vec = Vec::new();
-        file.read_to_end(&mut vec).unwrap();
+        file.read_to_end(&mut vec)?;
         Cursor::new(vec)
     };
 
-    let header = Header::read_from(&mut buf);
-
-    // TODO: detect machine cpu
-    let cpu_type = CpuType::X86_64(CpuSubTypeX86_64::All);
+    let header = Header::read_from(&mut buf)?;
 
+    // file offset of the mach header, which is not 0 in fat files.
+    let mut slice_offset = 0;
     let mach_header = match header {
-        Header::Mach(h) => h,
-        Header::Fat(fat_header) => {
-            if let Some(fat_arch) = fat_header.fat_archs.iter().find(|fat_arch| fat_arch.cpu_type == cpu_type) {
-                buf.set_position(fat_arch.offset as u64);
-                Header64::read_from(&mut buf)
-            } else {
-                panic!("Header for {:?} is not found", cpu_type);
-            }
+        Header::Fat(_) | Header::Fat64(_) => {
+            // prefer the slice of the host cpu, and fall back to the first one.
+            let fat_archs = atom_macho::fat::slices(buf.get_ref())?
+                .into_iter()
+                .map(|(fat_arch, _)| fat_arch)
+                .collect::<Vec<_>>();
+            let fat_arch = fat_archs
+                .iter()
+                .find(|fat_arch| fat_arch.cpu_type.without_capabilities() == host_cpu_type())
+                .or_else(|| fat_archs.first())
+                .expect("fat file has no slice");
+            slice_offset = fat_arch.offset;
+            buf.set_position(slice_offset);
+            Header::read_from(&mut buf)?
         }
+        header => header,
+    };
+
+    let (n_cmds, endian, cpu_type) = match &mach_header {
+        Header::Mach(h) => (h.n_cmds, h.endian(), h.cpu_type),
+        Header::Mach32(h) => (h.n_cmds, h.endian(), h.cpu_type),
+        Header::Fat(_) | Header::Fat64(_) => panic!("nested fat header"),
     };
 
     if args.header {
-        println!("");
+        println!();
         header::print_header(&mach_header);
     }
 
     // print list of load commands
-    let load_commands = (0..mach_header.n_cmds)
-        .map(|_| LoadCommand::read_from_in(&mut buf, mach_header.endian()))
-        .collect::<Vec<LoadCommand>>();
+    let load_commands = (0..n_cmds)
+        .map(|_| LoadCommand::read_from_in(&mut buf, endian, cpu_type))
+        .collect::<Result<Vec<LoadCommand>, _>>()?;
     if args.load_commands {
-        println!("");
+        println!();
         cmds::print_cmds(&load_commands);
     }
 
+    if args.dylibs {
+        println!();
+        cmds::print_dylibs(&load_commands);
+    }
+
+    if args.rebase || args.bind || args.weak_bind || args.lazy_bind {
+        let slice = &buf.get_ref()[slice_offset as usize..];
+        let info = MachOView::parse(slice)?
+            .dyld_info()?
+            .expect("no LC_DYLD_INFO command");
+        let printer = dyld_info::DyldInfoPrinter::new(&load_commands);
+        if args.rebase {
+            println!();
+            printer.print_rebases(&info)?;
+        }
+        if args.bind {
+            println!();
+            printer.print_binds(info.binds()?, "bind information");
+        }
+        if args.weak_bind {
+            println!();
+            printer.print_binds(info.weak_binds()?, "weak binding information");
+        }
+        if args.lazy_bind {
+            println!();
+            printer.print_binds(
+                info.lazy_binds()?,
+                "lazy binding information (from lazy_bind part of dyld info)",
+            );
+        }
+    }
+
+    if args.exports {
+        let slice = &buf.get_ref()[slice_offset as usize..];
+        println!();
+        dyld_info::print_exports(&MachOView::parse(slice)?.exports()?, &load_commands);
+    }
+
+    if args.fixups {
+        let view = MachOView::parse(&buf.get_ref()[slice_offset as usize..])?;
+        let fixups = view
+            .chained_fixups()?
+            .expect("no LC_DYLD_CHAINED_FIXUPS command");
+        println!();
+        dyld_info::DyldInfoPrinter::new(&load_commands)
+            .print_chained_fixups(&fixups, &fixups.walk(&view)?);
+    }
+
+    if args.function_starts {
+        let view = MachOView::parse(&buf.get_ref()[slice_offset as usize..])?;
+        let starts = view
+            .function_starts()?
+            .expect("no LC_FUNCTION_STARTS command");
+        println!();
+        function_starts::print_function_starts(&starts, &view)?;
+    }
+
+    if args.codesign {
+        let view = MachOView::parse(&buf.get_ref()[slice_offset as usize..])?;
+        let sig = view
+            .code_signature()?
+            .expect("no LC_CODE_SIGNATURE command");
+        println!();
+        codesign::print_code_signature(&sig);
+    }
+
+    if args.verify {
+        let view = MachOView::parse(&buf.get_ref()[slice_offset as usize..])?;
+        let code_resources = match &args.code_resources {
+            Some(path) => Some(std::fs::read(path)?),
+            None => None,
+        };
+        let result = atom_macho::code_signature::verify(&view, code_resources.as_deref())?
+            .expect("no LC_CODE_SIGNATURE command");
+        println!();
+        codesign::print_verification(&result);
+        if !result.is_valid() {
+            std::process::exit(1);
+        }
+    }
+
     // print specified load command
     for cmd_idx in args.load_command.iter() {
-        println!("");
+        println!();
         cmd::print_cmd(&load_commands, *cmd_idx);
     }
+
+    Ok(())
+}
+
+fn host_cpu_type() -> CpuType {
+    if cfg!(target_arch = "aarch64") {
+        CpuType::Arm64(CpuSubTypeArm64::All, CpuCapabilities::NONE)
+    } else {
+        CpuType::X86_64(CpuSubTypeX86_64::All, CpuCapabilities::NONE)
+    }
 }
@@ -0,0 +1,328 @@
+use super::{
+    section_header, segment_command,
+    symtab::{assign_symbol_values, symtab_command, SymbolDef, SymbolTable},
+    SectionId,
+};
+use crate::{
+    error::{Error, Result},
+    file::{MachOFile, SectionData},
+    header::{CpuCapabilities, CpuSubTypeX86_64, CpuType, FileType, Flag, Flags, Header64, Magic},
+    load_command::{
+        segment64::{SectionAttr, SectionAttrs, SectionType},
+        unix_thread::{StateX86_64, ThreadState},
+        LoadCommand, Section64, UnixThreadCommand,
+    },
+};
+use std::{collections::HashMap, io::Write};
+
+const VM_PROT_READ: i32 = 0x1;
+const VM_PROT_WRITE: i32 = 0x2;
+const VM_PROT_EXECUTE: i32 = 0x4;
+
+/// Builds a static x86_64 executable (`MH_EXECUTE`) which starts from `LC_UNIXTHREAD`.
+///
+/// The file has `__PAGEZERO`, `__TEXT`, `__DATA` (only if it has sections) and `__LINKEDIT`
+/// segments. Since nothing is linked dynamically, code must not refer to undefined symbols.
+#[derive(Debug, Clone)]
+pub struct ExecutableBuilder {
+    pagezero_size: u64,
+    sections: Vec<SectionDef>,
+    symbols: Vec<SymbolDef>,
+    symbol_ids: HashMap<String, usize>,
+    entry: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct SectionDef {
+    is_text: bool,
+    header: Section64,
+    data: Vec<u8>,
+}
+
+impl ExecutableBuilder {
+    /// Size of `__PAGEZERO` by default. `__TEXT` starts right after it.
+    pub const DEFAULT_PAGEZERO_SIZE: u64 = 0x1_0000_0000;
+
+    pub fn new() -> Self {
+        ExecutableBuilder {
+            pagezero_size: Self::DEFAULT_PAGEZERO_SIZE,
+            sections: Vec::new(),
+            symbols: Vec::new(),
+            symbol_ids: HashMap::new(),
+            entry: None,
+        }
+    }
+
+    /// Sets the size of `__PAGEZERO`, which is also the address of `__TEXT`.
+    /// It must be a multiple of the page size.
+    pub fn pagezero_size(&mut self, size: u64) -> &mut Self {
+        self.pagezero_size = size;
+        self
+    }
+
+    /// Adds a section of machine instructions to `__TEXT`. `align` is a power of 2.
+    pub fn add_text_section(&mut self, sectname: &str, align: u32, data: Vec<u8>) -> SectionId {
+        let mut attrs = SectionAttrs::new();
+        attrs.push(SectionAttr::PureInstructions);
+        attrs.push(SectionAttr::SomeInstructions);
+        let flags = (attrs, SectionType::Regular);
+        self.push_section(true, sectname, flags, align, data, 0)
+    }
+
+    /// Adds a section of read-only data, such as `__const` or `__cstring`, to `__TEXT`.
+    pub fn add_const_section(&mut self, sectname: &str, align: u32, data: Vec<u8>) -> SectionId {
+        let flags = (SectionAttrs::new(), SectionType::Regular);
+        self.push_section(true, sectname, flags, align, data, 0)
+    }
+
+    /// Adds a section of writable data to `__DATA`.
+    pub fn add_data_section(&mut self, sectname: &str, align: u32, data: Vec<u8>) -> SectionId {
+        let flags = (SectionAttrs::new(), SectionType::Regular);
+        self.push_section(false, sectname, flags, align, data, 0)
+    }
+
+    /// Adds a zero-fill section, such as `__bss`, to `__DATA`.
+    pub fn add_zerofill_section(&mut self, sectname: &str, align: u32, size: u64) -> SectionId {
+        let flags = (SectionAttrs::new(), SectionType::Zerofill);
+        self.push_section(false, sectname, flags, align, Vec::new(), size)
+    }
+
+    fn push_section(
+        &mut self,
+        is_text: bool,
+        sectname: &str,
+        flags: (SectionAttrs, SectionType),
+        align: u32,
+        data: Vec<u8>,
+        size: u64,
+    ) -> SectionId {
+        let segname = if is_text { "__TEXT" } else { "__DATA" };
+        self.sections.push(SectionDef {
+            is_text,
+            header: section_header(segname, sectname, flags, align, size),
+            data,
+        });
+        SectionId(self.sections.len() - 1)
+    }
+
+    /// Defines `name` at `offset` bytes from the start of `section`. Fails if `name` is already
+    /// defined.
+    pub fn define_symbol(
+        &mut self,
+        name: &str,
+        section: SectionId,
+        offset: u64,
+        external: bool,
+    ) -> Result<()> {
+        if self.symbol_ids.contains_key(name) {
+            return Err(Error::DuplicateSymbol {
+                name: name.to_string(),
+            });
+        }
+        self.symbol_ids.insert(name.to_string(), self.symbols.len());
+        self.symbols.push(SymbolDef {
+            name: name.to_string(),
+            def: Some((section.0, offset)),
+            external,
+        });
+        Ok(())
+    }
+
+    /// Sets the symbol which `__rip` of the initial thread points at.
+    pub fn entry_symbol(&mut self, name: &str) -> &mut Self {
+        self.entry = Some(name.to_string());
+        self
+    }
+
+    /// Builds the executable. Fails if a symbol is defined in a section after the 255th, which
+    /// the symbol table cannot refer to.
+    ///
+    /// # Panics
+    /// Panics if the entry symbol is not set or not defined.
+    pub fn build(&self) -> Result<MachOFile> {
+        let entry = self.entry.as_ref().expect("entry symbol is not set");
+        let entry_idx = *self
+            .symbol_ids
+            .get(entry)
+            .unwrap_or_else(|| panic!("entry symbol {} is not defined", entry));
+
+        // sections are stored in the file in the order of __TEXT and __DATA.
+        let order = (0..self.sections.len())
+            .filter(|i| self.sections[*i].is_text)
+            .chain((0..self.sections.len()).filter(|i| !self.sections[*i].is_text))
+            .collect::<Vec<_>>();
+        let mut section_index = vec![0; self.sections.len()];
+        for (idx, i) in order.iter().enumerate() {
+            section_index[*i] = idx;
+        }
+
+        let symbols = self
+            .symbols
+            .iter()
+            .map(|symbol| SymbolDef {
+                def: symbol
+                    .def
+                    .map(|(sect, offset)| (section_index[sect], offset)),
+                ..symbol.clone()
+            })
+            .collect::<Vec<_>>();
+        let table = SymbolTable::new(&symbols)?;
+
+        let headers_of = |is_text: bool| {
+            order
+                .iter()
+                .map(|i| &self.sections[*i])
+                .filter(|sect| sect.is_text == is_text)
+                .map(|sect| sect.header.clone())
+                .collect::<Vec<_>>()
+        };
+        let text_sects = headers_of(true);
+        let data_sects = headers_of(false);
+
+        let mut load_commands = vec![
+            LoadCommand::Segment64(
+                segment_command("__PAGEZERO", 0, self.pagezero_size, 0),
+                Vec::new(),
+            ),
+            LoadCommand::Segment64(
+                segment_command("__TEXT", 0, 0, VM_PROT_READ | VM_PROT_EXECUTE),
+                text_sects,
+            ),
+        ];
+        if !data_sects.is_empty() {
+            load_commands.push(LoadCommand::Segment64(
+                segment_command("__DATA", 0, 0, VM_PROT_READ | VM_PROT_WRITE),
+                data_sects,
+            ));
+        }
+        load_commands.extend([
+            LoadCommand::Segment64(
+                segment_command("__LINKEDIT", 0, 0, VM_PROT_READ),
+                Vec::new(),
+            ),
+            symtab_command(),
+            LoadCommand::Dysymtab(table.dysymtab),
+            LoadCommand::UnixThread(UnixThreadCommand::x86_64(StateX86_64::default())),
+        ]);
+
+        let mut flags = Flags::new();
+        flags.push(Flag::NoUndefs);
+
+        let mut file = MachOFile {
+            header: Header64 {
+                magic: Magic::Magic64,
+                cpu_type: CpuType::X86_64(CpuSubTypeX86_64::All, CpuCapabilities::NONE),
+                file_type: FileType::Execute,
+                n_cmds: 0,
+                size_of_cmds: 0,
+                flags,
+                reserved: 0,
+            },
+            load_commands,
+            sections: order
+                .iter()
+                .map(|i| SectionData {
+                    data: self.sections[*i].data.clone(),
+                    relocs: Vec::new(),
+                })
+                .collect(),
+            symbols: table.symbols,
+            string_table: table.string_table,
+            extra: Vec::new(),
+        };
+        file.layout();
+        assign_symbol_values(&mut file, &symbols, &table.order);
+
+        let entry_addr = file.symbols[table.index[entry_idx] as usize].n_value;
+        for lc in file.load_commands.iter_mut() {
+            if let LoadCommand::UnixThread(cmd) = lc {
+                for state in cmd.states.iter_mut() {
+                    if let ThreadState::X86_64(state) = state {
+                        state.__rip = entry_addr;
+                    }
+                }
+            }
+        }
+
+        Ok(file)
+    }
+
+    /// Builds the executable and writes it into `write`.
+    pub fn write<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.build()?.write(write)
+    }
+}
+
+impl Default for ExecutableBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_executable() {
+        let mut builder = ExecutableBuilder::new();
+
+        // mov eax, 0x2000001; xor edi, edi; syscall
+        let text = builder.add_text_section(
+            "__text",
+            4,
+            vec![0xb8, 0x01, 0x00, 0x00, 0x02, 0x31, 0xff, 0x0f, 0x05],
+        );
+        let data = builder.add_data_section("__data", 3, vec![1, 2, 3, 4]);
+        builder.add_zerofill_section("__bss", 3, 0x100);
+        builder.define_symbol("start", text, 0, true).unwrap();
+        builder.define_symbol("_value", data, 0, false).unwrap();
+        builder.entry_symbol("start");
+
+        let mut buf = Vec::new();
+        builder.write(&mut buf).unwrap();
+        let file = MachOFile::parse(&buf).unwrap();
+
+        assert_eq!(file.header.file_type, FileType::Execute);
+
+        let segs = file
+            .load_commands
+            .iter()
+            .filter_map(|lc| match lc {
+                LoadCommand::Segment64(seg, _) => Some(seg),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let names = segs
+            .iter()
+            .map(|seg| seg.segname.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["__PAGEZERO", "__TEXT", "__DATA", "__LINKEDIT"]);
+        assert_eq!((segs[0].vmsize, segs[0].initprot), (0x1_0000_0000, 0));
+        assert_eq!((segs[1].vmaddr, segs[1].fileoff), (0x1_0000_0000, 0));
+        assert_eq!(segs[1].initprot, VM_PROT_READ | VM_PROT_EXECUTE);
+        assert_eq!(segs[2].initprot, VM_PROT_READ | VM_PROT_WRITE);
+        assert_eq!(segs[3].fileoff + segs[3].filesize, buf.len() as u64);
+
+        let text_addr = file.sections().next().unwrap().0.addr;
+        let rip = file
+            .load_commands
+            .iter()
+            .find_map(|lc| match lc {
+                LoadCommand::UnixThread(cmd) => match cmd.states.as_slice() {
+                    [ThreadState::X86_64(state)] => Some(state.__rip),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(rip, text_addr);
+
+        // local symbols come first
+        assert_eq!(
+            file.string_table.get(file.symbols[0].n_strx as usize),
+            Some("_value")
+        );
+        assert_eq!(file.symbols[1].n_value, text_addr);
+    }
+}
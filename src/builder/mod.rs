@@ -0,0 +1,57 @@
+//! High level builders which produce a complete `MachOFile`.
+
+mod executable;
+mod object;
+mod symtab;
+
+pub use self::{
+    executable::ExecutableBuilder,
+    object::{ObjectBuilder, SectionId, SymbolId},
+};
+
+use crate::load_command::{
+    segment64::{SectionAttrs, SectionType},
+    Section64, SegmentCommand64,
+};
+
+/// A section header whose offsets and address are filled by `MachOFile::layout`.
+fn section_header(
+    segname: &str,
+    sectname: &str,
+    flags: (SectionAttrs, SectionType),
+    align: u32,
+    size: u64,
+) -> Section64 {
+    Section64 {
+        sectname: sectname.to_string(),
+        segname: segname.to_string(),
+        addr: 0,
+        size,
+        offset: 0,
+        align,
+        reloff: 0,
+        nreloc: 0,
+        flags,
+        reserved1: 0,
+        reserved2: 0,
+        reserved3: 0,
+    }
+}
+
+/// A segment command whose sizes and offsets are filled by `MachOFile::layout`.
+/// `prot` is used for both `maxprot` and `initprot`.
+fn segment_command(segname: &str, vmaddr: u64, vmsize: u64, prot: i32) -> SegmentCommand64 {
+    SegmentCommand64 {
+        cmd: SegmentCommand64::TYPE,
+        cmdsize: 0,
+        segname: segname.to_string(),
+        vmaddr,
+        vmsize,
+        fileoff: 0,
+        filesize: 0,
+        maxprot: prot,
+        initprot: prot,
+        nsects: 0,
+        flags: 0,
+    }
+}
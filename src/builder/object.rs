@@ -0,0 +1,321 @@
+use super::{
+    section_header, segment_command,
+    symtab::{assign_symbol_values, symtab_command, SymbolDef, SymbolTable},
+};
+use crate::{
+    error::{Error, Result},
+    file::{MachOFile, SectionData},
+    header::{CpuCapabilities, CpuSubTypeX86_64, CpuType, FileType, Flags, Header64, Magic},
+    load_command::{
+        segment64::{SectionAttrs, SectionType},
+        LoadCommand, Section64,
+    },
+    reloc::{RelocLength, RelocationInfo, X86_64RelocType},
+};
+use std::{collections::HashMap, io::Write};
+
+/// Builds a relocatable x86_64 object file (`MH_OBJECT`).
+///
+/// Only x86_64 is supported, because relocations are `X86_64RelocType`.
+#[derive(Debug, Clone)]
+pub struct ObjectBuilder {
+    sections: Vec<SectionDef>,
+    symbols: Vec<SymbolDef>,
+    symbol_ids: HashMap<String, SymbolId>,
+}
+
+/// A section added by `ObjectBuilder::add_section`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SectionId(pub(super) usize);
+
+/// A symbol defined or declared in `ObjectBuilder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SymbolId(usize);
+
+#[derive(Debug, Clone)]
+struct SectionDef {
+    header: Section64,
+    data: Vec<u8>,
+    relocs: Vec<(u32, SymbolId, X86_64RelocType)>,
+}
+
+impl ObjectBuilder {
+    pub fn new() -> Self {
+        ObjectBuilder {
+            sections: Vec::new(),
+            symbols: Vec::new(),
+            symbol_ids: HashMap::new(),
+        }
+    }
+
+    /// Adds a regular section. `align` is a power of 2.
+    pub fn add_section(
+        &mut self,
+        segname: &str,
+        sectname: &str,
+        attrs: SectionAttrs,
+        align: u32,
+        data: Vec<u8>,
+    ) -> SectionId {
+        self.push_section(
+            segname,
+            sectname,
+            (attrs, SectionType::Regular),
+            align,
+            data,
+            0,
+        )
+    }
+
+    /// Adds a zero-fill section such as `__DATA,__bss`, which occupies no space in the file.
+    pub fn add_zerofill_section(
+        &mut self,
+        segname: &str,
+        sectname: &str,
+        align: u32,
+        size: u64,
+    ) -> SectionId {
+        let flags = (SectionAttrs::new(), SectionType::Zerofill);
+        self.push_section(segname, sectname, flags, align, Vec::new(), size)
+    }
+
+    fn push_section(
+        &mut self,
+        segname: &str,
+        sectname: &str,
+        flags: (SectionAttrs, SectionType),
+        align: u32,
+        data: Vec<u8>,
+        size: u64,
+    ) -> SectionId {
+        let header = section_header(segname, sectname, flags, align, size);
+        self.sections.push(SectionDef {
+            header,
+            data,
+            relocs: Vec::new(),
+        });
+        SectionId(self.sections.len() - 1)
+    }
+
+    /// Defines `name` at `offset` bytes from the start of `section`.
+    ///
+    /// If `name` was declared by `declare_undefined`, that symbol becomes defined and the same
+    /// `SymbolId` is returned. Fails if `name` is already defined.
+    pub fn define_symbol(
+        &mut self,
+        name: &str,
+        section: SectionId,
+        offset: u64,
+        external: bool,
+    ) -> Result<SymbolId> {
+        let id = self.symbol_id(name);
+        let symbol = &mut self.symbols[id.0];
+        if symbol.def.is_some() {
+            return Err(Error::DuplicateSymbol {
+                name: name.to_string(),
+            });
+        }
+        symbol.def = Some((section.0, offset));
+        symbol.external = external;
+        Ok(id)
+    }
+
+    /// Declares an external symbol which is defined in another object.
+    /// Returns the existing `SymbolId` if `name` is already known.
+    pub fn declare_undefined(&mut self, name: &str) -> SymbolId {
+        self.symbol_id(name)
+    }
+
+    fn symbol_id(&mut self, name: &str) -> SymbolId {
+        if let Some(id) = self.symbol_ids.get(name) {
+            return *id;
+        }
+
+        let id = SymbolId(self.symbols.len());
+        self.symbols.push(SymbolDef {
+            name: name.to_string(),
+            def: None,
+            external: true,
+        });
+        self.symbol_ids.insert(name.to_string(), id);
+        id
+    }
+
+    /// Adds a relocation at `offset` bytes in `section` which refers to `symbol`.
+    ///
+    /// `Unsigned` and `Subtractor` relocations are 8 bytes absolute values, and the others are
+    /// 4 bytes pc-relative displacements.
+    pub fn add_relocation(
+        &mut self,
+        section: SectionId,
+        offset: u32,
+        symbol: SymbolId,
+        r_type: X86_64RelocType,
+    ) {
+        self.sections[section.0]
+            .relocs
+            .push((offset, symbol, r_type));
+    }
+
+    /// Builds the object file.
+    ///
+    /// The symbol table is ordered as local symbols, defined external symbols and undefined
+    /// symbols, and the latter two are sorted by name, as `DysymtabCommand` requires.
+    /// Fails if a symbol cannot be written, as in `ExecutableBuilder::build`.
+    pub fn build(&self) -> Result<MachOFile> {
+        let table = SymbolTable::new(&self.symbols)?;
+
+        let sections = self
+            .sections
+            .iter()
+            .map(|sect| SectionData {
+                data: sect.data.clone(),
+                relocs: sect
+                    .relocs
+                    .iter()
+                    .map(|(offset, symbol, r_type)| {
+                        let (r_pcrel, r_length) = match r_type {
+                            X86_64RelocType::Unsigned | X86_64RelocType::Subtractor => {
+                                (false, RelocLength::Quad)
+                            }
+                            _ => (true, RelocLength::Long),
+                        };
+                        RelocationInfo {
+                            r_address: *offset as i32,
+                            r_symbolnum: table.index[symbol.0],
+                            r_pcrel,
+                            r_length,
+                            r_extern: true,
+                            r_type: r_type.to_u8(),
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let segment = segment_command("", 0, 0, 7);
+        let section_headers = self.sections.iter().map(|s| s.header.clone()).collect();
+        let mut file = MachOFile {
+            header: Header64 {
+                magic: Magic::Magic64,
+                cpu_type: CpuType::X86_64(CpuSubTypeX86_64::All, CpuCapabilities::NONE),
+                file_type: FileType::Object,
+                n_cmds: 0,
+                size_of_cmds: 0,
+                flags: Flags::new(),
+                reserved: 0,
+            },
+            load_commands: vec![
+                LoadCommand::Segment64(segment, section_headers),
+                symtab_command(),
+                LoadCommand::Dysymtab(table.dysymtab),
+            ],
+            sections,
+            symbols: table.symbols,
+            string_table: table.string_table,
+            extra: Vec::new(),
+        };
+        file.layout();
+
+        assign_symbol_values(&mut file, &self.symbols, &table.order);
+
+        Ok(file)
+    }
+
+    /// Builds the object file and writes it into `write`.
+    pub fn write<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.build()?.write(write)
+    }
+}
+
+impl Default for ObjectBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_object() {
+        let mut builder = ObjectBuilder::new();
+
+        // call _foo; call _bar; ret
+        let text = builder.add_section(
+            "__TEXT",
+            "__text",
+            SectionAttrs::new(),
+            4,
+            vec![0xe8, 0, 0, 0, 0, 0xe8, 0, 0, 0, 0, 0xc3],
+        );
+        let data = builder.add_section("__DATA", "__data", SectionAttrs::new(), 3, vec![0; 8]);
+        builder.add_zerofill_section("__DATA", "__bss", 3, 0x10);
+
+        let foo = builder.declare_undefined("_foo");
+        builder.add_relocation(text, 1, foo, X86_64RelocType::Branch);
+        let bar = builder.declare_undefined("_bar");
+        builder.add_relocation(text, 6, bar, X86_64RelocType::Branch);
+        let main = builder.define_symbol("_main", text, 0, true).unwrap();
+        builder.add_relocation(data, 0, main, X86_64RelocType::Unsigned);
+        builder.define_symbol("_local", data, 0, false).unwrap();
+        // defining a declared symbol keeps its id
+        assert_eq!(builder.define_symbol("_bar", text, 10, true).unwrap(), bar);
+        assert!(matches!(
+            builder.define_symbol("_main", data, 0, true),
+            Err(Error::DuplicateSymbol { name }) if name == "_main"
+        ));
+
+        let mut buf = Vec::new();
+        builder.write(&mut buf).unwrap();
+        let file = MachOFile::parse(&buf).unwrap();
+
+        let names = file
+            .symbols
+            .iter()
+            .map(|sym| file.string_table.get(sym.n_strx as usize).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["_local", "_bar", "_main", "_foo"]);
+
+        let dysymtab = file
+            .load_commands
+            .iter()
+            .find_map(|lc| match lc {
+                LoadCommand::Dysymtab(cmd) => Some(cmd),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!((dysymtab.ilocalsym, dysymtab.nlocalsym), (0, 1));
+        assert_eq!((dysymtab.iextdefsym, dysymtab.nextdefsym), (1, 2));
+        assert_eq!((dysymtab.iundefsym, dysymtab.nundefsym), (3, 1));
+
+        let sects = file.sections().collect::<Vec<_>>();
+        assert_eq!(file.symbols[0].n_value, sects[1].0.addr);
+        assert_eq!(file.symbols[1].n_value, 10);
+
+        let relocs = &sects[0].1.relocs;
+        assert_eq!(relocs[0].r_symbolnum, 3);
+        assert_eq!(relocs[1].r_symbolnum, 1);
+        assert!(relocs[0].r_pcrel);
+        assert_eq!(sects[1].1.relocs[0].r_length, RelocLength::Quad);
+        assert_eq!(sects[2].0.size, 0x10);
+    }
+
+    #[test]
+    fn build_object_with_256_sections() {
+        let mut builder = ObjectBuilder::new();
+        let sections = (0..256)
+            .map(|_| builder.add_section("__TEXT", "__text", SectionAttrs::new(), 0, vec![0xc3]))
+            .collect::<Vec<_>>();
+        builder.define_symbol("_a", sections[254], 0, true).unwrap();
+        assert_eq!(builder.build().unwrap().symbols[0].n_sect, 255);
+
+        // n_sect cannot refer to the 256th section.
+        builder.define_symbol("_b", sections[255], 0, true).unwrap();
+        assert!(matches!(
+            builder.build(),
+            Err(Error::InvalidValue { value: 256, .. })
+        ));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{
+    error::{Error, Result},
+    file::MachOFile,
+    load_command::{DysymtabCommand, LoadCommand, SymtabCommand},
+    nlist::{NList64, NType, NTypeField},
+    string_table::StringTable,
+};
+
+#[derive(Debug, Clone)]
+pub(super) struct SymbolDef {
+    pub name: String,
+    /// Index of the section in the whole file and offset in it, or `None` if undefined.
+    pub def: Option<(usize, u64)>,
+    pub external: bool,
+}
+
+/// The symbol table, string table and `DysymtabCommand` built from `SymbolDef`s.
+pub(super) struct SymbolTable {
+    /// Indices of `SymbolDef`s in the symbol table order.
+    pub order: Vec<usize>,
+    /// Symbol table index of each `SymbolDef`.
+    pub index: Vec<u32>,
+    pub symbols: Vec<NList64>,
+    pub string_table: StringTable,
+    pub dysymtab: DysymtabCommand,
+}
+
+impl SymbolTable {
+    /// Orders symbols as local symbols, defined external symbols and undefined symbols, and
+    /// sorts the latter two by name, as `DysymtabCommand` requires.
+    ///
+    /// Fails if a symbol is defined in a section after the 255th, which `n_sect` cannot refer
+    /// to.
+    pub fn new(defs: &[SymbolDef]) -> Result<Self> {
+        let mut locals = Vec::new();
+        let mut extdefs = Vec::new();
+        let mut undefs = Vec::new();
+        for (i, symbol) in defs.iter().enumerate() {
+            match symbol.def {
+                Some(_) if !symbol.external => locals.push(i),
+                Some(_) => extdefs.push(i),
+                None => undefs.push(i),
+            }
+        }
+        extdefs.sort_by(|a, b| defs[*a].name.cmp(&defs[*b].name));
+        undefs.sort_by(|a, b| defs[*a].name.cmp(&defs[*b].name));
+
+        let order = [&locals[..], &extdefs[..], &undefs[..]].concat();
+        let mut index = vec![0; defs.len()];
+        for (idx, i) in order.iter().enumerate() {
+            index[*i] = idx as u32;
+        }
+
+        let mut string_table = StringTable::with_null();
+        let mut symbols = Vec::new();
+        for i in order.iter() {
+            let symbol = &defs[*i];
+            let n_strx = string_table.len() as u32;
+            string_table.push_with_null(&symbol.name);
+
+            let (n_type, n_sect) = match symbol.def {
+                Some((section, _)) => {
+                    let n_sect = u8::try_from(section + 1).map_err(|_| Error::InvalidValue {
+                        kind: "section number",
+                        value: section as u64 + 1,
+                    })?;
+                    (NType::Sect, n_sect)
+                }
+                None => (NType::Undf, NList64::NO_SECT),
+            };
+            symbols.push(NList64 {
+                n_strx,
+                n_type: NTypeField::Norm {
+                    n_pext: false,
+                    n_type,
+                    n_ext: symbol.external,
+                },
+                n_sect,
+                n_desc: 0,
+                n_value: 0,
+            });
+        }
+
+        let dysymtab = DysymtabCommand {
+            cmd: DysymtabCommand::TYPE,
+            cmdsize: DysymtabCommand::SIZE,
+            ilocalsym: 0,
+            nlocalsym: locals.len() as u32,
+            iextdefsym: locals.len() as u32,
+            nextdefsym: extdefs.len() as u32,
+            iundefsym: (locals.len() + extdefs.len()) as u32,
+            nundefsym: undefs.len() as u32,
+            tocoff: 0,
+            ntoc: 0,
+            modtaboff: 0,
+            nmodtab: 0,
+            extrefsymoff: 0,
+            nextrefsyms: 0,
+            indirectsymoff: 0,
+            nindirectsyms: 0,
+            extreloff: 0,
+            nextrel: 0,
+            locreloff: 0,
+            nlocrel: 0,
+        };
+
+        Ok(SymbolTable {
+            order,
+            index,
+            symbols,
+            string_table,
+            dysymtab,
+        })
+    }
+}
+
+/// An empty `SymtabCommand`, whose offsets and sizes are filled by `MachOFile::layout`.
+pub(super) fn symtab_command() -> LoadCommand {
+    LoadCommand::Symtab(SymtabCommand {
+        cmd: SymtabCommand::TYPE,
+        cmdsize: SymtabCommand::SIZE,
+        symoff: 0,
+        nsyms: 0,
+        stroff: 0,
+        strsize: 0,
+    })
+}
+
+/// Sets `n_value` of every defined symbol to its address.
+/// Addresses are known only after `MachOFile::layout`.
+pub(super) fn assign_symbol_values(file: &mut MachOFile, defs: &[SymbolDef], order: &[usize]) {
+    let addrs = file
+        .sections()
+        .map(|(sect, _)| sect.addr)
+        .collect::<Vec<_>>();
+    for (symbol, i) in file.symbols.iter_mut().zip(order.iter()) {
+        if let Some((section, offset)) = defs[*i].def {
+            symbol.n_value = addrs[section] + offset;
+        }
+    }
+}
@@ -0,0 +1,1206 @@
+//! Chained fixups referred by `LC_DYLD_CHAINED_FIXUPS`, which replace the dyld info in modern
+//! images.
+//!
+//! The fixups data has a header, the page starts of every segment, the imports table and the
+//! symbol pool. Every location to be fixed up holds an encoded pointer whose `next` field links
+//! to the next location in the same page, so the page starts give the heads of the chains.
+//!
+//! Chained fixups are only used by little-endian images, so everything is read and written as
+//! little-endian.
+
+use crate::{
+    error::{check, Error, Result},
+    leb128::ByteReader,
+    view::MachOView,
+};
+use std::collections::HashMap;
+
+/// `page_starts` value of a page which has no fixups.
+pub const DYLD_CHAINED_PTR_START_NONE: u16 = 0xffff;
+/// Set in `page_starts` when the page has several chains, listed in the overflow area.
+pub const DYLD_CHAINED_PTR_START_MULTI: u16 = 0x8000;
+/// Set in the last chain start of a page in the overflow area.
+pub const DYLD_CHAINED_PTR_START_LAST: u16 = 0x8000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportsFormat {
+    /// DYLD_CHAINED_IMPORT
+    Import,
+    /// DYLD_CHAINED_IMPORT_ADDEND
+    Addend,
+    /// DYLD_CHAINED_IMPORT_ADDEND64
+    Addend64,
+    /// Keeps the value as is.
+    Unknown(u32),
+}
+
+impl ImportsFormat {
+    pub fn from_u32(n: u32) -> Self {
+        match n {
+            1 => ImportsFormat::Import,
+            2 => ImportsFormat::Addend,
+            3 => ImportsFormat::Addend64,
+            n => ImportsFormat::Unknown(n),
+        }
+    }
+
+    pub fn to_u32(self) -> u32 {
+        match self {
+            ImportsFormat::Import => 1,
+            ImportsFormat::Addend => 2,
+            ImportsFormat::Addend64 => 3,
+            ImportsFormat::Unknown(n) => n,
+        }
+    }
+
+    /// Size of an entry of the imports table.
+    pub fn entry_size(self) -> Option<u32> {
+        match self {
+            ImportsFormat::Import => Some(4),
+            ImportsFormat::Addend => Some(8),
+            ImportsFormat::Addend64 => Some(16),
+            ImportsFormat::Unknown(_) => None,
+        }
+    }
+}
+
+/// `DYLD_CHAINED_PTR_*`, the encoding of pointers in the chains of a segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerFormat {
+    Arm64e,
+    Ptr64,
+    Ptr32,
+    Ptr32Cache,
+    Ptr32Firmware,
+    Ptr64Offset,
+    Arm64eKernel,
+    Ptr64KernelCache,
+    Arm64eUserland,
+    Arm64eFirmware,
+    X86_64KernelCache,
+    Arm64eUserland24,
+    /// Keeps the value as is.
+    Unknown(u16),
+}
+
+impl PointerFormat {
+    pub fn from_u16(n: u16) -> Self {
+        match n {
+            1 => PointerFormat::Arm64e,
+            2 => PointerFormat::Ptr64,
+            3 => PointerFormat::Ptr32,
+            4 => PointerFormat::Ptr32Cache,
+            5 => PointerFormat::Ptr32Firmware,
+            6 => PointerFormat::Ptr64Offset,
+            7 => PointerFormat::Arm64eKernel,
+            8 => PointerFormat::Ptr64KernelCache,
+            9 => PointerFormat::Arm64eUserland,
+            10 => PointerFormat::Arm64eFirmware,
+            11 => PointerFormat::X86_64KernelCache,
+            12 => PointerFormat::Arm64eUserland24,
+            n => PointerFormat::Unknown(n),
+        }
+    }
+
+    pub fn to_u16(self) -> u16 {
+        match self {
+            PointerFormat::Arm64e => 1,
+            PointerFormat::Ptr64 => 2,
+            PointerFormat::Ptr32 => 3,
+            PointerFormat::Ptr32Cache => 4,
+            PointerFormat::Ptr32Firmware => 5,
+            PointerFormat::Ptr64Offset => 6,
+            PointerFormat::Arm64eKernel => 7,
+            PointerFormat::Ptr64KernelCache => 8,
+            PointerFormat::Arm64eUserland => 9,
+            PointerFormat::Arm64eFirmware => 10,
+            PointerFormat::X86_64KernelCache => 11,
+            PointerFormat::Arm64eUserland24 => 12,
+            PointerFormat::Unknown(n) => n,
+        }
+    }
+
+    /// Bytes between locations per unit of the `next` field, for formats this crate decodes.
+    pub fn stride(self) -> Option<u64> {
+        match self {
+            PointerFormat::Arm64e
+            | PointerFormat::Arm64eUserland
+            | PointerFormat::Arm64eUserland24 => Some(8),
+            PointerFormat::Ptr64 | PointerFormat::Ptr64Offset | PointerFormat::Ptr32 => Some(4),
+            _ => None,
+        }
+    }
+
+    /// Size of the pointers.
+    pub fn pointer_size(self) -> u64 {
+        match self {
+            PointerFormat::Ptr32 | PointerFormat::Ptr32Cache | PointerFormat::Ptr32Firmware => 4,
+            _ => 8,
+        }
+    }
+
+    /// Whether the target of a non-authenticated rebase is a vmaddr rather than an offset from
+    /// the mach header.
+    pub fn rebase_is_vmaddr(self) -> bool {
+        matches!(
+            self,
+            PointerFormat::Arm64e | PointerFormat::Ptr64 | PointerFormat::Ptr32
+        )
+    }
+}
+
+/// dyld_chained_fixups_header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainedFixupsHeader {
+    /// 0
+    pub fixups_version: u32,
+    /// offset of dyld_chained_starts_in_image in chain_data
+    pub starts_offset: u32,
+    /// offset of imports table in chain_data
+    pub imports_offset: u32,
+    /// offset of symbol strings in chain_data
+    pub symbols_offset: u32,
+    /// number of imported symbol names
+    pub imports_count: u32,
+    pub imports_format: ImportsFormat,
+    /// 0 => uncompressed, 1 => zlib compressed
+    pub symbols_format: u32,
+}
+
+impl ChainedFixupsHeader {
+    pub const SIZE: u32 = 0x1c; // 28
+}
+
+/// dyld_chained_starts_in_segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainedStartsInSegment {
+    /// size of this, including the page starts and the overflow area
+    pub size: u32,
+    /// 0x1000 or 0x4000
+    pub page_size: u16,
+    pub pointer_format: PointerFormat,
+    /// offset in memory to the start of the segment from the mach header
+    pub segment_offset: u64,
+    /// for 32-bit formats, values beyond this are not pointers
+    pub max_valid_pointer: u32,
+    /// Offsets of the chain heads in every page. Empty if the page has no fixups.
+    pub page_starts: Vec<Vec<u16>>,
+}
+
+impl ChainedStartsInSegment {
+    /// Size of the fields before the page starts.
+    pub const SIZE: u32 = 0x16; // 22
+}
+
+/// An entry of the imports table with its name from the symbol pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainedImport {
+    /// 1-based index of the dylib load commands, or a negative special ordinal.
+    pub library_ordinal: i64,
+    pub weak_import: bool,
+    pub name: String,
+    /// Always 0 for `ImportsFormat::Import`.
+    pub addend: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PtrAuthKey {
+    IA,
+    IB,
+    DA,
+    DB,
+}
+
+impl PtrAuthKey {
+    pub fn from_u8(n: u8) -> Self {
+        match n & 0b11 {
+            0 => PtrAuthKey::IA,
+            1 => PtrAuthKey::IB,
+            2 => PtrAuthKey::DA,
+            _ => PtrAuthKey::DB,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+/// How an arm64e pointer is signed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointerAuth {
+    pub key: PtrAuthKey,
+    pub diversity: u16,
+    /// Whether the address of the location is blended into the diversity.
+    pub addr_div: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainedTarget {
+    /// A pointer into the image. `target` is a vmaddr or an offset from the mach header,
+    /// depending on `PointerFormat::rebase_is_vmaddr`. Authenticated rebases always have an
+    /// offset. `high8` is the top byte of the pointer.
+    Rebase { target: u64, high8: u8 },
+    /// A pointer to the import at `ordinal` in the imports table plus `addend`.
+    Bind { ordinal: u32, addend: i64 },
+}
+
+/// A location fixed up by a chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainedFixup {
+    pub segment_index: u32,
+    /// Offset from the start of the segment.
+    pub offset: u64,
+    pub target: ChainedTarget,
+    pub auth: Option<PointerAuth>,
+}
+
+/// The contents of `LC_DYLD_CHAINED_FIXUPS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainedFixups {
+    pub header: ChainedFixupsHeader,
+    /// The page starts of every segment, `None` if the segment has no fixups.
+    pub segments: Vec<Option<ChainedStartsInSegment>>,
+    pub imports: Vec<ChainedImport>,
+}
+
+impl ChainedFixups {
+    /// Parses the fixups data. Offsets in errors are relative to `data`.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        Self::parse_at(data, 0)
+    }
+
+    pub(crate) fn parse_at(data: &[u8], base: u64) -> Result<Self> {
+        let mut read = ByteReader::new(data, base);
+        let header = ChainedFixupsHeader {
+            fixups_version: read.read_u32()?,
+            starts_offset: read.read_u32()?,
+            imports_offset: read.read_u32()?,
+            symbols_offset: read.read_u32()?,
+            imports_count: read.read_u32()?,
+            imports_format: ImportsFormat::from_u32(read.read_u32()?),
+            symbols_format: read.read_u32()?,
+        };
+        if header.fixups_version != 0 {
+            return Err(Error::UnknownValue {
+                offset: base,
+                kind: "chained fixups version",
+                value: header.fixups_version.into(),
+            });
+        }
+
+        // dyld_chained_starts_in_image
+        read.set_pos(header.starts_offset as usize);
+        let seg_count = read.read_u32()?;
+        let seg_info_offsets = (0..seg_count)
+            .map(|_| read.read_u32())
+            .collect::<Result<Vec<_>>>()?;
+        let segments = seg_info_offsets
+            .into_iter()
+            .map(|seg_info_offset| match seg_info_offset {
+                0 => Ok(None),
+                n => {
+                    read.set_pos(header.starts_offset as usize + n as usize);
+                    read_starts_in_segment(&mut read).map(Some)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let imports = read_imports(data, base, &header)?;
+
+        Ok(ChainedFixups {
+            header,
+            segments,
+            imports,
+        })
+    }
+
+    /// Walks every chain in the file viewed by `view`.
+    pub fn walk(&self, view: &MachOView) -> Result<Vec<ChainedFixup>> {
+        let segments = view
+            .segments()
+            .map(|seg| {
+                let seg = seg?;
+                Ok((seg.fileoff, seg.data()?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.walk_at(&segments)
+    }
+
+    /// Walks every chain in the contents of segments, `segments[i]` being the file contents of
+    /// the i-th segment. Offsets in errors are relative to each segment.
+    pub fn walk_segments(&self, segments: &[&[u8]]) -> Result<Vec<ChainedFixup>> {
+        let segments = segments.iter().map(|data| (0, *data)).collect::<Vec<_>>();
+        self.walk_at(&segments)
+    }
+
+    fn walk_at(&self, segments: &[(u64, &[u8])]) -> Result<Vec<ChainedFixup>> {
+        let mut fixups = Vec::new();
+
+        for (segment_index, starts) in self.segments.iter().enumerate() {
+            let starts = match starts {
+                Some(starts) => starts,
+                None => continue,
+            };
+            let (fileoff, data) = segments.get(segment_index).copied().unwrap_or((0, &[]));
+            let format = starts.pointer_format;
+            let stride = format.stride().ok_or(Error::UnknownValue {
+                offset: fileoff,
+                kind: "chained pointer format",
+                value: format.to_u16().into(),
+            })?;
+
+            for (page_index, page_starts) in starts.page_starts.iter().enumerate() {
+                for start in page_starts.iter() {
+                    let mut offset =
+                        page_index as u64 * u64::from(starts.page_size) + u64::from(*start);
+                    loop {
+                        let mut read = ByteReader::new(data, fileoff);
+                        read.set_pos(offset.try_into().unwrap_or(usize::MAX));
+                        let raw = match format.pointer_size() {
+                            4 => read.read_u32()?.into(),
+                            _ => read.read_u64()?,
+                        };
+                        let (target, auth, next) = decode_pointer(format, raw);
+                        fixups.push(ChainedFixup {
+                            segment_index: segment_index as u32,
+                            offset,
+                            target,
+                            auth,
+                        });
+                        if next == 0 {
+                            break;
+                        }
+                        offset += next * stride;
+                    }
+                }
+            }
+        }
+
+        Ok(fixups)
+    }
+}
+
+fn read_starts_in_segment(read: &mut ByteReader) -> Result<ChainedStartsInSegment> {
+    let start = read.pos();
+    let size = read.read_u32()?;
+    let page_size = read.read_u16()?;
+    let pointer_format = PointerFormat::from_u16(read.read_u16()?);
+    let segment_offset = read.read_u64()?;
+    let max_valid_pointer = read.read_u32()?;
+    let page_count = read.read_u16()?;
+
+    // the page starts are followed by the overflow area of multi-start pages.
+    let n_entries = (size.saturating_sub(ChainedStartsInSegment::SIZE) / 2).max(page_count.into());
+    let mut entries = Vec::new();
+    for _ in 0..n_entries {
+        entries.push(read.read_u16()?);
+    }
+
+    let mut page_starts = Vec::with_capacity(page_count.into());
+    for page_start in entries[..page_count as usize].iter() {
+        let starts = match *page_start {
+            DYLD_CHAINED_PTR_START_NONE => Vec::new(),
+            n if n & DYLD_CHAINED_PTR_START_MULTI != 0 => {
+                let mut starts = Vec::new();
+                let mut idx = (n & !DYLD_CHAINED_PTR_START_MULTI) as usize;
+                loop {
+                    let entry = *entries.get(idx).ok_or(Error::Truncated {
+                        offset: read.offset(),
+                    })?;
+                    starts.push(entry & !DYLD_CHAINED_PTR_START_LAST);
+                    if entry & DYLD_CHAINED_PTR_START_LAST != 0 {
+                        break;
+                    }
+                    idx += 1;
+                }
+                starts
+            }
+            n => vec![n],
+        };
+        page_starts.push(starts);
+    }
+    read.set_pos(start + size as usize);
+
+    Ok(ChainedStartsInSegment {
+        size,
+        page_size,
+        pointer_format,
+        segment_offset,
+        max_valid_pointer,
+        page_starts,
+    })
+}
+
+fn read_imports(
+    data: &[u8],
+    base: u64,
+    header: &ChainedFixupsHeader,
+) -> Result<Vec<ChainedImport>> {
+    if header.symbols_format != 0 {
+        return Err(Error::UnknownValue {
+            offset: base + 0x18,
+            kind: "chained fixups symbols format",
+            value: header.symbols_format.into(),
+        });
+    }
+
+    let mut read = ByteReader::new(data, base);
+    read.set_pos(header.imports_offset as usize);
+    let mut symbols = ByteReader::new(data, base);
+
+    let mut imports = Vec::new();
+    for _ in 0..header.imports_count {
+        let (library_ordinal, weak_import, name_offset, addend) = match header.imports_format {
+            ImportsFormat::Import | ImportsFormat::Addend => {
+                let raw = read.read_u32()?;
+                let addend = match header.imports_format {
+                    ImportsFormat::Addend => read.read_u32()? as i32 as i64,
+                    _ => 0,
+                };
+                let ordinal = sign_extend_ordinal(u64::from(raw & 0xff), 8);
+                (ordinal, raw & 0x100 != 0, raw >> 9, addend)
+            }
+            ImportsFormat::Addend64 => {
+                let raw = read.read_u64()?;
+                let addend = read.read_u64()? as i64;
+                let ordinal = sign_extend_ordinal(raw & 0xffff, 16);
+                (ordinal, raw & 0x1_0000 != 0, (raw >> 32) as u32, addend)
+            }
+            ImportsFormat::Unknown(n) => {
+                return Err(Error::UnknownValue {
+                    offset: base + 0x14,
+                    kind: "chained imports format",
+                    value: n.into(),
+                })
+            }
+        };
+
+        symbols.set_pos(header.symbols_offset as usize + name_offset as usize);
+        imports.push(ChainedImport {
+            library_ordinal,
+            weak_import,
+            name: symbols.read_cstr()?.to_string(),
+            addend,
+        });
+    }
+    Ok(imports)
+}
+
+/// Ordinals in the top 16 values of the field are the negative special ordinals.
+fn sign_extend_ordinal(n: u64, width: u32) -> i64 {
+    if n >= (1 << width) - 16 {
+        n as i64 - (1 << width)
+    } else {
+        n as i64
+    }
+}
+
+fn bits(raw: u64, shift: u32, width: u32) -> u64 {
+    (raw >> shift) & ((1 << width) - 1)
+}
+
+/// Decodes a pointer of a format whose stride is known into the target, the pointer
+/// authentication and the `next` field.
+fn decode_pointer(format: PointerFormat, raw: u64) -> (ChainedTarget, Option<PointerAuth>, u64) {
+    match format {
+        PointerFormat::Ptr64 | PointerFormat::Ptr64Offset => {
+            let target = if bits(raw, 63, 1) == 0 {
+                ChainedTarget::Rebase {
+                    target: bits(raw, 0, 36),
+                    high8: bits(raw, 36, 8) as u8,
+                }
+            } else {
+                ChainedTarget::Bind {
+                    ordinal: bits(raw, 0, 24) as u32,
+                    addend: bits(raw, 24, 8) as i64,
+                }
+            };
+            (target, None, bits(raw, 51, 12))
+        }
+        PointerFormat::Ptr32 => {
+            let target = if bits(raw, 31, 1) == 0 {
+                ChainedTarget::Rebase {
+                    target: bits(raw, 0, 26),
+                    high8: 0,
+                }
+            } else {
+                ChainedTarget::Bind {
+                    ordinal: bits(raw, 0, 20) as u32,
+                    addend: bits(raw, 20, 6) as i64,
+                }
+            };
+            (target, None, bits(raw, 26, 5))
+        }
+        _ => {
+            let ordinal_width = if format == PointerFormat::Arm64eUserland24 {
+                24
+            } else {
+                16
+            };
+            let is_auth = bits(raw, 63, 1) != 0;
+            let is_bind = bits(raw, 62, 1) != 0;
+            let auth = is_auth.then(|| PointerAuth {
+                key: PtrAuthKey::from_u8(bits(raw, 49, 2) as u8),
+                diversity: bits(raw, 32, 16) as u16,
+                addr_div: bits(raw, 48, 1) != 0,
+            });
+            let target = match (is_auth, is_bind) {
+                (false, false) => ChainedTarget::Rebase {
+                    target: bits(raw, 0, 43),
+                    high8: bits(raw, 43, 8) as u8,
+                },
+                (true, false) => ChainedTarget::Rebase {
+                    target: bits(raw, 0, 32),
+                    high8: 0,
+                },
+                (false, true) => ChainedTarget::Bind {
+                    ordinal: bits(raw, 0, ordinal_width) as u32,
+                    // 19-bit signed addend.
+                    addend: ((bits(raw, 32, 19) << 45) as i64) >> 45,
+                },
+                (true, true) => ChainedTarget::Bind {
+                    ordinal: bits(raw, 0, ordinal_width) as u32,
+                    addend: 0,
+                },
+            };
+            (target, auth, bits(raw, 51, 11))
+        }
+    }
+}
+
+/// A segment whose chains are written by `encode`.
+#[derive(Debug)]
+pub struct ChainedSegment<'a> {
+    /// Offset in memory to the start of the segment from the mach header.
+    pub segment_offset: u64,
+    /// The file contents of the segment, where the encoded pointers are written.
+    pub data: &'a mut [u8],
+}
+
+/// Chooses the pointer format ld64 uses for `fixups`: `Ptr32` for 32-bit images,
+/// `Arm64eUserland24` if some fixup is authenticated, and `Ptr64Offset` otherwise.
+///
+/// Rebase targets of all of them but `Ptr32` are offsets from the mach header.
+pub fn choose_pointer_format(fixups: &[ChainedFixup], is_64: bool) -> PointerFormat {
+    if !is_64 {
+        PointerFormat::Ptr32
+    } else if fixups.iter().any(|fixup| fixup.auth.is_some()) {
+        PointerFormat::Arm64eUserland24
+    } else {
+        PointerFormat::Ptr64Offset
+    }
+}
+
+/// Encodes fixups into chains written to `segments`, and returns the data of
+/// `LC_DYLD_CHAINED_FIXUPS` with the page starts, the imports table and the symbol pool.
+///
+/// `fixups[i].segment_index` refers to `segments`, and `ChainedTarget::Bind::ordinal` refers to
+/// `imports`. Locations in a page are linked into one chain while the gap fits in the `next`
+/// field, and a page which needs more chains gets multiple starts. The imports format is the
+/// smallest one which can hold `imports`.
+///
+/// Fails with `Error::InvalidValue` if `format` is not supported, if a fixup refers to a
+/// segment which is not in `segments`, overlaps another fixup or is out of its segment, or if
+/// its target does not fit in the fields of `format`.
+pub fn encode(
+    fixups: &[ChainedFixup],
+    imports: &[ChainedImport],
+    format: PointerFormat,
+    page_size: u16,
+    segments: &mut [ChainedSegment],
+) -> Result<Vec<u8>> {
+    let stride = format.stride().ok_or(Error::InvalidValue {
+        kind: "chained pointer format",
+        value: format.to_u16().into(),
+    })?;
+    let pointer_size = format.pointer_size();
+    let max_next = match format {
+        PointerFormat::Ptr32 => 0x1f,
+        PointerFormat::Ptr64 | PointerFormat::Ptr64Offset => 0xfff,
+        _ => 0x7ff,
+    };
+    let page_size64 = u64::from(page_size);
+
+    let mut sorted = fixups.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|fixup| (fixup.segment_index, fixup.offset));
+    if let Some(fixup) = sorted.last() {
+        check(
+            (fixup.segment_index as usize) < segments.len(),
+            "chained fixup segment index",
+            fixup.segment_index.into(),
+        )?;
+    }
+
+    // page starts of every segment, written after being threaded.
+    let mut segment_starts = Vec::with_capacity(segments.len());
+    for (segment_index, segment) in segments.iter_mut().enumerate() {
+        let in_segment = sorted
+            .iter()
+            .filter(|fixup| fixup.segment_index as usize == segment_index)
+            .collect::<Vec<_>>();
+        if in_segment.is_empty() {
+            segment_starts.push(None);
+            continue;
+        }
+
+        let page_count = (segment.data.len() as u64).div_ceil(page_size64);
+        let mut page_starts = vec![Vec::new(); page_count as usize];
+        // the `next` field of a location which is followed by another one.
+        let link = |fixup: &ChainedFixup, next: &ChainedFixup| {
+            let delta = next.offset - fixup.offset;
+            let same_page = next.offset / page_size64 == fixup.offset / page_size64;
+            if same_page
+                && delta >= pointer_size
+                && delta.is_multiple_of(stride)
+                && delta / stride <= max_next
+            {
+                delta / stride
+            } else {
+                0
+            }
+        };
+        for (i, fixup) in in_segment.iter().enumerate() {
+            let overlaps = i > 0 && in_segment[i - 1].offset + pointer_size > fixup.offset;
+            let in_bounds = fixup
+                .offset
+                .checked_add(pointer_size)
+                .is_some_and(|end| end <= segment.data.len() as u64);
+            check(!overlaps && in_bounds, "chained fixup offset", fixup.offset)?;
+            let next = in_segment.get(i + 1).map_or(0, |next| link(fixup, next));
+            let is_head = i == 0 || link(in_segment[i - 1], fixup) == 0;
+            if is_head {
+                let page = (fixup.offset / page_size64) as usize;
+                page_starts[page].push((fixup.offset % page_size64) as u16);
+            }
+
+            let raw = encode_pointer(format, fixup, next)?;
+            let offset = fixup.offset as usize;
+            match pointer_size {
+                4 => segment.data[offset..offset + 4].copy_from_slice(&(raw as u32).to_le_bytes()),
+                _ => segment.data[offset..offset + 8].copy_from_slice(&raw.to_le_bytes()),
+            }
+        }
+        segment_starts.push(Some((segment.segment_offset, page_starts)));
+    }
+
+    let mut buf = Vec::new();
+    let starts_offset = align8(ChainedFixupsHeader::SIZE.into());
+    buf.resize(starts_offset as usize, 0);
+
+    // dyld_chained_starts_in_image, whose segment offsets are filled later.
+    buf.extend_from_slice(&(segments.len() as u32).to_le_bytes());
+    let seg_info_offsets_pos = buf.len();
+    buf.resize(buf.len() + 4 * segments.len(), 0);
+
+    for (segment_index, starts) in segment_starts.iter().enumerate() {
+        let (segment_offset, page_starts) = match starts {
+            Some(starts) => starts,
+            None => continue,
+        };
+        buf.resize(align8(buf.len() as u64) as usize, 0);
+        let seg_info_offset = (buf.len() as u64 - starts_offset) as u32;
+        let pos = seg_info_offsets_pos + 4 * segment_index;
+        buf[pos..pos + 4].copy_from_slice(&seg_info_offset.to_le_bytes());
+
+        let page_count = page_starts.len();
+        check(
+            page_count <= u16::MAX.into(),
+            "chained page count",
+            page_count as u64,
+        )?;
+        let mut entries = Vec::with_capacity(page_count);
+        let mut overflow = Vec::new();
+        for starts in page_starts.iter() {
+            match starts.as_slice() {
+                [] => entries.push(DYLD_CHAINED_PTR_START_NONE),
+                [start] => entries.push(*start),
+                starts => {
+                    // the index in the overflow area has 15 bits.
+                    let idx = page_count + overflow.len();
+                    check(
+                        idx < usize::from(DYLD_CHAINED_PTR_START_MULTI),
+                        "chained page start index",
+                        idx as u64,
+                    )?;
+                    entries.push(DYLD_CHAINED_PTR_START_MULTI | idx as u16);
+                    overflow.extend_from_slice(starts);
+                    *overflow.last_mut().unwrap() |= DYLD_CHAINED_PTR_START_LAST;
+                }
+            }
+        }
+        entries.extend(overflow);
+
+        let size = ChainedStartsInSegment::SIZE + 2 * entries.len() as u32;
+        buf.extend_from_slice(&size.to_le_bytes());
+        buf.extend_from_slice(&page_size.to_le_bytes());
+        buf.extend_from_slice(&format.to_u16().to_le_bytes());
+        buf.extend_from_slice(&segment_offset.to_le_bytes());
+        // every 32-bit value is a pointer, as this encoder writes no non-pointers.
+        let max_valid_pointer: u32 = match format {
+            PointerFormat::Ptr32 => (1 << 26) - 1,
+            _ => 0,
+        };
+        buf.extend_from_slice(&max_valid_pointer.to_le_bytes());
+        buf.extend_from_slice(&(page_count as u16).to_le_bytes());
+        for entry in entries {
+            buf.extend_from_slice(&entry.to_le_bytes());
+        }
+    }
+
+    // the symbol pool, where the same name is shared.
+    let mut symbols = Vec::new();
+    let mut pooled = HashMap::new();
+    let mut name_offsets = Vec::with_capacity(imports.len());
+    for import in imports.iter() {
+        let name_offset = *pooled.entry(import.name.as_str()).or_insert_with(|| {
+            let name_offset = symbols.len() as u64;
+            symbols.extend_from_slice(import.name.as_bytes());
+            symbols.push(0);
+            name_offset
+        });
+        name_offsets.push(name_offset);
+    }
+
+    let imports_format = choose_imports_format(imports, symbols.len() as u64);
+    buf.resize(buf.len().next_multiple_of(4), 0);
+    let imports_offset = buf.len() as u32;
+    for (import, name_offset) in imports.iter().zip(name_offsets) {
+        let ordinal = import.library_ordinal as u64;
+        let weak = u64::from(import.weak_import);
+        match imports_format {
+            ImportsFormat::Import | ImportsFormat::Addend => {
+                let raw = (ordinal & 0xff) | weak << 8 | name_offset << 9;
+                buf.extend_from_slice(&(raw as u32).to_le_bytes());
+                if imports_format == ImportsFormat::Addend {
+                    buf.extend_from_slice(&(import.addend as i32).to_le_bytes());
+                }
+            }
+            _ => {
+                let raw = (ordinal & 0xffff) | weak << 16 | name_offset << 32;
+                buf.extend_from_slice(&raw.to_le_bytes());
+                buf.extend_from_slice(&import.addend.to_le_bytes());
+            }
+        }
+    }
+    let symbols_offset = buf.len() as u32;
+    buf.extend_from_slice(&symbols);
+    buf.resize(align8(buf.len() as u64) as usize, 0);
+
+    let header = [
+        0, // fixups_version
+        starts_offset as u32,
+        imports_offset,
+        symbols_offset,
+        imports.len() as u32,
+        imports_format.to_u32(),
+        0, // symbols_format
+    ];
+    for (i, n) in header.iter().enumerate() {
+        buf[i * 4..i * 4 + 4].copy_from_slice(&n.to_le_bytes());
+    }
+    Ok(buf)
+}
+
+fn choose_imports_format(imports: &[ChainedImport], symbols_size: u64) -> ImportsFormat {
+    let fits_in_8bit = imports
+        .iter()
+        .all(|import| (-16..0xf0).contains(&import.library_ordinal));
+    let fits_in_i32 = imports
+        .iter()
+        .all(|import| i32::try_from(import.addend).is_ok());
+    if fits_in_8bit && symbols_size < 1 << 23 && fits_in_i32 {
+        if imports.iter().all(|import| import.addend == 0) {
+            ImportsFormat::Import
+        } else {
+            ImportsFormat::Addend
+        }
+    } else {
+        ImportsFormat::Addend64
+    }
+}
+
+fn align8(n: u64) -> u64 {
+    n.next_multiple_of(8)
+}
+
+/// Encodes a pointer, the inverse of `decode_pointer`.
+fn encode_pointer(format: PointerFormat, fixup: &ChainedFixup, next: u64) -> Result<u64> {
+    let is_arm64e = format.stride() == Some(8);
+    check(
+        fixup.auth.is_none() || is_arm64e,
+        "chained pointer format with authentication",
+        format.to_u16().into(),
+    )?;
+
+    let raw = match (format, fixup.target) {
+        (PointerFormat::Ptr32, ChainedTarget::Rebase { target, high8 }) => {
+            check(target < 1 << 26, "chained rebase target", target)?;
+            check(high8 == 0, "chained rebase high8", high8.into())?;
+            target | next << 26
+        }
+        (PointerFormat::Ptr32, ChainedTarget::Bind { ordinal, addend }) => {
+            check(ordinal < 1 << 20, "chained bind ordinal", ordinal.into())?;
+            check(
+                (0..1 << 6).contains(&addend),
+                "chained bind addend",
+                addend as u64,
+            )?;
+            u64::from(ordinal) | (addend as u64) << 20 | next << 26 | 1 << 31
+        }
+        (_, ChainedTarget::Rebase { target, high8 }) if !is_arm64e => {
+            check(target < 1 << 36, "chained rebase target", target)?;
+            target | u64::from(high8) << 36 | next << 51
+        }
+        (_, ChainedTarget::Bind { ordinal, addend }) if !is_arm64e => {
+            check(ordinal < 1 << 24, "chained bind ordinal", ordinal.into())?;
+            check(
+                (0..1 << 8).contains(&addend),
+                "chained bind addend",
+                addend as u64,
+            )?;
+            u64::from(ordinal) | (addend as u64) << 24 | next << 51 | 1 << 63
+        }
+        (_, target) => {
+            let ordinal_width = if format == PointerFormat::Arm64eUserland24 {
+                24
+            } else {
+                16
+            };
+            let auth = fixup.auth.map_or(0, |auth| {
+                u64::from(auth.diversity) << 32
+                    | u64::from(auth.addr_div) << 48
+                    | u64::from(auth.key.to_u8()) << 49
+                    | 1 << 63
+            });
+            let ordinal_fits = |ordinal: u32| {
+                check(
+                    ordinal < 1 << ordinal_width,
+                    "chained bind ordinal",
+                    ordinal.into(),
+                )
+            };
+            let target = match (target, fixup.auth) {
+                (ChainedTarget::Rebase { target, high8 }, None) => {
+                    check(target < 1 << 43, "chained rebase target", target)?;
+                    target | u64::from(high8) << 43
+                }
+                (ChainedTarget::Rebase { target, high8 }, Some(_)) => {
+                    check(target < 1 << 32, "chained rebase target", target)?;
+                    check(high8 == 0, "chained rebase high8", high8.into())?;
+                    target
+                }
+                (ChainedTarget::Bind { ordinal, addend }, None) => {
+                    ordinal_fits(ordinal)?;
+                    check(
+                        (-(1 << 18)..1 << 18).contains(&addend),
+                        "chained bind addend",
+                        addend as u64,
+                    )?;
+                    u64::from(ordinal) | (addend as u64 & 0x7ffff) << 32 | 1 << 62
+                }
+                (ChainedTarget::Bind { ordinal, addend }, Some(_)) => {
+                    ordinal_fits(ordinal)?;
+                    check(addend == 0, "chained bind addend", addend as u64)?;
+                    u64::from(ordinal) | 1 << 62
+                }
+            };
+            target | auth | next << 51
+        }
+    };
+    Ok(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds fixups data with one segment of `format` whose page starts and overflow area are
+    /// `page_starts`, and DYLD_CHAINED_IMPORT entries of `imports`.
+    fn fixups_data(
+        format: u16,
+        page_count: u16,
+        page_starts: &[u16],
+        imports: &[(u8, &str)],
+    ) -> Vec<u8> {
+        let starts_offset = ChainedFixupsHeader::SIZE;
+        let seg_size = ChainedStartsInSegment::SIZE + 2 * page_starts.len() as u32;
+        let imports_offset = starts_offset + 8 + seg_size;
+        let symbols_offset = imports_offset + 4 * imports.len() as u32;
+
+        let mut buf = Vec::new();
+        for n in [0, starts_offset, imports_offset, symbols_offset] {
+            buf.extend_from_slice(&n.to_le_bytes());
+        }
+        for n in [imports.len() as u32, 1, 0] {
+            buf.extend_from_slice(&n.to_le_bytes());
+        }
+        // starts in image with one segment
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        buf.extend_from_slice(&8u32.to_le_bytes());
+        // starts in segment
+        buf.extend_from_slice(&seg_size.to_le_bytes());
+        buf.extend_from_slice(&0x4000u16.to_le_bytes());
+        buf.extend_from_slice(&format.to_le_bytes());
+        buf.extend_from_slice(&0x4000u64.to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        buf.extend_from_slice(&page_count.to_le_bytes());
+        for n in page_starts {
+            buf.extend_from_slice(&n.to_le_bytes());
+        }
+
+        let mut symbols = vec![0];
+        for (ordinal, name) in imports {
+            let raw = u32::from(*ordinal) | (symbols.len() as u32) << 9;
+            buf.extend_from_slice(&raw.to_le_bytes());
+            symbols.extend_from_slice(name.as_bytes());
+            symbols.push(0);
+        }
+        buf.extend_from_slice(&symbols);
+        buf
+    }
+
+    #[test]
+    fn walk_arm64e_chain() {
+        let data = fixups_data(1, 1, &[0x10], &[(1, "_malloc"), (0xfe, "_flat")]);
+        let fixups = ChainedFixups::parse(&data).unwrap();
+        assert_eq!(fixups.imports.len(), 2);
+        assert_eq!(fixups.imports[0].name, "_malloc");
+        assert_eq!(fixups.imports[1].library_ordinal, -2);
+        assert_eq!(fixups.segments[0].as_ref().unwrap().page_starts, [[0x10]]);
+
+        let mut segment = vec![0u8; 0x40];
+        // rebase to 0x1_0000_3f00 with high8 0x80, next 1
+        let rebase: u64 = 0x1_0000_3f00 | 0x80 << 43 | 1 << 51;
+        // bind to import 1 with addend -4, next 2
+        let bind: u64 = 1 | (0x7fffc << 32) | 2 << 51 | 1 << 62;
+        // auth rebase to 0x3f00 with key DA, diversity 0x1234 and address diversity
+        let auth_rebase: u64 = 0x3f00 | 0x1234 << 32 | 1 << 48 | 2 << 49 | 1 << 51 | 1 << 63;
+        // auth bind to import 0 with key IA, last
+        let auth_bind: u64 = 3 << 62;
+        for (offset, raw) in [(0x10, rebase), (0x18, bind), (0x28, auth_rebase)] {
+            segment[offset..offset + 8].copy_from_slice(&raw.to_le_bytes());
+        }
+        segment[0x30..0x38].copy_from_slice(&auth_bind.to_le_bytes());
+
+        let walked = fixups.walk_segments(&[&segment]).unwrap();
+        let targets = walked
+            .iter()
+            .map(|f| (f.offset, f.target))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            targets,
+            [
+                (
+                    0x10,
+                    ChainedTarget::Rebase {
+                        target: 0x1_0000_3f00,
+                        high8: 0x80
+                    }
+                ),
+                (
+                    0x18,
+                    ChainedTarget::Bind {
+                        ordinal: 1,
+                        addend: -4
+                    }
+                ),
+                (
+                    0x28,
+                    ChainedTarget::Rebase {
+                        target: 0x3f00,
+                        high8: 0
+                    }
+                ),
+                (
+                    0x30,
+                    ChainedTarget::Bind {
+                        ordinal: 0,
+                        addend: 0
+                    }
+                ),
+            ]
+        );
+        assert_eq!(walked[1].auth, None);
+        assert_eq!(
+            walked[2].auth,
+            Some(PointerAuth {
+                key: PtrAuthKey::DA,
+                diversity: 0x1234,
+                addr_div: true
+            })
+        );
+        assert_eq!(walked[3].auth.unwrap().key, PtrAuthKey::IA);
+    }
+
+    #[test]
+    fn parse_huge_counts_over_tiny_data() {
+        // the counts must not be trusted to reserve memory.
+        let mut data = fixups_data(6, 1, &[0x0], &[]);
+        data[0x10..0x14].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            ChainedFixups::parse(&data),
+            Err(Error::Truncated { .. })
+        ));
+
+        let mut data = fixups_data(6, 1, &[0x0], &[]);
+        let seg_size = ChainedFixupsHeader::SIZE as usize + 8;
+        data[seg_size..seg_size + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            ChainedFixups::parse(&data),
+            Err(Error::Truncated { .. })
+        ));
+    }
+
+    #[test]
+    fn walk_ptr64_multi_start_page() {
+        // one page with chains at 0x0 and 0x20 in the overflow area, and an empty page.
+        let data = fixups_data(6, 2, &[0x8002, 0xffff, 0x0, 0x8020], &[(1, "_a")]);
+        let fixups = ChainedFixups::parse(&data).unwrap();
+        let starts = fixups.segments[0].as_ref().unwrap();
+        assert_eq!(starts.pointer_format, PointerFormat::Ptr64Offset);
+        assert_eq!(starts.page_starts, [vec![0x0, 0x20], vec![]]);
+
+        let mut segment = vec![0u8; 0x8000];
+        // rebase to 0x100 with next 2 (8 bytes), then a bind to "_a" + 8
+        let rebase: u64 = 0x100 | 2 << 51;
+        let bind: u64 = 1 << 63 | 8 << 24;
+        segment[0x0..0x8].copy_from_slice(&rebase.to_le_bytes());
+        segment[0x8..0x10].copy_from_slice(&bind.to_le_bytes());
+        segment[0x20..0x28].copy_from_slice(&0x200u64.to_le_bytes());
+
+        let walked = fixups.walk_segments(&[&segment]).unwrap();
+        let offsets = walked.iter().map(|f| f.offset).collect::<Vec<_>>();
+        assert_eq!(offsets, [0x0, 0x8, 0x20]);
+        assert_eq!(
+            walked[1].target,
+            ChainedTarget::Bind {
+                ordinal: 0,
+                addend: 8
+            }
+        );
+    }
+
+    #[test]
+    fn encode_and_walk_round_trip() {
+        let imports = vec![
+            ChainedImport {
+                library_ordinal: 1,
+                weak_import: false,
+                name: "_malloc".to_string(),
+                addend: 0,
+            },
+            ChainedImport {
+                library_ordinal: -2,
+                weak_import: true,
+                name: "_free".to_string(),
+                addend: 0x1_0000_0000,
+            },
+            ChainedImport {
+                library_ordinal: 1,
+                weak_import: false,
+                name: "_malloc".to_string(),
+                addend: 16,
+            },
+        ];
+        let fixup = |segment_index, offset, target, auth| ChainedFixup {
+            segment_index,
+            offset,
+            target,
+            auth,
+        };
+        let rebase = |target| ChainedTarget::Rebase { target, high8: 0 };
+        let bind = |ordinal, addend| ChainedTarget::Bind { ordinal, addend };
+        let auth = Some(PointerAuth {
+            key: PtrAuthKey::IA,
+            diversity: 0xbeef,
+            addr_div: true,
+        });
+        let fixups = vec![
+            fixup(1, 0x0, bind(0, 0), auth),
+            fixup(1, 0x8, rebase(0x3f00), None),
+            fixup(1, 0x20, bind(1, -8), None),
+            // misaligned to the stride, so the page has another chain.
+            fixup(1, 0x2c, rebase(0x3f10), auth),
+            fixup(1, 0x4010, bind(2, 0), None),
+            fixup(2, 0x0, rebase(0x8000), None),
+        ];
+
+        let mut text = vec![0; 0x4000];
+        let mut data = vec![0; 0x8000];
+        let mut data_const = vec![0; 0x4000];
+        let mut segments = [&mut text, &mut data, &mut data_const].map(|data| ChainedSegment {
+            segment_offset: 0,
+            data,
+        });
+        let format = choose_pointer_format(&fixups, true);
+        assert_eq!(format, PointerFormat::Arm64eUserland24);
+        let encoded = encode(&fixups, &imports, format, 0x4000, &mut segments).unwrap();
+
+        let parsed = ChainedFixups::parse(&encoded).unwrap();
+        assert_eq!(parsed.imports, imports);
+        assert_eq!(parsed.header.imports_format, ImportsFormat::Addend64);
+        assert!(parsed.segments[0].is_none());
+        let starts = parsed.segments[1].as_ref().unwrap();
+        assert_eq!(starts.page_starts, [vec![0x0, 0x2c], vec![0x10]]);
+
+        let walked = parsed.walk_segments(&[&text, &data, &data_const]).unwrap();
+        let mut walked_sorted = walked.clone();
+        walked_sorted.sort_by_key(|fixup| (fixup.segment_index, fixup.offset));
+        assert_eq!(walked_sorted, fixups);
+    }
+
+    #[test]
+    fn encode_ptr32_with_gaps() {
+        let fixups = [0x0, 0x4, 0x100, 0x104, 0x1000]
+            .iter()
+            .map(|offset| ChainedFixup {
+                segment_index: 0,
+                offset: *offset,
+                target: ChainedTarget::Rebase {
+                    target: 0x1000 + offset,
+                    high8: 0,
+                },
+                auth: None,
+            })
+            .collect::<Vec<_>>();
+        let mut data = vec![0; 0x2000];
+        let mut segments = [ChainedSegment {
+            segment_offset: 0x4000,
+            data: &mut data,
+        }];
+        let format = choose_pointer_format(&fixups, false);
+        let encoded = encode(&fixups, &[], format, 0x1000, &mut segments).unwrap();
+
+        let parsed = ChainedFixups::parse(&encoded).unwrap();
+        let starts = parsed.segments[0].as_ref().unwrap();
+        assert_eq!(starts.segment_offset, 0x4000);
+        // 0x100 is too far for the 5-bit `next` field.
+        assert_eq!(starts.page_starts, [vec![0x0, 0x100], vec![0x0]]);
+        assert_eq!(parsed.header.imports_format, ImportsFormat::Import);
+        assert_eq!(parsed.walk_segments(&[&data]).unwrap(), fixups);
+
+        // a fixup in a segment which is not given must not be dropped.
+        let mut fixups = fixups;
+        let mut data = vec![0; 0x2000];
+        let mut segments = [ChainedSegment {
+            segment_offset: 0x4000,
+            data: &mut data,
+        }];
+        fixups[4].segment_index = 1;
+        assert!(matches!(
+            encode(&fixups, &[], format, 0x1000, &mut segments),
+            Err(Error::InvalidValue { value: 1, .. })
+        ));
+        // a target which does not fit in 26 bits is an error rather than a panic.
+        fixups[4].segment_index = 0;
+        fixups[4].target = ChainedTarget::Rebase {
+            target: 1 << 26,
+            high8: 0,
+        };
+        assert!(matches!(
+            encode(&fixups, &[], format, 0x1000, &mut segments),
+            Err(Error::InvalidValue { value, .. }) if value == 1 << 26
+        ));
+    }
+}
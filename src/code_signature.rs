@@ -0,0 +1,1082 @@
+//! Code signature referred by `LC_CODE_SIGNATURE`.
+//!
+//! The signature is a SuperBlob, an index of blobs keyed by slot. The CodeDirectory in slot 0
+//! holds the hash of every page of the file and of the other blobs (special slots), and the CMS
+//! blob signs the CodeDirectory. Unlike the rest of the file, everything in the signature is
+//! big-endian.
+
+use crate::{
+    error::{Error, Result},
+    leb128::ByteReader,
+    load_command::LinkeditDataCommand,
+    view::MachOView,
+};
+use sha2::{Digest as _, Sha256, Sha384};
+use std::collections::BTreeSet;
+
+/// Magic of a single requirement.
+pub const CSMAGIC_REQUIREMENT: u32 = 0xfade0c00;
+/// Magic of the requirement set.
+pub const CSMAGIC_REQUIREMENTS: u32 = 0xfade0c01;
+pub const CSMAGIC_CODEDIRECTORY: u32 = 0xfade0c02;
+/// Magic of the SuperBlob embedded in Mach-O files.
+pub const CSMAGIC_EMBEDDED_SIGNATURE: u32 = 0xfade0cc0;
+/// Magic of the XML entitlements.
+pub const CSMAGIC_EMBEDDED_ENTITLEMENTS: u32 = 0xfade7171;
+/// Magic of the DER encoded entitlements.
+pub const CSMAGIC_EMBEDDED_DER_ENTITLEMENTS: u32 = 0xfade7172;
+/// Magic of the CMS signature.
+pub const CSMAGIC_BLOBWRAPPER: u32 = 0xfade0b01;
+
+pub const CSSLOT_CODEDIRECTORY: u32 = 0;
+pub const CSSLOT_INFOSLOT: u32 = 1;
+pub const CSSLOT_REQUIREMENTS: u32 = 2;
+pub const CSSLOT_RESOURCEDIR: u32 = 3;
+pub const CSSLOT_APPLICATION: u32 = 4;
+pub const CSSLOT_ENTITLEMENTS: u32 = 5;
+pub const CSSLOT_DER_ENTITLEMENTS: u32 = 7;
+/// The first of the CodeDirectories which use other hash types.
+pub const CSSLOT_ALTERNATE_CODEDIRECTORIES: u32 = 0x1000;
+pub const CSSLOT_SIGNATURESLOT: u32 = 0x10000;
+
+/// CodeDirectory version which has `scatterOffset`.
+pub const CS_SUPPORTSSCATTER: u32 = 0x20100;
+/// CodeDirectory version which has `teamOffset`.
+pub const CS_SUPPORTSTEAMID: u32 = 0x20200;
+/// CodeDirectory version which has `codeLimit64`.
+pub const CS_SUPPORTSCODELIMIT64: u32 = 0x20300;
+/// CodeDirectory version which has `execSegBase`, `execSegLimit` and `execSegFlags`.
+pub const CS_SUPPORTSEXECSEG: u32 = 0x20400;
+/// CodeDirectory version which has `runtime` and `preEncryptOffset`.
+pub const CS_SUPPORTSRUNTIME: u32 = 0x20500;
+
+/// CodeDirectory flag of ad-hoc signed code.
+pub const CS_ADHOC: u32 = 0x2;
+pub const CS_HARD: u32 = 0x100;
+pub const CS_KILL: u32 = 0x200;
+pub const CS_RESTRICT: u32 = 0x800;
+/// CodeDirectory flag to opt in the hardened runtime.
+pub const CS_RUNTIME: u32 = 0x10000;
+/// CodeDirectory flag of ad-hoc signatures made by the linker.
+pub const CS_LINKER_SIGNED: u32 = 0x20000;
+
+/// `exec_seg_flags` of the main executable.
+pub const CS_EXECSEG_MAIN_BINARY: u64 = 0x1;
+
+/// Requirement types in the requirement set.
+pub const REQUIREMENT_HOST: u32 = 1;
+pub const REQUIREMENT_GUEST: u32 = 2;
+pub const REQUIREMENT_DESIGNATED: u32 = 3;
+pub const REQUIREMENT_LIBRARY: u32 = 4;
+pub const REQUIREMENT_PLUGIN: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashType {
+    Sha1,
+    Sha256,
+    /// SHA-256 truncated to 20 bytes
+    Sha256Truncated,
+    Sha384,
+    /// Keeps the value as is.
+    Unknown(u8),
+}
+
+impl HashType {
+    pub fn from_u8(n: u8) -> Self {
+        match n {
+            1 => HashType::Sha1,
+            2 => HashType::Sha256,
+            3 => HashType::Sha256Truncated,
+            4 => HashType::Sha384,
+            n => HashType::Unknown(n),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            HashType::Sha1 => 1,
+            HashType::Sha256 => 2,
+            HashType::Sha256Truncated => 3,
+            HashType::Sha384 => 4,
+            HashType::Unknown(n) => n,
+        }
+    }
+
+    /// Size of a hash in bytes, or `None` for unknown types.
+    pub fn size(self) -> Option<usize> {
+        match self {
+            HashType::Sha1 | HashType::Sha256Truncated => Some(20),
+            HashType::Sha256 => Some(32),
+            HashType::Sha384 => Some(48),
+            HashType::Unknown(_) => None,
+        }
+    }
+
+    /// Hashes `data`, or returns `None` if this crate cannot compute hashes of this type.
+    /// SHA-1 is not supported.
+    pub fn digest(self, data: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            HashType::Sha256 => Some(Sha256::digest(data).to_vec()),
+            HashType::Sha256Truncated => Some(Sha256::digest(data)[..20].to_vec()),
+            HashType::Sha384 => Some(Sha384::digest(data).to_vec()),
+            HashType::Sha1 | HashType::Unknown(_) => None,
+        }
+    }
+}
+
+/// The SuperBlob which an embedded signature starts with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuperBlob {
+    /// `CSMAGIC_EMBEDDED_SIGNATURE`
+    pub magic: u32,
+    /// total length of the SuperBlob, including the blobs
+    pub length: u32,
+    pub index: Vec<BlobIndex>,
+}
+
+/// An entry of the index of a SuperBlob.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobIndex {
+    /// One of `CSSLOT_*`
+    pub slot: u32,
+    /// offset of the blob from the start of the SuperBlob
+    pub offset: u32,
+    pub blob: Blob,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Blob {
+    CodeDirectory(CodeDirectory),
+    /// The requirement set.
+    Requirements(Vec<Requirement>),
+    /// Entitlements as an XML property list.
+    Entitlements(String),
+    /// Entitlements encoded in DER.
+    DerEntitlements(Vec<u8>),
+    /// CMS signature of the CodeDirectories. Empty for ad-hoc signatures.
+    Cms(Vec<u8>),
+    /// A blob this crate does not understand, kept as its magic and the bytes following the
+    /// length.
+    Unknown {
+        magic: u32,
+        data: Vec<u8>,
+    },
+}
+
+/// A requirement in the requirement set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    /// One of `REQUIREMENT_*`
+    pub req_type: u32,
+    /// The compiled requirement expression, which follows the magic and length of the blob.
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeDirectory {
+    pub version: u32,
+    /// `CS_*` flags
+    pub flags: u32,
+    pub hash_type: HashType,
+    /// platform identifier, zero if not platform binary
+    pub platform: u8,
+    /// log2 of the page size in bytes, 12 to 16. 0 means the code is hashed as a single page.
+    pub page_size_log2: u8,
+    /// limit to main image signature range. `codeLimit64` is used if it is not zero.
+    pub code_limit: u64,
+    pub identifier: String,
+    /// Only in versions since `CS_SUPPORTSTEAMID`.
+    pub team_id: Option<String>,
+    /// offset of the executable segment
+    pub exec_seg_base: u64,
+    /// limit of the executable segment
+    pub exec_seg_limit: u64,
+    /// `CS_EXECSEG_*` flags
+    pub exec_seg_flags: u64,
+    /// runtime version, which is the SDK version of the hardened runtime
+    pub runtime: u32,
+    /// offset of the obsolete scatter vector, zero if there is none. The vector is not kept.
+    pub scatter_offset: u32,
+    /// offset of the hashes of the unencrypted pages of an encrypted image, zero if there are
+    /// none. The hashes are not kept.
+    pub pre_encrypt_offset: u32,
+    /// `special_slots[i]` is the hash of the blob in slot `i + 1`. All zeros if the slot is
+    /// empty.
+    pub special_slots: Vec<Vec<u8>>,
+    /// Hash of every page up to `code_limit`.
+    pub code_slots: Vec<Vec<u8>>,
+}
+
+/// The result of `verify`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Verification {
+    /// Slot of the CodeDirectory which was verified.
+    pub code_directory_slot: u32,
+    /// Indices of the pages whose hash does not match their code slot. Pages beyond the end
+    /// of the file, and code slots beyond `code_limit` count as mismatches.
+    pub mismatched_pages: Vec<usize>,
+    /// Special slots whose hash does not match their contents. Contents which are hashed but
+    /// missing, and contents which are present but not hashed count as mismatches.
+    pub mismatched_slots: Vec<u32>,
+    /// Special slots which are hashed but were not checked, because their contents are not in
+    /// the file.
+    pub unchecked_slots: Vec<u32>,
+}
+
+impl Verification {
+    /// Returns `true` if no page or special slot mismatches.
+    pub fn is_valid(&self) -> bool {
+        self.mismatched_pages.is_empty() && self.mismatched_slots.is_empty()
+    }
+}
+
+impl SuperBlob {
+    /// Parses the signature, which is the whole data referred by `LC_CODE_SIGNATURE`. Bytes
+    /// after `length` are padding and ignored.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        Self::parse_at(data, 0)
+    }
+
+    pub(crate) fn parse_at(data: &[u8], base: u64) -> Result<Self> {
+        let mut read = ByteReader::new(data, base);
+        let magic = read.read_be_u32()?;
+        if magic != CSMAGIC_EMBEDDED_SIGNATURE {
+            return Err(Error::BadMagic {
+                offset: base,
+                magic,
+            });
+        }
+        let length = read.read_be_u32()?;
+        let data = data.get(..length as usize).ok_or(Error::Truncated {
+            offset: base + data.len() as u64,
+        })?;
+
+        let mut read = ByteReader::new(data, base);
+        read.set_pos(8);
+        let count = read.read_be_u32()?;
+        let entries = (0..count)
+            .map(|_| Ok((read.read_be_u32()?, read.read_be_u32()?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let index = entries
+            .into_iter()
+            .map(|(slot, offset)| {
+                let blob = data.get(offset as usize..).ok_or(Error::Truncated {
+                    offset: base + data.len() as u64,
+                })?;
+                Ok(BlobIndex {
+                    slot,
+                    offset,
+                    blob: Blob::parse_at(blob, base + u64::from(offset))?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(SuperBlob {
+            magic,
+            length,
+            index,
+        })
+    }
+
+    /// Creates an embedded signature of `(slot, blob)` pairs, and computes the offsets and
+    /// the length. Blobs are placed in the given order.
+    ///
+    /// Fails if a blob cannot be serialized, as in `Blob::to_bytes`.
+    pub fn new(blobs: Vec<(u32, Blob)>) -> Result<Self> {
+        let mut offset = 12 + 8 * blobs.len() as u32;
+        let index = blobs
+            .into_iter()
+            .map(|(slot, blob)| {
+                let index = BlobIndex { slot, offset, blob };
+                offset += index.blob.to_bytes()?.len() as u32;
+                Ok(index)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(SuperBlob {
+            magic: CSMAGIC_EMBEDDED_SIGNATURE,
+            length: offset,
+            index,
+        })
+    }
+
+    /// Serializes the SuperBlob, placing every blob at its `offset`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        write_be32(&mut buf, self.magic);
+        write_be32(&mut buf, self.length);
+        write_be32(&mut buf, self.index.len() as u32);
+        for index in self.index.iter() {
+            write_be32(&mut buf, index.slot);
+            write_be32(&mut buf, index.offset);
+        }
+
+        for index in self.index.iter() {
+            let blob = index.blob.to_bytes()?;
+            let start = index.offset as usize;
+            let end = start + blob.len();
+            if buf.len() < end {
+                buf.resize(end, 0);
+            }
+            buf[start..end].copy_from_slice(&blob);
+        }
+        if buf.len() < self.length as usize {
+            buf.resize(self.length as usize, 0);
+        }
+        Ok(buf)
+    }
+
+    /// Returns the blob in `slot`, if any.
+    pub fn blob(&self, slot: u32) -> Option<&Blob> {
+        self.index.iter().find(|i| i.slot == slot).map(|i| &i.blob)
+    }
+
+    /// Returns the CodeDirectory in `CSSLOT_CODEDIRECTORY`, if any.
+    pub fn code_directory(&self) -> Option<&CodeDirectory> {
+        match self.blob(CSSLOT_CODEDIRECTORY)? {
+            Blob::CodeDirectory(cd) => Some(cd),
+            _ => None,
+        }
+    }
+}
+
+impl Blob {
+    pub fn magic(&self) -> u32 {
+        match self {
+            Blob::CodeDirectory(_) => CSMAGIC_CODEDIRECTORY,
+            Blob::Requirements(_) => CSMAGIC_REQUIREMENTS,
+            Blob::Entitlements(_) => CSMAGIC_EMBEDDED_ENTITLEMENTS,
+            Blob::DerEntitlements(_) => CSMAGIC_EMBEDDED_DER_ENTITLEMENTS,
+            Blob::Cms(_) => CSMAGIC_BLOBWRAPPER,
+            Blob::Unknown { magic, .. } => *magic,
+        }
+    }
+
+    /// Serializes the blob, including its magic and length.
+    ///
+    /// Only a CodeDirectory can fail, as in `CodeDirectory::to_bytes`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let payload = match self {
+            Blob::CodeDirectory(cd) => return cd.to_bytes(),
+            Blob::Requirements(reqs) => return Ok(write_requirements(reqs)),
+            Blob::Entitlements(xml) => xml.as_bytes(),
+            Blob::DerEntitlements(data) | Blob::Cms(data) | Blob::Unknown { data, .. } => data,
+        };
+        Ok(write_blob(self.magic(), payload))
+    }
+
+    /// Parses the blob at the start of `data`, whose file offset is `base`.
+    fn parse_at(data: &[u8], base: u64) -> Result<Self> {
+        let (magic, blob) = read_blob(data, base)?;
+        let payload = blob[8..].to_vec();
+
+        match magic {
+            CSMAGIC_CODEDIRECTORY => CodeDirectory::parse_at(blob, base).map(Blob::CodeDirectory),
+            CSMAGIC_REQUIREMENTS => read_requirements(blob, base).map(Blob::Requirements),
+            CSMAGIC_EMBEDDED_ENTITLEMENTS => String::from_utf8(payload)
+                .map(Blob::Entitlements)
+                .map_err(|_| Error::InvalidString { offset: base + 8 }),
+            CSMAGIC_EMBEDDED_DER_ENTITLEMENTS => Ok(Blob::DerEntitlements(payload)),
+            CSMAGIC_BLOBWRAPPER => Ok(Blob::Cms(payload)),
+            magic => Ok(Blob::Unknown {
+                magic,
+                data: payload,
+            }),
+        }
+    }
+}
+
+impl CodeDirectory {
+    /// Serializes the CodeDirectory with the fields of its `version`. The identifier and the
+    /// team ID follow the fixed part, and the special slots and code slots follow them.
+    ///
+    /// Fails with `Error::InvalidValue` if `version` is newer than `CS_SUPPORTSRUNTIME`, if the
+    /// version cannot represent `team_id` or `code_limit`, if the CodeDirectory refers to a
+    /// scatter vector or pre-encrypt hashes, which are not kept, or if the hashes have
+    /// different sizes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let version = self.version;
+        if version > CS_SUPPORTSRUNTIME || (self.team_id.is_some() && version < CS_SUPPORTSTEAMID) {
+            return Err(Error::InvalidValue {
+                kind: "code directory version",
+                value: version.into(),
+            });
+        }
+        for (kind, offset) in [
+            ("code directory scatter offset", self.scatter_offset),
+            ("code directory pre-encrypt offset", self.pre_encrypt_offset),
+        ] {
+            if offset != 0 {
+                return Err(Error::InvalidValue {
+                    kind,
+                    value: offset.into(),
+                });
+            }
+        }
+        let (code_limit32, code_limit64) = match u32::try_from(self.code_limit) {
+            Ok(code_limit) => (code_limit, 0),
+            Err(_) if version >= CS_SUPPORTSCODELIMIT64 => (0, self.code_limit),
+            Err(_) => {
+                return Err(Error::InvalidValue {
+                    kind: "code limit",
+                    value: self.code_limit,
+                })
+            }
+        };
+
+        let header_size: u32 = 44
+            + [
+                (CS_SUPPORTSSCATTER, 4),
+                (CS_SUPPORTSTEAMID, 4),
+                (CS_SUPPORTSCODELIMIT64, 12),
+                (CS_SUPPORTSEXECSEG, 24),
+                (CS_SUPPORTSRUNTIME, 8),
+            ]
+            .iter()
+            .filter(|(since, _)| version >= *since)
+            .map(|(_, size)| size)
+            .sum::<u32>();
+
+        let hash_size = self.hash_size();
+        let ident_offset = header_size;
+        let mut strings_end = ident_offset + self.identifier.len() as u32 + 1;
+        let team_offset = match &self.team_id {
+            Some(team_id) => {
+                let offset = strings_end;
+                strings_end += team_id.len() as u32 + 1;
+                offset
+            }
+            None => 0,
+        };
+        let hash_offset = strings_end + (self.special_slots.len() * hash_size) as u32;
+        let length = hash_offset + (self.code_slots.len() * hash_size) as u32;
+
+        let mut buf = Vec::new();
+        write_be32(&mut buf, CSMAGIC_CODEDIRECTORY);
+        write_be32(&mut buf, length);
+        write_be32(&mut buf, version);
+        write_be32(&mut buf, self.flags);
+        write_be32(&mut buf, hash_offset);
+        write_be32(&mut buf, ident_offset);
+        write_be32(&mut buf, self.special_slots.len() as u32);
+        write_be32(&mut buf, self.code_slots.len() as u32);
+        write_be32(&mut buf, code_limit32);
+        buf.extend_from_slice(&[
+            hash_size as u8,
+            self.hash_type.to_u8(),
+            self.platform,
+            self.page_size_log2,
+        ]);
+        write_be32(&mut buf, 0); // spare2
+        if version >= CS_SUPPORTSSCATTER {
+            write_be32(&mut buf, self.scatter_offset);
+        }
+        if version >= CS_SUPPORTSTEAMID {
+            write_be32(&mut buf, team_offset);
+        }
+        if version >= CS_SUPPORTSCODELIMIT64 {
+            write_be32(&mut buf, 0); // spare3
+            buf.extend_from_slice(&code_limit64.to_be_bytes());
+        }
+        if version >= CS_SUPPORTSEXECSEG {
+            buf.extend_from_slice(&self.exec_seg_base.to_be_bytes());
+            buf.extend_from_slice(&self.exec_seg_limit.to_be_bytes());
+            buf.extend_from_slice(&self.exec_seg_flags.to_be_bytes());
+        }
+        if version >= CS_SUPPORTSRUNTIME {
+            write_be32(&mut buf, self.runtime);
+            write_be32(&mut buf, self.pre_encrypt_offset);
+        }
+
+        buf.extend_from_slice(self.identifier.as_bytes());
+        buf.push(0);
+        if let Some(team_id) = &self.team_id {
+            buf.extend_from_slice(team_id.as_bytes());
+            buf.push(0);
+        }
+        for hash in self
+            .special_slots
+            .iter()
+            .rev()
+            .chain(self.code_slots.iter())
+        {
+            if hash.len() != hash_size {
+                return Err(Error::InvalidValue {
+                    kind: "hash size",
+                    value: hash.len() as u64,
+                });
+            }
+            buf.extend_from_slice(hash);
+        }
+        Ok(buf)
+    }
+
+    /// Size of a hash in bytes. It is taken from the slots if the hash type is unknown.
+    fn hash_size(&self) -> usize {
+        self.hash_type.size().unwrap_or_else(|| {
+            let mut slots = self.code_slots.iter().chain(self.special_slots.iter());
+            slots.next().map_or(0, Vec::len)
+        })
+    }
+
+    /// Parses the CodeDirectory blob `data`, including its magic and length.
+    fn parse_at(data: &[u8], base: u64) -> Result<Self> {
+        let mut read = ByteReader::new(data, base);
+        read.set_pos(8);
+        let version = read.read_be_u32()?;
+        let flags = read.read_be_u32()?;
+        let hash_offset = read.read_be_u32()?;
+        let ident_offset = read.read_be_u32()?;
+        let n_special_slots = read.read_be_u32()?;
+        let n_code_slots = read.read_be_u32()?;
+        let code_limit32 = read.read_be_u32()?;
+        let hash_size = read.read_u8()?;
+        let hash_type = HashType::from_u8(read.read_u8()?);
+        let platform = read.read_u8()?;
+        let page_size_offset = read.offset();
+        let page_size_log2 = read.read_u8()?;
+        // 0 hashes the code as a single page, and real pages are 4K to 64K.
+        if page_size_log2 != 0 && !(12..=16).contains(&page_size_log2) {
+            return Err(Error::UnknownValue {
+                offset: page_size_offset,
+                kind: "code directory page size",
+                value: page_size_log2.into(),
+            });
+        }
+        let _spare2 = read.read_be_u32()?;
+
+        let mut cd = CodeDirectory {
+            version,
+            flags,
+            hash_type,
+            platform,
+            page_size_log2,
+            code_limit: code_limit32.into(),
+            identifier: String::new(),
+            team_id: None,
+            exec_seg_base: 0,
+            exec_seg_limit: 0,
+            exec_seg_flags: 0,
+            runtime: 0,
+            scatter_offset: 0,
+            pre_encrypt_offset: 0,
+            special_slots: Vec::new(),
+            code_slots: Vec::new(),
+        };
+
+        let mut team_offset = 0;
+        if version >= CS_SUPPORTSSCATTER {
+            cd.scatter_offset = read.read_be_u32()?;
+        }
+        if version >= CS_SUPPORTSTEAMID {
+            team_offset = read.read_be_u32()?;
+        }
+        if version >= CS_SUPPORTSCODELIMIT64 {
+            let _spare3 = read.read_be_u32()?;
+            match read.read_be_u64()? {
+                0 => {}
+                code_limit64 => cd.code_limit = code_limit64,
+            }
+        }
+        if version >= CS_SUPPORTSEXECSEG {
+            cd.exec_seg_base = read.read_be_u64()?;
+            cd.exec_seg_limit = read.read_be_u64()?;
+            cd.exec_seg_flags = read.read_be_u64()?;
+        }
+        if version >= CS_SUPPORTSRUNTIME {
+            cd.runtime = read.read_be_u32()?;
+            cd.pre_encrypt_offset = read.read_be_u32()?;
+        }
+
+        read.set_pos(ident_offset as usize);
+        cd.identifier = read.read_cstr()?.to_string();
+        if team_offset != 0 {
+            read.set_pos(team_offset as usize);
+            cd.team_id = Some(read.read_cstr()?.to_string());
+        }
+
+        // special slots are stored backwards in front of the code slots.
+        let hash_size = usize::from(hash_size);
+        let special_size = n_special_slots as usize * hash_size;
+        let special_start = (hash_offset as usize)
+            .checked_sub(special_size)
+            .ok_or(Error::Truncated { offset: base })?;
+        read.set_pos(special_start);
+        let mut special_slots = (0..n_special_slots)
+            .map(|_| read.read_bytes(hash_size).map(<[u8]>::to_vec))
+            .collect::<Result<Vec<_>>>()?;
+        special_slots.reverse();
+        cd.special_slots = special_slots;
+
+        cd.code_slots = (0..n_code_slots)
+            .map(|_| read.read_bytes(hash_size).map(<[u8]>::to_vec))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(cd)
+    }
+}
+
+/// Verifies the code signature of the file viewed by `view` by hashing everything again.
+/// Returns `None` if the file has no `LC_CODE_SIGNATURE`.
+///
+/// The first CodeDirectory whose hash type is supported is used. Every page up to
+/// `code_limit` is checked against the code slots, and the blobs of the signature are
+/// checked against the special slots. The Info.plist slot is checked against the
+/// `__TEXT,__info_plist` section. `code_resources` is the `_CodeSignature/CodeResources` file
+/// of the bundle, and the resource dir slot is left unchecked without it.
+pub fn verify(view: &MachOView, code_resources: Option<&[u8]>) -> Result<Option<Verification>> {
+    let mut signature = None;
+    for lc in view.load_commands() {
+        if let Some((cmd, data)) = lc?.linkedit_data()? {
+            if cmd.cmd == LinkeditDataCommand::CODE_SIGNATURE {
+                signature = Some((u64::from(cmd.dataoff), data));
+                break;
+            }
+        }
+    }
+    let (base, data) = match signature {
+        Some(signature) => signature,
+        None => return Ok(None),
+    };
+    let sig = SuperBlob::parse_at(data, base)?;
+
+    let mut cds = sig.index.iter().filter_map(|index| match &index.blob {
+        Blob::CodeDirectory(cd) => Some((index, cd)),
+        _ => None,
+    });
+    let (cd_index, cd) = match cds
+        .clone()
+        .find(|(_, cd)| cd.hash_type.digest(&[]).is_some())
+    {
+        Some(found) => found,
+        None => {
+            // report the hash type of the primary CodeDirectory
+            let (index, cd) = cds.next().ok_or(Error::UnknownValue {
+                offset: base,
+                kind: "code signature without code directory",
+                value: sig.index.len() as u64,
+            })?;
+            return Err(Error::UnknownValue {
+                offset: base + u64::from(index.offset) + 37,
+                kind: "code directory hash type",
+                value: cd.hash_type.to_u8().into(),
+            });
+        }
+    };
+    let hash = |data: &[u8]| {
+        cd.hash_type.digest(data).ok_or(Error::UnknownValue {
+            offset: base + u64::from(cd_index.offset) + 37,
+            kind: "code directory hash type",
+            value: cd.hash_type.to_u8().into(),
+        })
+    };
+
+    let mut result = Verification {
+        code_directory_slot: cd_index.slot,
+        ..Verification::default()
+    };
+
+    // pages
+    let file = view.data();
+    let page_size = match cd.page_size_log2 {
+        0 => cd.code_limit.max(1),
+        n => 1u64 << n,
+    };
+    let n_pages = cd.code_limit.div_ceil(page_size);
+    let n_file_pages = cd.code_limit.min(file.len() as u64).div_ceil(page_size);
+    for i in 0..(cd.code_slots.len() as u64).max(n_file_pages) {
+        let start = i * page_size;
+        let end = (start + page_size).min(cd.code_limit);
+        let matches = match cd.code_slots.get(i as usize) {
+            Some(slot) if i < n_pages && end <= file.len() as u64 => {
+                hash(&file[start as usize..end as usize])? == *slot
+            }
+            _ => false,
+        };
+        if !matches {
+            result.mismatched_pages.push(i as usize);
+        }
+    }
+
+    // special slots
+    let mut slots = (1..=cd.special_slots.len() as u32).collect::<BTreeSet<_>>();
+    slots.extend(
+        sig.index
+            .iter()
+            .map(|index| index.slot)
+            .filter(|slot| (1..CSSLOT_ALTERNATE_CODEDIRECTORIES).contains(slot)),
+    );
+    let info_plist = info_plist(view)?;
+    for slot in slots {
+        let expected = cd
+            .special_slots
+            .get(slot as usize - 1)
+            .filter(|hash| hash.iter().any(|b| *b != 0));
+        let contents = match slot {
+            CSSLOT_INFOSLOT => info_plist,
+            CSSLOT_RESOURCEDIR => match code_resources {
+                Some(code_resources) => Some(code_resources),
+                None => {
+                    if expected.is_some() {
+                        result.unchecked_slots.push(slot);
+                    }
+                    continue;
+                }
+            },
+            slot => match sig.index.iter().find(|index| index.slot == slot) {
+                Some(index) => {
+                    let offset = index.offset as usize;
+                    Some(read_blob(&data[offset..], base + offset as u64)?.1)
+                }
+                None => None,
+            },
+        };
+        let matches = match (expected, contents) {
+            (Some(expected), Some(contents)) => hash(contents)? == *expected,
+            (None, None) => true,
+            _ => false,
+        };
+        if !matches {
+            result.mismatched_slots.push(slot);
+        }
+    }
+
+    Ok(Some(result))
+}
+
+/// Returns the contents of the `__TEXT,__info_plist` section, if any.
+fn info_plist<'a>(view: &MachOView<'a>) -> Result<Option<&'a [u8]>> {
+    for seg in view.segments() {
+        for sect in seg?.sections() {
+            let sect = sect?;
+            if sect.segname == "__TEXT" && sect.sectname == "__info_plist" {
+                return sect.data();
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn write_be32(buf: &mut Vec<u8>, n: u32) {
+    buf.extend_from_slice(&n.to_be_bytes());
+}
+
+/// Serializes a blob of `magic` whose content is `payload`.
+fn write_blob(magic: u32, payload: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(8 + payload.len());
+    write_be32(&mut buf, magic);
+    write_be32(&mut buf, 8 + payload.len() as u32);
+    buf.extend_from_slice(payload);
+    buf
+}
+
+/// Serializes the requirement set, placing the requirements after the index.
+fn write_requirements(reqs: &[Requirement]) -> Vec<u8> {
+    let blobs = reqs
+        .iter()
+        .map(|req| write_blob(CSMAGIC_REQUIREMENT, &req.data))
+        .collect::<Vec<_>>();
+
+    let mut offset = 12 + 8 * reqs.len() as u32;
+    let mut index = Vec::new();
+    for (req, blob) in reqs.iter().zip(blobs.iter()) {
+        write_be32(&mut index, req.req_type);
+        write_be32(&mut index, offset);
+        offset += blob.len() as u32;
+    }
+
+    let mut buf = Vec::new();
+    write_be32(&mut buf, CSMAGIC_REQUIREMENTS);
+    write_be32(&mut buf, offset);
+    write_be32(&mut buf, reqs.len() as u32);
+    buf.extend_from_slice(&index);
+    for blob in blobs.iter() {
+        buf.extend_from_slice(blob);
+    }
+    buf
+}
+
+/// Reads the magic and length of the blob at the start of `data`, and returns the magic and the
+/// whole blob.
+fn read_blob(data: &[u8], base: u64) -> Result<(u32, &[u8])> {
+    let mut read = ByteReader::new(data, base);
+    let magic = read.read_be_u32()?;
+    let length = read.read_be_u32()?;
+    if length < 8 {
+        return Err(Error::UnknownValue {
+            offset: base + 4,
+            kind: "blob length",
+            value: length.into(),
+        });
+    }
+    let blob = data.get(..length as usize).ok_or(Error::Truncated {
+        offset: base + data.len() as u64,
+    })?;
+    Ok((magic, blob))
+}
+
+/// Reads the requirement set, which is a SuperBlob of requirements.
+fn read_requirements(data: &[u8], base: u64) -> Result<Vec<Requirement>> {
+    let mut read = ByteReader::new(data, base);
+    read.set_pos(8);
+    let count = read.read_be_u32()?;
+    let entries = (0..count)
+        .map(|_| Ok((read.read_be_u32()?, read.read_be_u32()?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    entries
+        .into_iter()
+        .map(|(req_type, offset)| {
+            let base = base + u64::from(offset);
+            let data = data
+                .get(offset as usize..)
+                .ok_or(Error::Truncated { offset: base })?;
+            let (magic, blob) = read_blob(data, base)?;
+            if magic != CSMAGIC_REQUIREMENT {
+                return Err(Error::BadMagic {
+                    offset: base,
+                    magic,
+                });
+            }
+            Ok(Requirement {
+                req_type,
+                data: blob[8..].to_vec(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::ExecutableBuilder, file::MachOFile, load_command::LoadCommand};
+
+    fn be32(buf: &mut Vec<u8>, n: u32) {
+        buf.extend_from_slice(&n.to_be_bytes());
+    }
+
+    /// Builds a version 0x20400 CodeDirectory with two special slots and two code slots whose
+    /// hashes are filled with their slot numbers.
+    fn code_directory() -> Vec<u8> {
+        let ident_offset = 88;
+        let hash_offset = ident_offset + 8 + 2 * 32;
+        let length = hash_offset + 2 * 32;
+
+        let mut buf = Vec::new();
+        be32(&mut buf, CSMAGIC_CODEDIRECTORY);
+        be32(&mut buf, length);
+        be32(&mut buf, CS_SUPPORTSEXECSEG);
+        be32(&mut buf, CS_ADHOC | CS_LINKER_SIGNED);
+        be32(&mut buf, hash_offset);
+        be32(&mut buf, ident_offset);
+        be32(&mut buf, 2); // nSpecialSlots
+        be32(&mut buf, 2); // nCodeSlots
+        be32(&mut buf, 0x1800); // codeLimit
+        buf.extend_from_slice(&[32, 2, 0, 12]);
+        be32(&mut buf, 0); // spare2
+        be32(&mut buf, 0); // scatterOffset
+        be32(&mut buf, 0); // teamOffset
+        be32(&mut buf, 0); // spare3
+        buf.extend_from_slice(&0u64.to_be_bytes()); // codeLimit64
+        buf.extend_from_slice(&0u64.to_be_bytes()); // execSegBase
+        buf.extend_from_slice(&0x1000u64.to_be_bytes()); // execSegLimit
+        buf.extend_from_slice(&CS_EXECSEG_MAIN_BINARY.to_be_bytes());
+        assert_eq!(buf.len(), ident_offset as usize);
+        buf.extend_from_slice(b"a.out\0\0\0");
+        buf.extend_from_slice(&[2; 32]);
+        buf.extend_from_slice(&[1; 32]);
+        buf.extend_from_slice(&[0x10; 32]);
+        buf.extend_from_slice(&[0x11; 32]);
+        assert_eq!(buf.len(), length as usize);
+        buf
+    }
+
+    #[test]
+    fn parse_super_blob() {
+        let cd = code_directory();
+        let entitlements = b"<plist version=\"1.0\"><dict/></plist>";
+        let cd_offset = 12 + 8 * 4;
+        let reqs_offset = cd_offset + cd.len() as u32;
+        let ents_offset = reqs_offset + 12;
+        let cms_offset = ents_offset + 8 + entitlements.len() as u32;
+        let length = cms_offset + 8;
+
+        let mut buf = Vec::new();
+        be32(&mut buf, CSMAGIC_EMBEDDED_SIGNATURE);
+        be32(&mut buf, length);
+        be32(&mut buf, 4);
+        for (slot, offset) in [
+            (CSSLOT_CODEDIRECTORY, cd_offset),
+            (CSSLOT_REQUIREMENTS, reqs_offset),
+            (CSSLOT_ENTITLEMENTS, ents_offset),
+            (CSSLOT_SIGNATURESLOT, cms_offset),
+        ] {
+            be32(&mut buf, slot);
+            be32(&mut buf, offset);
+        }
+        buf.extend_from_slice(&cd);
+        be32(&mut buf, CSMAGIC_REQUIREMENTS);
+        be32(&mut buf, 12);
+        be32(&mut buf, 0);
+        be32(&mut buf, CSMAGIC_EMBEDDED_ENTITLEMENTS);
+        be32(&mut buf, 8 + entitlements.len() as u32);
+        buf.extend_from_slice(entitlements);
+        be32(&mut buf, CSMAGIC_BLOBWRAPPER);
+        be32(&mut buf, 8);
+        // padding up to the datasize of LC_CODE_SIGNATURE
+        buf.extend_from_slice(&[0; 16]);
+
+        let sig = SuperBlob::parse(&buf).unwrap();
+        assert_eq!(sig.length, length);
+        assert_eq!(sig.index.len(), 4);
+
+        let cd = sig.code_directory().unwrap();
+        assert_eq!(cd.identifier, "a.out");
+        assert_eq!(cd.team_id, None);
+        assert_eq!(cd.hash_type, HashType::Sha256);
+        assert_eq!((cd.page_size_log2, cd.code_limit), (12, 0x1800));
+        assert_eq!(cd.exec_seg_limit, 0x1000);
+        assert_eq!(cd.exec_seg_flags, CS_EXECSEG_MAIN_BINARY);
+        assert_eq!(cd.special_slots, [vec![1; 32], vec![2; 32]]);
+        assert_eq!(cd.code_slots, [vec![0x10; 32], vec![0x11; 32]]);
+
+        assert_eq!(
+            sig.blob(CSSLOT_REQUIREMENTS),
+            Some(&Blob::Requirements(vec![]))
+        );
+        assert_eq!(
+            sig.blob(CSSLOT_ENTITLEMENTS),
+            Some(&Blob::Entitlements(
+                String::from_utf8(entitlements.to_vec()).unwrap()
+            ))
+        );
+        assert_eq!(sig.blob(CSSLOT_SIGNATURESLOT), Some(&Blob::Cms(vec![])));
+
+        // a page size which does not fit in u64
+        buf[cd_offset as usize + 39] = 64;
+        assert!(matches!(
+            SuperBlob::parse(&buf),
+            Err(Error::UnknownValue { offset, value: 64, .. }) if offset == u64::from(cd_offset) + 39
+        ));
+    }
+
+    #[test]
+    fn build_and_parse_super_blob() {
+        let cd = CodeDirectory {
+            version: CS_SUPPORTSRUNTIME,
+            flags: CS_RUNTIME,
+            hash_type: HashType::Sha384,
+            platform: 0,
+            page_size_log2: 14,
+            code_limit: 0x1_0000_0000,
+            identifier: "com.example.tool".to_string(),
+            team_id: Some("ABCDE12345".to_string()),
+            exec_seg_base: 0,
+            exec_seg_limit: 0x4000,
+            exec_seg_flags: CS_EXECSEG_MAIN_BINARY,
+            runtime: 0x000d_0000,
+            scatter_offset: 0,
+            pre_encrypt_offset: 0,
+            special_slots: vec![vec![0; 48], vec![2; 48]],
+            code_slots: vec![vec![0xaa; 48]; 3],
+        };
+        let sig = SuperBlob::new(vec![
+            (CSSLOT_CODEDIRECTORY, Blob::CodeDirectory(cd)),
+            (
+                CSSLOT_REQUIREMENTS,
+                Blob::Requirements(vec![Requirement {
+                    req_type: REQUIREMENT_DESIGNATED,
+                    data: vec![0, 0, 0, 1, 0, 0, 0, 0],
+                }]),
+            ),
+            (
+                CSSLOT_DER_ENTITLEMENTS,
+                Blob::DerEntitlements(vec![0x70, 0]),
+            ),
+            (CSSLOT_SIGNATURESLOT, Blob::Cms(vec![])),
+        ])
+        .unwrap();
+
+        let bytes = sig.to_bytes().unwrap();
+        assert_eq!(bytes.len(), sig.length as usize);
+        assert_eq!(SuperBlob::parse(&bytes).unwrap(), sig);
+
+        // versions newer than this crate knows are an error rather than a panic, and so are
+        // the parts of older versions which are not kept.
+        let mut cd = sig.code_directory().unwrap().clone();
+        cd.version = CS_SUPPORTSRUNTIME + 0x100;
+        assert!(matches!(
+            Blob::CodeDirectory(cd.clone()).to_bytes(),
+            Err(Error::InvalidValue { value, .. }) if value == u64::from(cd.version)
+        ));
+        cd.version = CS_SUPPORTSRUNTIME;
+        cd.pre_encrypt_offset = 0x100;
+        assert!(cd.to_bytes().is_err());
+    }
+
+    #[test]
+    fn parse_truncated_code_directory() {
+        let mut cd = code_directory();
+        // nCodeSlots points past the end of the blob
+        cd[28..32].copy_from_slice(&3u32.to_be_bytes());
+
+        let mut buf = Vec::new();
+        be32(&mut buf, CSMAGIC_EMBEDDED_SIGNATURE);
+        be32(&mut buf, 20 + cd.len() as u32);
+        be32(&mut buf, 1);
+        be32(&mut buf, CSSLOT_CODEDIRECTORY);
+        be32(&mut buf, 20);
+        buf.extend_from_slice(&cd);
+
+        let err = SuperBlob::parse_at(&buf, 0x4000).unwrap_err();
+        assert!(matches!(err, Error::Truncated { offset } if offset == 0x4000 + 20 + 224));
+    }
+
+    #[test]
+    fn verify_signed_executable() {
+        let mut builder = ExecutableBuilder::new();
+        let text = builder.add_text_section("__text", 4, vec![0x90; 0x1801]);
+        builder.define_symbol("start", text, 0, true).unwrap();
+        builder.entry_symbol("start");
+        let mut buf = Vec::new();
+        builder.write(&mut buf).unwrap();
+
+        let mut file = MachOFile::parse(&buf).unwrap();
+        file.sign_adhoc("a.out", 0).unwrap();
+        let mut buf = Vec::new();
+        file.write(&mut buf).unwrap();
+
+        let check = |buf: &[u8]| {
+            let view = MachOView::parse(buf).unwrap();
+            verify(&view, None).unwrap().unwrap()
+        };
+        let result = check(&buf);
+        assert!(result.is_valid());
+        assert_eq!(result.code_directory_slot, CSSLOT_CODEDIRECTORY);
+
+        // modify the second page after signing
+        buf[0x1005] ^= 0xff;
+        let result = check(&buf);
+        assert_eq!(result.mismatched_pages, [1]);
+        assert!(result.mismatched_slots.is_empty());
+        buf[0x1005] ^= 0xff;
+
+        // break the hash of the requirements. Special slots are stored backwards before the
+        // code slots.
+        let dataoff = file
+            .load_commands
+            .iter()
+            .find_map(|lc| match lc {
+                LoadCommand::LinkeditData(cmd) => Some(cmd.dataoff as usize),
+                _ => None,
+            })
+            .unwrap();
+        let cd = dataoff + 12 + 8 * 3;
+        let hash_offset = u32::from_be_bytes(buf[cd + 16..cd + 20].try_into().unwrap());
+        buf[cd + hash_offset as usize - 2 * 32] ^= 0xff;
+        let result = check(&buf);
+        assert!(result.mismatched_pages.is_empty());
+        assert_eq!(result.mismatched_slots, [CSSLOT_REQUIREMENTS]);
+    }
+}
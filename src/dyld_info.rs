@@ -0,0 +1,986 @@
+//! Decoding and encoding of the compressed dyld information referred by `LC_DYLD_INFO(_ONLY)`.
+//!
+//! Rebase and bind information is a stream of opcodes which drives a small state machine.
+//! Decoders execute the stream and return one record for every location to be fixed up, and
+//! encoders produce the stream back from the records.
+
+use crate::{
+    error::{check, Error, Result},
+    export_trie::{self, Export},
+    leb128::{write_sleb, write_uleb, ByteReader},
+    load_command::DyldInfoCommand,
+    view::slice,
+};
+
+pub const REBASE_OPCODE_MASK: u8 = 0xf0;
+pub const REBASE_IMMEDIATE_MASK: u8 = 0x0f;
+pub const REBASE_OPCODE_DONE: u8 = 0x00;
+pub const REBASE_OPCODE_SET_TYPE_IMM: u8 = 0x10;
+pub const REBASE_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB: u8 = 0x20;
+pub const REBASE_OPCODE_ADD_ADDR_ULEB: u8 = 0x30;
+pub const REBASE_OPCODE_ADD_ADDR_IMM_SCALED: u8 = 0x40;
+pub const REBASE_OPCODE_DO_REBASE_IMM_TIMES: u8 = 0x50;
+pub const REBASE_OPCODE_DO_REBASE_ULEB_TIMES: u8 = 0x60;
+pub const REBASE_OPCODE_DO_REBASE_ADD_ADDR_ULEB: u8 = 0x70;
+pub const REBASE_OPCODE_DO_REBASE_ULEB_TIMES_SKIPPING_ULEB: u8 = 0x80;
+
+pub const BIND_OPCODE_MASK: u8 = 0xf0;
+pub const BIND_IMMEDIATE_MASK: u8 = 0x0f;
+pub const BIND_OPCODE_DONE: u8 = 0x00;
+pub const BIND_OPCODE_SET_DYLIB_ORDINAL_IMM: u8 = 0x10;
+pub const BIND_OPCODE_SET_DYLIB_ORDINAL_ULEB: u8 = 0x20;
+pub const BIND_OPCODE_SET_DYLIB_SPECIAL_IMM: u8 = 0x30;
+pub const BIND_OPCODE_SET_SYMBOL_TRAILING_FLAGS_IMM: u8 = 0x40;
+pub const BIND_OPCODE_SET_TYPE_IMM: u8 = 0x50;
+pub const BIND_OPCODE_SET_ADDEND_SLEB: u8 = 0x60;
+pub const BIND_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB: u8 = 0x70;
+pub const BIND_OPCODE_ADD_ADDR_ULEB: u8 = 0x80;
+pub const BIND_OPCODE_DO_BIND: u8 = 0x90;
+pub const BIND_OPCODE_DO_BIND_ADD_ADDR_ULEB: u8 = 0xa0;
+pub const BIND_OPCODE_DO_BIND_ADD_ADDR_IMM_SCALED: u8 = 0xb0;
+pub const BIND_OPCODE_DO_BIND_ULEB_TIMES_SKIPPING_ULEB: u8 = 0xc0;
+
+/// The symbol is allowed to be missing at runtime.
+pub const BIND_SYMBOL_FLAGS_WEAK_IMPORT: u8 = 0x1;
+/// The image has a strong definition of this weak symbol.
+pub const BIND_SYMBOL_FLAGS_NON_WEAK_DEFINITION: u8 = 0x8;
+
+/// Library ordinal which refers to the image itself.
+pub const BIND_SPECIAL_DYLIB_SELF: i64 = 0;
+pub const BIND_SPECIAL_DYLIB_MAIN_EXECUTABLE: i64 = -1;
+pub const BIND_SPECIAL_DYLIB_FLAT_LOOKUP: i64 = -2;
+pub const BIND_SPECIAL_DYLIB_WEAK_LOOKUP: i64 = -3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RebaseType {
+    Pointer,
+    TextAbsolute32,
+    TextPcrel32,
+    /// Keeps the value as is.
+    Unknown(u8),
+}
+
+impl RebaseType {
+    pub fn from_u8(n: u8) -> Self {
+        match n {
+            1 => RebaseType::Pointer,
+            2 => RebaseType::TextAbsolute32,
+            3 => RebaseType::TextPcrel32,
+            n => RebaseType::Unknown(n),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            RebaseType::Pointer => 1,
+            RebaseType::TextAbsolute32 => 2,
+            RebaseType::TextPcrel32 => 3,
+            RebaseType::Unknown(n) => n,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindType {
+    Pointer,
+    TextAbsolute32,
+    TextPcrel32,
+    /// Keeps the value as is.
+    Unknown(u8),
+}
+
+impl BindType {
+    pub fn from_u8(n: u8) -> Self {
+        match n {
+            1 => BindType::Pointer,
+            2 => BindType::TextAbsolute32,
+            3 => BindType::TextPcrel32,
+            n => BindType::Unknown(n),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            BindType::Pointer => 1,
+            BindType::TextAbsolute32 => 2,
+            BindType::TextPcrel32 => 3,
+            BindType::Unknown(n) => n,
+        }
+    }
+}
+
+/// A location which dyld slides by the load address of the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rebase {
+    pub segment_index: u8,
+    /// Offset from the start of the segment.
+    pub offset: u64,
+    pub rebase_type: RebaseType,
+}
+
+/// A location which dyld sets to the address of a symbol plus `addend`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bind {
+    pub segment_index: u8,
+    /// Offset from the start of the segment.
+    pub offset: u64,
+    pub bind_type: BindType,
+    /// 1-based index of the dylib load commands, or one of `BIND_SPECIAL_DYLIB_*`.
+    pub library_ordinal: i64,
+    pub symbol_name: String,
+    pub addend: i64,
+    /// `BIND_SYMBOL_FLAGS_*`
+    pub flags: u8,
+}
+
+/// The state of the bind state machine before any opcode is executed.
+fn initial_state() -> Bind {
+    Bind {
+        segment_index: 0,
+        offset: 0,
+        bind_type: BindType::Pointer,
+        library_ordinal: 0,
+        symbol_name: String::new(),
+        addend: 0,
+        flags: 0,
+    }
+}
+
+/// Executes rebase opcodes. Offsets in errors are relative to `opcodes`.
+///
+/// `segment_sizes` are the VM sizes of the segments. As dyld does, every rebased pointer must be
+/// in its segment, and a repeat count never exceeds the number of pointers the segment holds, so
+/// a short stream cannot produce an unbounded number of rebases.
+pub fn decode_rebase(
+    opcodes: &[u8],
+    pointer_size: u64,
+    segment_sizes: &[u64],
+) -> Result<Vec<Rebase>> {
+    decode_rebase_at(opcodes, 0, pointer_size, segment_sizes)
+}
+
+/// Executes bind or weak bind opcodes. Offsets in errors are relative to `opcodes`.
+///
+/// Every bound pointer must be in its segment, as in `decode_rebase`.
+pub fn decode_bind(opcodes: &[u8], pointer_size: u64, segment_sizes: &[u64]) -> Result<Vec<Bind>> {
+    decode_bind_at(opcodes, 0, pointer_size, segment_sizes, false)
+}
+
+/// Executes lazy bind opcodes, where `BIND_OPCODE_DONE` separates the self-contained entries of
+/// each lazy pointer instead of ending the stream. Offsets in errors are relative to `opcodes`.
+pub fn decode_lazy_bind(
+    opcodes: &[u8],
+    pointer_size: u64,
+    segment_sizes: &[u64],
+) -> Result<Vec<Bind>> {
+    decode_bind_at(opcodes, 0, pointer_size, segment_sizes, true)
+}
+
+fn segment_size(segment_sizes: &[u64], segment_index: u8) -> u64 {
+    segment_sizes
+        .get(usize::from(segment_index))
+        .copied()
+        .unwrap_or(0)
+}
+
+/// Checks that the pointer at `offset` is in the segment, and returns the offset of the next
+/// pointer `skip` bytes after it. As in dyld, the offset wraps around so that ld64 can encode
+/// backward deltas as huge ULEB values.
+fn next_pointer(
+    segment_size: u64,
+    offset: u64,
+    pointer_size: u64,
+    skip: u64,
+    opcode_offset: u64,
+) -> Result<u64> {
+    match offset.checked_add(pointer_size) {
+        Some(end) if end <= segment_size => Ok(end.wrapping_add(skip)),
+        _ => Err(Error::UnknownValue {
+            offset: opcode_offset,
+            kind: "segment offset",
+            value: offset,
+        }),
+    }
+}
+
+fn decode_rebase_at(
+    opcodes: &[u8],
+    base: u64,
+    pointer_size: u64,
+    segment_sizes: &[u64],
+) -> Result<Vec<Rebase>> {
+    let mut read = ByteReader::new(opcodes, base);
+    let mut rebases = Vec::new();
+
+    let mut rebase_type = RebaseType::Pointer;
+    let mut segment_index = 0;
+    let mut offset = 0u64;
+
+    while !read.is_empty() {
+        let opcode_offset = read.offset();
+        let byte = read.read_u8()?;
+        let imm = byte & REBASE_IMMEDIATE_MASK;
+        // rebases `count` pointers each followed by `skip` bytes.
+        let (count, skip) = match byte & REBASE_OPCODE_MASK {
+            REBASE_OPCODE_DONE => break,
+            REBASE_OPCODE_SET_TYPE_IMM => {
+                rebase_type = RebaseType::from_u8(imm);
+                continue;
+            }
+            REBASE_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB => {
+                segment_index = imm;
+                offset = read.read_uleb()?;
+                continue;
+            }
+            REBASE_OPCODE_ADD_ADDR_ULEB => {
+                offset = offset.wrapping_add(read.read_uleb()?);
+                continue;
+            }
+            REBASE_OPCODE_ADD_ADDR_IMM_SCALED => {
+                offset = offset.wrapping_add(u64::from(imm) * pointer_size);
+                continue;
+            }
+            REBASE_OPCODE_DO_REBASE_IMM_TIMES => (imm.into(), 0),
+            REBASE_OPCODE_DO_REBASE_ULEB_TIMES => (read.read_uleb()?, 0),
+            REBASE_OPCODE_DO_REBASE_ADD_ADDR_ULEB => (1, read.read_uleb()?),
+            REBASE_OPCODE_DO_REBASE_ULEB_TIMES_SKIPPING_ULEB => {
+                (read.read_uleb()?, read.read_uleb()?)
+            }
+            _ => {
+                return Err(Error::UnknownValue {
+                    offset: opcode_offset,
+                    kind: "rebase opcode",
+                    value: byte.into(),
+                })
+            }
+        };
+
+        // `count` from the file is capped so that it cannot make us allocate more pointers
+        // than the segment holds.
+        let size = segment_size(segment_sizes, segment_index);
+        for _ in 0..count.min(size / pointer_size) {
+            let next = next_pointer(size, offset, pointer_size, skip, opcode_offset)?;
+            rebases.push(Rebase {
+                segment_index,
+                offset,
+                rebase_type,
+            });
+            offset = next;
+        }
+    }
+
+    Ok(rebases)
+}
+
+fn decode_bind_at(
+    opcodes: &[u8],
+    base: u64,
+    pointer_size: u64,
+    segment_sizes: &[u64],
+    lazy: bool,
+) -> Result<Vec<Bind>> {
+    let mut read = ByteReader::new(opcodes, base);
+    let mut binds = Vec::new();
+
+    let mut state = initial_state();
+
+    while !read.is_empty() {
+        let opcode_offset = read.offset();
+        let byte = read.read_u8()?;
+        let imm = byte & BIND_IMMEDIATE_MASK;
+        // binds `count` pointers each followed by `skip` bytes.
+        let (count, skip) = match byte & BIND_OPCODE_MASK {
+            // each lazy entry is executed from the initial state.
+            BIND_OPCODE_DONE if lazy => {
+                state = initial_state();
+                continue;
+            }
+            BIND_OPCODE_DONE => break,
+            BIND_OPCODE_SET_DYLIB_ORDINAL_IMM => {
+                state.library_ordinal = imm.into();
+                continue;
+            }
+            BIND_OPCODE_SET_DYLIB_ORDINAL_ULEB => {
+                state.library_ordinal = read.read_uleb()? as i64;
+                continue;
+            }
+            BIND_OPCODE_SET_DYLIB_SPECIAL_IMM => {
+                // the immediate is a negative number sign-extended from 4 bits.
+                state.library_ordinal = if imm == 0 {
+                    0
+                } else {
+                    i64::from((imm | BIND_OPCODE_MASK) as i8)
+                };
+                continue;
+            }
+            BIND_OPCODE_SET_SYMBOL_TRAILING_FLAGS_IMM => {
+                state.flags = imm;
+                state.symbol_name = read.read_cstr()?.to_string();
+                continue;
+            }
+            BIND_OPCODE_SET_TYPE_IMM => {
+                state.bind_type = BindType::from_u8(imm);
+                continue;
+            }
+            BIND_OPCODE_SET_ADDEND_SLEB => {
+                state.addend = read.read_sleb()?;
+                continue;
+            }
+            BIND_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB => {
+                state.segment_index = imm;
+                state.offset = read.read_uleb()?;
+                continue;
+            }
+            BIND_OPCODE_ADD_ADDR_ULEB => {
+                state.offset = state.offset.wrapping_add(read.read_uleb()?);
+                continue;
+            }
+            BIND_OPCODE_DO_BIND => (1, 0),
+            BIND_OPCODE_DO_BIND_ADD_ADDR_ULEB => (1, read.read_uleb()?),
+            BIND_OPCODE_DO_BIND_ADD_ADDR_IMM_SCALED => (1, u64::from(imm) * pointer_size),
+            BIND_OPCODE_DO_BIND_ULEB_TIMES_SKIPPING_ULEB => (read.read_uleb()?, read.read_uleb()?),
+            _ => {
+                return Err(Error::UnknownValue {
+                    offset: opcode_offset,
+                    kind: "bind opcode",
+                    value: byte.into(),
+                })
+            }
+        };
+
+        // bounded by the segment size as in `decode_rebase_at`.
+        let size = segment_size(segment_sizes, state.segment_index);
+        for _ in 0..count.min(size / pointer_size) {
+            let next = next_pointer(size, state.offset, pointer_size, skip, opcode_offset)?;
+            binds.push(state.clone());
+            state.offset = next;
+        }
+    }
+
+    Ok(binds)
+}
+
+/// Encodes rebases into opcodes in the given order.
+///
+/// Consecutive records are folded into `REBASE_OPCODE_DO_REBASE_*_TIMES*` opcodes, so records
+/// sorted by segment and offset give the most compact stream, as ld64 does.
+///
+/// Fails with `Error::InvalidValue` if the segment index or the type of a record does not fit
+/// in the immediate of its opcode, or if its pointer runs past the end of the address space.
+pub fn encode_rebase(rebases: &[Rebase], pointer_size: u64) -> Result<Vec<u8>> {
+    for rebase in rebases.iter() {
+        check_location(rebase.segment_index, rebase.offset, pointer_size)?;
+        let ty = rebase.rebase_type.to_u8();
+        check(ty <= REBASE_IMMEDIATE_MASK, "rebase type", ty.into())?;
+    }
+
+    let mut buf = Vec::new();
+    let mut cur_type = None;
+    let mut cur_segment = None;
+    let mut cur_offset = 0u64;
+
+    let mut i = 0;
+    while i < rebases.len() {
+        let rebase = &rebases[i];
+
+        if cur_type != Some(rebase.rebase_type) {
+            buf.push(REBASE_OPCODE_SET_TYPE_IMM | rebase.rebase_type.to_u8());
+            cur_type = Some(rebase.rebase_type);
+        }
+        if cur_segment != Some(rebase.segment_index) || rebase.offset < cur_offset {
+            buf.push(REBASE_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB | rebase.segment_index);
+            write_uleb(&mut buf, rebase.offset);
+            cur_segment = Some(rebase.segment_index);
+        } else if rebase.offset != cur_offset {
+            let delta = rebase.offset - cur_offset;
+            if delta.is_multiple_of(pointer_size)
+                && delta / pointer_size <= u64::from(REBASE_IMMEDIATE_MASK)
+            {
+                buf.push(REBASE_OPCODE_ADD_ADDR_IMM_SCALED | (delta / pointer_size) as u8);
+            } else {
+                buf.push(REBASE_OPCODE_ADD_ADDR_ULEB);
+                write_uleb(&mut buf, delta);
+            }
+        }
+
+        // records which can share the current state.
+        let rest = rebases[i + 1..]
+            .iter()
+            .take_while(|r| r.segment_index == rebase.segment_index)
+            .take_while(|r| r.rebase_type == rebase.rebase_type);
+        let offsets = std::iter::once(rebase.offset).chain(rest.map(|r| r.offset));
+        let (count, stride) = run_length(offsets, pointer_size);
+
+        if count == 1 {
+            match rebases.get(i + 1) {
+                Some(next)
+                    if next.segment_index == rebase.segment_index
+                        && next.rebase_type == rebase.rebase_type
+                        && next.offset > rebase.offset + pointer_size =>
+                {
+                    buf.push(REBASE_OPCODE_DO_REBASE_ADD_ADDR_ULEB);
+                    write_uleb(&mut buf, next.offset - rebase.offset - pointer_size);
+                    cur_offset = next.offset;
+                    i += 1;
+                    continue;
+                }
+                _ => buf.push(REBASE_OPCODE_DO_REBASE_IMM_TIMES | 1),
+            }
+        } else if stride == pointer_size {
+            if count <= u64::from(REBASE_IMMEDIATE_MASK) {
+                buf.push(REBASE_OPCODE_DO_REBASE_IMM_TIMES | count as u8);
+            } else {
+                buf.push(REBASE_OPCODE_DO_REBASE_ULEB_TIMES);
+                write_uleb(&mut buf, count);
+            }
+        } else {
+            buf.push(REBASE_OPCODE_DO_REBASE_ULEB_TIMES_SKIPPING_ULEB);
+            write_uleb(&mut buf, count);
+            write_uleb(&mut buf, stride - pointer_size);
+        }
+        // wraps around past the last pointer as the decoder does.
+        cur_offset = rebase.offset.wrapping_add(count.wrapping_mul(stride));
+        i += count as usize;
+    }
+
+    buf.push(REBASE_OPCODE_DONE);
+    pad_to(&mut buf, pointer_size);
+    Ok(buf)
+}
+
+/// Encodes binds or weak binds into opcodes in the given order.
+///
+/// Consecutive records of the same symbol are folded into `BIND_OPCODE_DO_BIND_*` opcodes, so
+/// records sorted by library ordinal, symbol and offset give the most compact stream, as ld64
+/// does.
+///
+/// Fails with `Error::InvalidValue` if a record cannot be encoded, as in `check_bind`.
+pub fn encode_bind(binds: &[Bind], pointer_size: u64) -> Result<Vec<u8>> {
+    for bind in binds.iter() {
+        check_bind(bind, pointer_size)?;
+    }
+
+    let mut buf = Vec::new();
+    let mut state: Option<&Bind> = None;
+    let mut cur_offset = 0u64;
+
+    let mut i = 0;
+    while i < binds.len() {
+        let bind = &binds[i];
+        write_bind_state(&mut buf, state, bind);
+        if state.map(|s| s.segment_index) != Some(bind.segment_index) || bind.offset < cur_offset {
+            buf.push(BIND_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB | bind.segment_index);
+            write_uleb(&mut buf, bind.offset);
+        } else if bind.offset != cur_offset {
+            buf.push(BIND_OPCODE_ADD_ADDR_ULEB);
+            write_uleb(&mut buf, bind.offset - cur_offset);
+        }
+        state = Some(bind);
+
+        // records which differ only in their offsets.
+        let rest = binds[i + 1..]
+            .iter()
+            .take_while(|b| same_except_offset(b, bind));
+        let offsets = std::iter::once(bind.offset).chain(rest.map(|b| b.offset));
+        let (count, stride) = run_length(offsets, pointer_size);
+
+        if count > 2 {
+            buf.push(BIND_OPCODE_DO_BIND_ULEB_TIMES_SKIPPING_ULEB);
+            write_uleb(&mut buf, count);
+            write_uleb(&mut buf, stride - pointer_size);
+            cur_offset = bind.offset.wrapping_add(count.wrapping_mul(stride));
+            i += count as usize;
+            continue;
+        }
+
+        match binds.get(i + 1) {
+            Some(next)
+                if next.segment_index == bind.segment_index
+                    && next.offset > bind.offset + pointer_size =>
+            {
+                let delta = next.offset - bind.offset - pointer_size;
+                if delta.is_multiple_of(pointer_size)
+                    && delta / pointer_size <= u64::from(BIND_IMMEDIATE_MASK)
+                {
+                    buf.push(
+                        BIND_OPCODE_DO_BIND_ADD_ADDR_IMM_SCALED | (delta / pointer_size) as u8,
+                    );
+                } else {
+                    buf.push(BIND_OPCODE_DO_BIND_ADD_ADDR_ULEB);
+                    write_uleb(&mut buf, delta);
+                }
+                cur_offset = next.offset;
+            }
+            _ => {
+                buf.push(BIND_OPCODE_DO_BIND);
+                cur_offset = bind.offset + pointer_size;
+            }
+        }
+        i += 1;
+    }
+
+    buf.push(BIND_OPCODE_DONE);
+    pad_to(&mut buf, pointer_size);
+    Ok(buf)
+}
+
+/// Encodes lazy binds into opcodes, one self-contained entry per record.
+///
+/// Also returns the offset of every entry in the stream, which stub helpers pass to dyld.
+/// Fails as `encode_bind` does.
+pub fn encode_lazy_bind(binds: &[Bind], pointer_size: u64) -> Result<(Vec<u8>, Vec<u32>)> {
+    for bind in binds.iter() {
+        check_bind(bind, pointer_size)?;
+    }
+
+    let mut buf = Vec::new();
+    let mut entry_offsets = Vec::with_capacity(binds.len());
+
+    for bind in binds.iter() {
+        entry_offsets.push(buf.len() as u32);
+        buf.push(BIND_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB | bind.segment_index);
+        write_uleb(&mut buf, bind.offset);
+        write_bind_state(&mut buf, Some(&initial_state()), bind);
+        buf.push(BIND_OPCODE_DO_BIND);
+        buf.push(BIND_OPCODE_DONE);
+    }
+
+    pad_to(&mut buf, pointer_size);
+    Ok((buf, entry_offsets))
+}
+
+/// Checks that the segment index fits in the immediate of `*_SET_SEGMENT_AND_OFFSET_ULEB`, and
+/// that the pointer at `offset` does not run past the end of the address space.
+fn check_location(segment_index: u8, offset: u64, pointer_size: u64) -> Result<()> {
+    check(
+        segment_index <= REBASE_IMMEDIATE_MASK,
+        "segment index",
+        segment_index.into(),
+    )?;
+    check(
+        offset.checked_add(pointer_size).is_some(),
+        "segment offset",
+        offset,
+    )
+}
+
+/// Checks that the location, the flags, the library ordinal and the type of `bind` can be
+/// encoded.
+fn check_bind(bind: &Bind, pointer_size: u64) -> Result<()> {
+    check_location(bind.segment_index, bind.offset, pointer_size)?;
+    check(
+        bind.flags <= BIND_IMMEDIATE_MASK,
+        "bind symbol flags",
+        bind.flags.into(),
+    )?;
+    check(
+        bind.library_ordinal >= BIND_SPECIAL_DYLIB_WEAK_LOOKUP,
+        "library ordinal",
+        bind.library_ordinal as u64,
+    )?;
+    let ty = bind.bind_type.to_u8();
+    check(ty <= BIND_IMMEDIATE_MASK, "bind type", ty.into())
+}
+
+/// Writes the opcodes which change the ordinal, symbol, type and addend from `prev` to `bind`,
+/// which has passed `check_bind`.
+fn write_bind_state(buf: &mut Vec<u8>, prev: Option<&Bind>, bind: &Bind) {
+    if prev.map(|p| p.library_ordinal) != Some(bind.library_ordinal) {
+        match bind.library_ordinal {
+            n if n <= 0 => {
+                buf.push(BIND_OPCODE_SET_DYLIB_SPECIAL_IMM | (n as u8 & BIND_IMMEDIATE_MASK));
+            }
+            n if n <= i64::from(BIND_IMMEDIATE_MASK) => {
+                buf.push(BIND_OPCODE_SET_DYLIB_ORDINAL_IMM | n as u8)
+            }
+            n => {
+                buf.push(BIND_OPCODE_SET_DYLIB_ORDINAL_ULEB);
+                write_uleb(buf, n as u64);
+            }
+        }
+    }
+    if prev.map(|p| (p.symbol_name.as_str(), p.flags))
+        != Some((bind.symbol_name.as_str(), bind.flags))
+    {
+        buf.push(BIND_OPCODE_SET_SYMBOL_TRAILING_FLAGS_IMM | bind.flags);
+        buf.extend_from_slice(bind.symbol_name.as_bytes());
+        buf.push(0);
+    }
+    if prev.map(|p| p.bind_type) != Some(bind.bind_type) {
+        buf.push(BIND_OPCODE_SET_TYPE_IMM | bind.bind_type.to_u8());
+    }
+    if prev.map_or(0, |p| p.addend) != bind.addend {
+        buf.push(BIND_OPCODE_SET_ADDEND_SLEB);
+        write_sleb(buf, bind.addend);
+    }
+}
+
+fn same_except_offset(a: &Bind, b: &Bind) -> bool {
+    a.segment_index == b.segment_index
+        && a.bind_type == b.bind_type
+        && a.library_ordinal == b.library_ordinal
+        && a.symbol_name == b.symbol_name
+        && a.addend == b.addend
+        && a.flags == b.flags
+}
+
+/// Counts the leading offsets which are evenly spaced by a stride of at least `pointer_size`,
+/// and returns the count and the stride.
+fn run_length(mut offsets: impl Iterator<Item = u64>, pointer_size: u64) -> (u64, u64) {
+    let first = match offsets.next() {
+        Some(first) => first,
+        None => return (0, pointer_size),
+    };
+    let second = match offsets.next() {
+        Some(second) if second >= first + pointer_size => second,
+        _ => return (1, pointer_size),
+    };
+    let stride = second - first;
+    let mut count = 2;
+    let mut prev = second;
+    for offset in offsets {
+        if prev.checked_add(stride) != Some(offset) {
+            break;
+        }
+        count += 1;
+        prev = offset;
+    }
+    (count, stride)
+}
+
+/// Pads opcodes with `*_OPCODE_DONE` to the pointer size, as ld64 does.
+fn pad_to(buf: &mut Vec<u8>, pointer_size: u64) {
+    let len = (buf.len() as u64).div_ceil(pointer_size) * pointer_size;
+    buf.resize(len as usize, 0);
+}
+
+/// The payloads of `LC_DYLD_INFO(_ONLY)` in a file.
+#[derive(Debug, Clone)]
+pub struct DyldInfo<'a> {
+    data: &'a [u8],
+    cmd: DyldInfoCommand,
+    pointer_size: u64,
+    /// VM sizes of the segments, which bound the rebased and bound pointers.
+    segment_sizes: Vec<u64>,
+}
+
+impl<'a> DyldInfo<'a> {
+    /// Checks that every payload of `cmd` is in `data`, the whole file.
+    ///
+    /// `segment_sizes` are the VM sizes of the segments in the order of the load commands.
+    pub fn new(
+        data: &'a [u8],
+        cmd: DyldInfoCommand,
+        is_64: bool,
+        segment_sizes: Vec<u64>,
+    ) -> Result<Self> {
+        let ranges = [
+            (cmd.rebase_off, cmd.rebase_size),
+            (cmd.bind_off, cmd.bind_size),
+            (cmd.weak_bind_off, cmd.weak_bind_size),
+            (cmd.lazy_bind_off, cmd.lazy_bind_size),
+            (cmd.export_off, cmd.export_size),
+        ];
+        for (offset, size) in ranges {
+            slice(data, offset.into(), size.into())?;
+        }
+
+        Ok(DyldInfo {
+            data,
+            cmd,
+            pointer_size: if is_64 { 8 } else { 4 },
+            segment_sizes,
+        })
+    }
+
+    pub fn command(&self) -> &DyldInfoCommand {
+        &self.cmd
+    }
+
+    fn payload(&self, offset: u32, size: u32) -> &'a [u8] {
+        &self.data[offset as usize..(offset + size) as usize]
+    }
+
+    pub fn rebase_opcodes(&self) -> &'a [u8] {
+        self.payload(self.cmd.rebase_off, self.cmd.rebase_size)
+    }
+
+    pub fn bind_opcodes(&self) -> &'a [u8] {
+        self.payload(self.cmd.bind_off, self.cmd.bind_size)
+    }
+
+    pub fn weak_bind_opcodes(&self) -> &'a [u8] {
+        self.payload(self.cmd.weak_bind_off, self.cmd.weak_bind_size)
+    }
+
+    pub fn lazy_bind_opcodes(&self) -> &'a [u8] {
+        self.payload(self.cmd.lazy_bind_off, self.cmd.lazy_bind_size)
+    }
+
+    pub fn export_trie(&self) -> &'a [u8] {
+        self.payload(self.cmd.export_off, self.cmd.export_size)
+    }
+
+    pub fn exports(&self) -> Result<Vec<Export>> {
+        export_trie::parse_at(self.export_trie(), self.cmd.export_off.into())
+    }
+
+    pub fn rebases(&self) -> Result<Vec<Rebase>> {
+        let base = self.cmd.rebase_off.into();
+        decode_rebase_at(
+            self.rebase_opcodes(),
+            base,
+            self.pointer_size,
+            &self.segment_sizes,
+        )
+    }
+
+    pub fn binds(&self) -> Result<Vec<Bind>> {
+        let base = self.cmd.bind_off.into();
+        decode_bind_at(
+            self.bind_opcodes(),
+            base,
+            self.pointer_size,
+            &self.segment_sizes,
+            false,
+        )
+    }
+
+    pub fn weak_binds(&self) -> Result<Vec<Bind>> {
+        let base = self.cmd.weak_bind_off.into();
+        decode_bind_at(
+            self.weak_bind_opcodes(),
+            base,
+            self.pointer_size,
+            &self.segment_sizes,
+            false,
+        )
+    }
+
+    pub fn lazy_binds(&self) -> Result<Vec<Bind>> {
+        let base = self.cmd.lazy_bind_off.into();
+        decode_bind_at(
+            self.lazy_bind_opcodes(),
+            base,
+            self.pointer_size,
+            &self.segment_sizes,
+            true,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEGMENT_SIZES: [u64; 4] = [0x4000; 4];
+
+    #[test]
+    fn decode_rebase_opcodes() {
+        let opcodes = [
+            REBASE_OPCODE_SET_TYPE_IMM | 1,
+            REBASE_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB | 2,
+            0x10,
+            REBASE_OPCODE_DO_REBASE_IMM_TIMES | 2,
+            REBASE_OPCODE_ADD_ADDR_IMM_SCALED | 1,
+            REBASE_OPCODE_DO_REBASE_ULEB_TIMES_SKIPPING_ULEB,
+            2,
+            8,
+            REBASE_OPCODE_DONE,
+        ];
+        let rebases = decode_rebase(&opcodes, 8, &SEGMENT_SIZES).unwrap();
+        let offsets = rebases.iter().map(|r| r.offset).collect::<Vec<_>>();
+        assert_eq!(offsets, [0x10, 0x18, 0x28, 0x38]);
+        assert!(rebases
+            .iter()
+            .all(|r| r.segment_index == 2 && r.rebase_type == RebaseType::Pointer));
+
+        let err = decode_rebase(&[0xf0], 8, &SEGMENT_SIZES).unwrap_err();
+        assert!(matches!(err, Error::UnknownValue { offset: 0, .. }));
+
+        // a huge count is capped at the pointers in the segment instead of exhausting memory.
+        let mut huge = vec![REBASE_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB | 1, 0x00];
+        huge.push(REBASE_OPCODE_DO_REBASE_ULEB_TIMES_SKIPPING_ULEB);
+        huge.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0x0f]);
+        write_uleb(&mut huge, 8u64.wrapping_neg());
+        assert_eq!(
+            decode_rebase(&huge, 8, &SEGMENT_SIZES).unwrap().len(),
+            0x800
+        );
+
+        // but every pointer must be in the segment.
+        let past_end = [
+            REBASE_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB | 1,
+            0x10,
+            REBASE_OPCODE_DO_REBASE_ULEB_TIMES,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            0x0f,
+        ];
+        let err = decode_rebase(&past_end, 8, &SEGMENT_SIZES).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::UnknownValue {
+                offset: 2,
+                value: 0x4000,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn decode_bind_opcodes() {
+        let mut opcodes = vec![
+            BIND_OPCODE_SET_DYLIB_ORDINAL_IMM | 1,
+            BIND_OPCODE_SET_SYMBOL_TRAILING_FLAGS_IMM | BIND_SYMBOL_FLAGS_WEAK_IMPORT,
+        ];
+        opcodes.extend_from_slice(b"_foo\0");
+        opcodes.extend_from_slice(&[
+            BIND_OPCODE_SET_TYPE_IMM | 1,
+            BIND_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB | 1,
+            0x20,
+            BIND_OPCODE_SET_ADDEND_SLEB,
+            0x7c, // -4
+            BIND_OPCODE_DO_BIND,
+            BIND_OPCODE_SET_DYLIB_SPECIAL_IMM | 0x0e, // -2
+            BIND_OPCODE_DO_BIND_ADD_ADDR_IMM_SCALED | 1,
+            BIND_OPCODE_DO_BIND,
+            BIND_OPCODE_DONE,
+        ]);
+
+        let binds = decode_bind(&opcodes, 8, &SEGMENT_SIZES).unwrap();
+        assert_eq!(binds.len(), 3);
+        assert_eq!(binds[0].symbol_name, "_foo");
+        assert_eq!(binds[0].flags, BIND_SYMBOL_FLAGS_WEAK_IMPORT);
+        assert_eq!(binds[0].addend, -4);
+        assert_eq!((binds[0].segment_index, binds[0].offset), (1, 0x20));
+        assert_eq!(binds[0].library_ordinal, 1);
+        assert_eq!(binds[1].library_ordinal, BIND_SPECIAL_DYLIB_FLAT_LOOKUP);
+        assert_eq!((binds[1].offset, binds[2].offset), (0x28, 0x38));
+
+        // ld64 moves backwards with deltas which wrap around.
+        let mut opcodes = vec![BIND_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB | 1, 0x20];
+        opcodes.push(BIND_OPCODE_DO_BIND_ADD_ADDR_ULEB);
+        write_uleb(&mut opcodes, 0x18u64.wrapping_neg());
+        opcodes.push(BIND_OPCODE_DO_BIND);
+        opcodes.push(BIND_OPCODE_ADD_ADDR_ULEB);
+        write_uleb(&mut opcodes, 0x10u64.wrapping_neg());
+        opcodes.push(BIND_OPCODE_DO_BIND);
+        let binds = decode_bind(&opcodes, 8, &SEGMENT_SIZES).unwrap();
+        let offsets = binds.iter().map(|b| b.offset).collect::<Vec<_>>();
+        assert_eq!(offsets, [0x20, 0x10, 0x8]);
+    }
+
+    #[test]
+    fn decode_lazy_bind_opcodes() {
+        let mut opcodes = Vec::new();
+        for (offset, name) in [(0u8, b"_a\0"), (8, b"_b\0")] {
+            opcodes.extend_from_slice(&[BIND_OPCODE_SET_SEGMENT_AND_OFFSET_ULEB | 3, offset]);
+            opcodes.push(BIND_OPCODE_SET_DYLIB_ORDINAL_IMM | 2);
+            opcodes.push(BIND_OPCODE_SET_SYMBOL_TRAILING_FLAGS_IMM);
+            opcodes.extend_from_slice(name);
+            opcodes.extend_from_slice(&[BIND_OPCODE_DO_BIND, BIND_OPCODE_DONE]);
+        }
+
+        let binds = decode_lazy_bind(&opcodes, 8, &SEGMENT_SIZES).unwrap();
+        let names = binds
+            .iter()
+            .map(|b| b.symbol_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["_a", "_b"]);
+        assert_eq!(binds[1].offset, 8);
+        // a normal bind stream ends at the first DONE.
+        assert_eq!(decode_bind(&opcodes, 8, &SEGMENT_SIZES).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn encode_rebase_round_trip() {
+        let mut rebases = Vec::new();
+        let mut push = |segment_index, offset, rebase_type| {
+            rebases.push(Rebase {
+                segment_index,
+                offset,
+                rebase_type,
+            })
+        };
+        (0..20).for_each(|i| push(1, i * 8, RebaseType::Pointer));
+        (0..4).for_each(|i| push(1, 0x200 + i * 0x18, RebaseType::Pointer));
+        push(1, 0x1000, RebaseType::Pointer);
+        push(1, 0x2000, RebaseType::TextAbsolute32);
+        push(2, 0x10, RebaseType::Pointer);
+        push(2, 0x8, RebaseType::Pointer);
+
+        let opcodes = encode_rebase(&rebases, 8).unwrap();
+        assert_eq!(opcodes.len() % 8, 0);
+        let decoded = decode_rebase(&opcodes, 8, &SEGMENT_SIZES).unwrap();
+        assert_eq!(decoded, rebases);
+        assert_eq!(encode_rebase(&decoded, 8).unwrap(), opcodes);
+
+        let ops = opcodes.iter().map(|b| b & REBASE_OPCODE_MASK);
+        assert!(ops
+            .clone()
+            .any(|op| op == REBASE_OPCODE_DO_REBASE_ULEB_TIMES));
+        assert!(ops
+            .clone()
+            .any(|op| op == REBASE_OPCODE_DO_REBASE_ULEB_TIMES_SKIPPING_ULEB));
+
+        // records which cannot be encoded are errors rather than panics.
+        rebases[0].segment_index = 16;
+        assert!(matches!(
+            encode_rebase(&rebases, 8),
+            Err(Error::InvalidValue { value: 16, .. })
+        ));
+        rebases[0].segment_index = 1;
+        rebases[0].offset = u64::MAX - 4;
+        assert!(encode_rebase(&rebases, 8).is_err());
+    }
+
+    #[test]
+    fn encode_bind_round_trip() {
+        let bind = |segment_index, offset, library_ordinal, symbol_name: &str, addend| Bind {
+            segment_index,
+            offset,
+            bind_type: BindType::Pointer,
+            library_ordinal,
+            symbol_name: symbol_name.to_string(),
+            addend,
+            flags: 0,
+        };
+        let mut binds = vec![
+            bind(1, 0x0, 1, "_a", 0),
+            bind(1, 0x8, 1, "_b", 0),
+            bind(1, 0x18, 2, "_c", -8),
+            bind(1, 0x100, 20, "_d", 0),
+            bind(2, 0x0, BIND_SPECIAL_DYLIB_FLAT_LOOKUP, "_e", 0),
+        ];
+        binds.extend((0..5).map(|i| bind(2, 0x40 + i * 0x10, 3, "_f", 4)));
+        binds[1].flags = BIND_SYMBOL_FLAGS_WEAK_IMPORT;
+
+        let opcodes = encode_bind(&binds, 8).unwrap();
+        let decoded = decode_bind(&opcodes, 8, &SEGMENT_SIZES).unwrap();
+        assert_eq!(decoded, binds);
+        assert_eq!(encode_bind(&decoded, 8).unwrap(), opcodes);
+        assert!(opcodes.contains(&(BIND_OPCODE_DO_BIND_ADD_ADDR_IMM_SCALED | 1)));
+        assert!(opcodes.contains(&BIND_OPCODE_DO_BIND_ULEB_TIMES_SKIPPING_ULEB));
+
+        let (lazy_opcodes, entry_offsets) = encode_lazy_bind(&binds, 8).unwrap();
+        assert_eq!(
+            decode_lazy_bind(&lazy_opcodes, 8, &SEGMENT_SIZES).unwrap(),
+            binds
+        );
+        let last = *entry_offsets.last().unwrap() as usize;
+        assert_eq!(
+            decode_lazy_bind(&lazy_opcodes[last..], 8, &SEGMENT_SIZES).unwrap(),
+            binds[9..]
+        );
+
+        binds[4].library_ordinal = -4;
+        assert!(matches!(
+            encode_bind(&binds, 8),
+            Err(Error::InvalidValue { value, .. }) if value == -4i64 as u64
+        ));
+        assert!(encode_lazy_bind(&binds, 8).is_err());
+    }
+}
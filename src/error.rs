@@ -0,0 +1,137 @@
+use crate::header::CpuType;
+use std::{fmt, io};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An error which occurs while reading or writing a Mach-O file.
+///
+/// Every error detected while reading carries the file offset at which it happened.
+#[derive(Debug)]
+pub enum Error {
+    /// The input ended before the structure at `offset` was fully read.
+    Truncated { offset: u64 },
+    /// The value at `offset` is not a magic number expected here.
+    BadMagic { offset: u64, magic: u32 },
+    /// The value at `offset` is not a known value of `kind`.
+    UnknownValue {
+        offset: u64,
+        kind: &'static str,
+        value: u64,
+    },
+    /// The `cmdsize` of the load command at `offset` does not match its content.
+    InconsistentCmdSize { offset: u64, cmd: u32, cmdsize: u32 },
+    /// The `cmdsize` of a load command being written is not `size`, the size of its content.
+    CmdSizeMismatch { cmd: u32, cmdsize: u32, size: u32 },
+    /// The fixed size string at `offset` is not a valid UTF-8 string.
+    InvalidString { offset: u64 },
+    /// The fat file has no slice for `cpu_type`.
+    ArchNotFound { cpu_type: CpuType },
+    /// More than one slice for `cpu_type` is given to a fat file.
+    DuplicateArch { cpu_type: CpuType },
+    /// The symbol `name` is defined more than once in a builder.
+    DuplicateSymbol { name: String },
+    /// The file has no segment named `segname`.
+    SegmentNotFound { segname: &'static str },
+    /// The string does not fit in the fixed size field of `size` bytes it is written to.
+    StringTooLong { string: String, size: usize },
+    /// There is no free space between the load commands and the first section to add the
+    /// load command `cmd`.
+    NoRoomForLoadCommand { cmd: u32 },
+    /// The value of `kind` being written cannot be represented in the file.
+    InvalidValue { kind: &'static str, value: u64 },
+    /// An underlying I/O error other than an unexpected EOF.
+    Io(io::Error),
+}
+
+impl Error {
+    /// Returns the file offset where this error happened, if any.
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            Error::Truncated { offset }
+            | Error::BadMagic { offset, .. }
+            | Error::UnknownValue { offset, .. }
+            | Error::InconsistentCmdSize { offset, .. }
+            | Error::InvalidString { offset } => Some(*offset),
+            Error::ArchNotFound { .. }
+            | Error::DuplicateArch { .. }
+            | Error::DuplicateSymbol { .. }
+            | Error::SegmentNotFound { .. }
+            | Error::CmdSizeMismatch { .. }
+            | Error::StringTooLong { .. }
+            | Error::NoRoomForLoadCommand { .. }
+            | Error::InvalidValue { .. }
+            | Error::Io(_) => None,
+        }
+    }
+}
+
+/// Fails with `Error::InvalidValue` of `kind` unless the value being written `fits`.
+pub(crate) fn check(fits: bool, kind: &'static str, value: u64) -> Result<()> {
+    if fits {
+        Ok(())
+    } else {
+        Err(Error::InvalidValue { kind, value })
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Truncated { offset } => write!(f, "unexpected end of input at 0x{:x}", offset),
+            Error::BadMagic { offset, magic } => {
+                write!(f, "bad magic 0x{:08x} at 0x{:x}", magic, offset)
+            }
+            Error::UnknownValue {
+                offset,
+                kind,
+                value,
+            } => write!(f, "unknown {} 0x{:x} at 0x{:x}", kind, value, offset),
+            Error::InconsistentCmdSize {
+                offset,
+                cmd,
+                cmdsize,
+            } => write!(
+                f,
+                "inconsistent cmdsize {} of load command 0x{:x} at 0x{:x}",
+                cmdsize, cmd, offset
+            ),
+            Error::CmdSizeMismatch { cmd, cmdsize, size } => write!(
+                f,
+                "cmdsize {} of load command 0x{:x} does not match its size {}",
+                cmdsize, cmd, size
+            ),
+            Error::InvalidString { offset } => write!(f, "invalid string at 0x{:x}", offset),
+            Error::ArchNotFound { cpu_type } => write!(f, "no slice for {:?}", cpu_type),
+            Error::DuplicateArch { cpu_type } => {
+                write!(f, "more than one slice for {:?}", cpu_type)
+            }
+            Error::DuplicateSymbol { name } => write!(f, "symbol {} is defined twice", name),
+            Error::SegmentNotFound { segname } => write!(f, "no segment named {}", segname),
+            Error::StringTooLong { string, size } => {
+                write!(f, "string {:?} is longer than {} bytes", string, size)
+            }
+            Error::NoRoomForLoadCommand { cmd } => {
+                write!(f, "no room for load command 0x{:x}", cmd)
+            }
+            Error::InvalidValue { kind, value } => {
+                write!(f, "{} 0x{:x} cannot be written", kind, value)
+            }
+            Error::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -0,0 +1,400 @@
+//! The export trie, which lists the symbols exported by an image.
+//!
+//! The trie is referred by `LC_DYLD_EXPORTS_TRIE` or by the export range of
+//! `LC_DYLD_INFO(_ONLY)`. Every node has optional terminal information of the symbol whose name
+//! is the concatenation of the edge labels from the root, followed by the edges to its children.
+
+use crate::{
+    error::{Error, Result},
+    leb128::{write_uleb, ByteReader},
+};
+use std::collections::HashSet;
+
+pub const EXPORT_SYMBOL_FLAGS_KIND_MASK: u64 = 0x03;
+pub const EXPORT_SYMBOL_FLAGS_KIND_REGULAR: u64 = 0x00;
+pub const EXPORT_SYMBOL_FLAGS_KIND_THREAD_LOCAL: u64 = 0x01;
+pub const EXPORT_SYMBOL_FLAGS_KIND_ABSOLUTE: u64 = 0x02;
+pub const EXPORT_SYMBOL_FLAGS_WEAK_DEFINITION: u64 = 0x04;
+pub const EXPORT_SYMBOL_FLAGS_REEXPORT: u64 = 0x08;
+pub const EXPORT_SYMBOL_FLAGS_STUB_AND_RESOLVER: u64 = 0x10;
+pub const EXPORT_SYMBOL_FLAGS_STATIC_RESOLVER: u64 = 0x20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind {
+    Regular,
+    ThreadLocal,
+    Absolute,
+    /// Keeps the value as is.
+    Unknown(u8),
+}
+
+impl ExportKind {
+    pub fn from_flags(flags: u64) -> Self {
+        match flags & EXPORT_SYMBOL_FLAGS_KIND_MASK {
+            EXPORT_SYMBOL_FLAGS_KIND_REGULAR => ExportKind::Regular,
+            EXPORT_SYMBOL_FLAGS_KIND_THREAD_LOCAL => ExportKind::ThreadLocal,
+            EXPORT_SYMBOL_FLAGS_KIND_ABSOLUTE => ExportKind::Absolute,
+            n => ExportKind::Unknown(n as u8),
+        }
+    }
+}
+
+/// What an exported symbol refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportData {
+    /// Offset of the symbol from the mach header, or the value of an absolute symbol.
+    Regular { address: u64 },
+    /// The symbol is `imported_name` of the dylib at `library_ordinal`. An empty
+    /// `imported_name` means the same name as the export.
+    Reexport {
+        library_ordinal: u64,
+        imported_name: String,
+    },
+    /// The symbol is a stub which calls the resolver function to find the implementation.
+    StubAndResolver {
+        stub_address: u64,
+        resolver_address: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Export {
+    pub name: String,
+    /// `EXPORT_SYMBOL_FLAGS_*`. `EXPORT_SYMBOL_FLAGS_REEXPORT` and
+    /// `EXPORT_SYMBOL_FLAGS_STUB_AND_RESOLVER` agree with `data`.
+    pub flags: u64,
+    pub data: ExportData,
+}
+
+impl Export {
+    /// Creates an export of a regular symbol at `address` with `flags`.
+    pub fn regular(name: impl Into<String>, flags: u64, address: u64) -> Self {
+        Export {
+            name: name.into(),
+            flags,
+            data: ExportData::Regular { address },
+        }
+    }
+
+    pub fn kind(&self) -> ExportKind {
+        ExportKind::from_flags(self.flags)
+    }
+
+    pub fn is_weak(&self) -> bool {
+        self.flags & EXPORT_SYMBOL_FLAGS_WEAK_DEFINITION != 0
+    }
+
+    /// Returns the address of the symbol, or of the stub for a resolver.
+    pub fn address(&self) -> Option<u64> {
+        match self.data {
+            ExportData::Regular { address } => Some(address),
+            ExportData::StubAndResolver { stub_address, .. } => Some(stub_address),
+            ExportData::Reexport { .. } => None,
+        }
+    }
+}
+
+/// Walks the trie and returns every export in the order of the nodes.
+///
+/// Offsets in errors are relative to `trie`.
+pub fn parse(trie: &[u8]) -> Result<Vec<Export>> {
+    parse_at(trie, 0)
+}
+
+pub(crate) fn parse_at(trie: &[u8], base: u64) -> Result<Vec<Export>> {
+    let mut exports = Vec::new();
+    if trie.is_empty() {
+        return Ok(exports);
+    }
+
+    // nodes to visit with the name up to them, in reverse order.
+    let mut stack = vec![(0u64, Vec::new())];
+    let mut visited = HashSet::new();
+
+    while let Some((node_offset, name)) = stack.pop() {
+        // a node referred twice would make the walk endless.
+        if node_offset >= trie.len() as u64 || !visited.insert(node_offset) {
+            return Err(Error::UnknownValue {
+                offset: base + node_offset,
+                kind: "export trie node offset",
+                value: node_offset,
+            });
+        }
+
+        let mut read = ByteReader::new(&trie[node_offset as usize..], base + node_offset);
+        let terminal_size = read.read_uleb()?;
+        let children_pos = read.pos() as u64 + terminal_size;
+        if terminal_size != 0 {
+            let flags = read.read_uleb()?;
+            let data = if flags & EXPORT_SYMBOL_FLAGS_REEXPORT != 0 {
+                ExportData::Reexport {
+                    library_ordinal: read.read_uleb()?,
+                    imported_name: read.read_cstr()?.to_string(),
+                }
+            } else if flags & EXPORT_SYMBOL_FLAGS_STUB_AND_RESOLVER != 0 {
+                ExportData::StubAndResolver {
+                    stub_address: read.read_uleb()?,
+                    resolver_address: read.read_uleb()?,
+                }
+            } else {
+                ExportData::Regular {
+                    address: read.read_uleb()?,
+                }
+            };
+            exports.push(Export {
+                name: String::from_utf8(name.clone()).map_err(|_| Error::InvalidString {
+                    offset: base + node_offset,
+                })?,
+                flags,
+                data,
+            });
+        }
+
+        read.set_pos(children_pos.try_into().unwrap_or(usize::MAX));
+        let n_children = read.read_u8()?;
+        let mut children = Vec::with_capacity(n_children.into());
+        for _ in 0..n_children {
+            let mut child_name = name.clone();
+            child_name.extend_from_slice(read.read_cstr()?.as_bytes());
+            children.push((read.read_uleb()?, child_name));
+        }
+        stack.extend(children.into_iter().rev());
+    }
+
+    Ok(exports)
+}
+
+/// Serializes exports into a trie, laid out in the order ld64 does.
+///
+/// `EXPORT_SYMBOL_FLAGS_REEXPORT` and `EXPORT_SYMBOL_FLAGS_STUB_AND_RESOLVER` are set from
+/// `Export::data` regardless of `Export::flags`. No exports makes an empty trie.
+pub fn build(exports: &[Export]) -> Vec<u8> {
+    if exports.is_empty() {
+        return Vec::new();
+    }
+
+    let mut nodes = vec![Node::default()];
+    for export in exports.iter() {
+        insert(&mut nodes, export.name.as_bytes(), terminal_info(export));
+    }
+
+    // nodes in preorder, which is the order in the trie.
+    let mut order = Vec::with_capacity(nodes.len());
+    let mut stack = vec![0];
+    while let Some(idx) = stack.pop() {
+        order.push(idx);
+        stack.extend(nodes[idx].edges.iter().rev().map(|(_, child)| *child));
+    }
+
+    // the size of a node depends on the ULEB offsets of its children, so lay nodes out until
+    // offsets stop growing.
+    loop {
+        let mut offset = 0;
+        let mut changed = false;
+        for idx in order.iter() {
+            if nodes[*idx].offset != offset {
+                nodes[*idx].offset = offset;
+                changed = true;
+            }
+            offset += nodes[*idx].size(&nodes);
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut buf = Vec::new();
+    for idx in order.iter() {
+        let node = &nodes[*idx];
+        debug_assert_eq!(buf.len() as u64, node.offset);
+        match &node.terminal {
+            Some(info) => {
+                write_uleb(&mut buf, info.len() as u64);
+                buf.extend_from_slice(info);
+            }
+            None => buf.push(0),
+        }
+        buf.push(node.edges.len() as u8);
+        for (label, child) in node.edges.iter() {
+            buf.extend_from_slice(label);
+            buf.push(0);
+            write_uleb(&mut buf, nodes[*child].offset);
+        }
+    }
+    buf
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    terminal: Option<Vec<u8>>,
+    edges: Vec<(Vec<u8>, usize)>,
+    offset: u64,
+}
+
+impl Node {
+    fn size(&self, nodes: &[Node]) -> u64 {
+        let terminal = match &self.terminal {
+            Some(info) => uleb_len(info.len() as u64) + info.len() as u64,
+            None => 1,
+        };
+        let edges = self
+            .edges
+            .iter()
+            .map(|(label, child)| label.len() as u64 + 1 + uleb_len(nodes[*child].offset))
+            .sum::<u64>();
+        terminal + 1 + edges
+    }
+}
+
+fn insert(nodes: &mut Vec<Node>, mut name: &[u8], info: Vec<u8>) {
+    let mut idx = 0;
+    loop {
+        if name.is_empty() {
+            nodes[idx].terminal = Some(info);
+            return;
+        }
+
+        let edge = nodes[idx]
+            .edges
+            .iter()
+            .position(|(label, _)| label[0] == name[0]);
+        let edge = match edge {
+            Some(edge) => edge,
+            None => {
+                nodes.push(Node {
+                    terminal: Some(info),
+                    ..Node::default()
+                });
+                let child = nodes.len() - 1;
+                nodes[idx].edges.push((name.to_vec(), child));
+                return;
+            }
+        };
+
+        let (label, child) = nodes[idx].edges[edge].clone();
+        let common = label.iter().zip(name).take_while(|(a, b)| a == b).count();
+        if common < label.len() {
+            // split the edge at the end of the common prefix.
+            nodes.push(Node {
+                edges: vec![(label[common..].to_vec(), child)],
+                ..Node::default()
+            });
+            let mid = nodes.len() - 1;
+            nodes[idx].edges[edge] = (label[..common].to_vec(), mid);
+            idx = mid;
+        } else {
+            idx = child;
+        }
+        name = &name[common..];
+    }
+}
+
+fn terminal_info(export: &Export) -> Vec<u8> {
+    let mut flags =
+        export.flags & !(EXPORT_SYMBOL_FLAGS_REEXPORT | EXPORT_SYMBOL_FLAGS_STUB_AND_RESOLVER);
+    let mut info = Vec::new();
+    match &export.data {
+        ExportData::Regular { address } => {
+            write_uleb(&mut info, flags);
+            write_uleb(&mut info, *address);
+        }
+        ExportData::Reexport {
+            library_ordinal,
+            imported_name,
+        } => {
+            flags |= EXPORT_SYMBOL_FLAGS_REEXPORT;
+            write_uleb(&mut info, flags);
+            write_uleb(&mut info, *library_ordinal);
+            info.extend_from_slice(imported_name.as_bytes());
+            info.push(0);
+        }
+        ExportData::StubAndResolver {
+            stub_address,
+            resolver_address,
+        } => {
+            flags |= EXPORT_SYMBOL_FLAGS_STUB_AND_RESOLVER;
+            write_uleb(&mut info, flags);
+            write_uleb(&mut info, *stub_address);
+            write_uleb(&mut info, *resolver_address);
+        }
+    }
+    info
+}
+
+fn uleb_len(n: u64) -> u64 {
+    let bits = 64 - u64::from(n.leading_zeros());
+    bits.div_ceil(7).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_export_trie() {
+        // "_" -> { "foo" (0x1000), "bar" (weak, 0x2000) }
+        let trie = [
+            0x00, 0x01, b'_', 0x00, 0x05, // root
+            0x00, 0x02, b'f', b'o', b'o', 0x00, 0x11, b'b', b'a', b'r', 0x00, 0x16, // "_"
+            0x03, 0x00, 0x80, 0x20, 0x00, // "_foo"
+            0x03, 0x04, 0x80, 0x40, 0x00, // "_bar"
+        ];
+        let exports = parse(&trie).unwrap();
+        assert_eq!(
+            exports,
+            [
+                Export::regular("_foo", 0, 0x1000),
+                Export::regular("_bar", EXPORT_SYMBOL_FLAGS_WEAK_DEFINITION, 0x2000),
+            ]
+        );
+        assert!(exports[1].is_weak());
+
+        // a child pointing back to the root.
+        let mut cyclic = trie;
+        cyclic[4] = 0x00;
+        assert!(matches!(
+            parse(&cyclic),
+            Err(Error::UnknownValue { offset: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn build_and_parse_export_trie() {
+        let mut exports = vec![
+            Export::regular("_main", 0, 0x3f50),
+            Export::regular("__mh_execute_header", 0, 0),
+            Export::regular("_mach", EXPORT_SYMBOL_FLAGS_KIND_THREAD_LOCAL, 0x8000),
+            Export::regular("_ma", EXPORT_SYMBOL_FLAGS_KIND_ABSOLUTE, 0x1234),
+            Export {
+                name: "_strlen".to_string(),
+                flags: EXPORT_SYMBOL_FLAGS_REEXPORT,
+                data: ExportData::Reexport {
+                    library_ordinal: 2,
+                    imported_name: "_platform_strlen".to_string(),
+                },
+            },
+            Export {
+                name: "_resolved".to_string(),
+                flags: EXPORT_SYMBOL_FLAGS_STUB_AND_RESOLVER,
+                data: ExportData::StubAndResolver {
+                    stub_address: 0x4000,
+                    resolver_address: 0x4100,
+                },
+            },
+        ];
+        // offsets of children grow beyond one ULEB byte.
+        exports.extend((0..64).map(|i| Export::regular(format!("_sym{}", i), 0, i * 0x100)));
+
+        let trie = build(&exports);
+        let mut parsed = parse(&trie).unwrap();
+        assert!(trie.len() > 0x80);
+        assert_eq!(parsed.len(), exports.len());
+
+        parsed.sort_by(|a, b| a.name.cmp(&b.name));
+        exports.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(parsed, exports);
+        assert_eq!(parsed[1].name, "_ma");
+        assert_eq!(parsed[1].kind(), ExportKind::Absolute);
+        assert!(build(&[]).is_empty());
+    }
+}
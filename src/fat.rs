@@ -0,0 +1,295 @@
+//! Universal (fat) files, which bundle Mach-O files of several architectures.
+//!
+//! These functions correspond to the operations of `lipo`. Every function which produces a fat
+//! file lays slices out in the given order, with the offset of each one aligned to
+//! `1 << FatArch64::align`. Slices are described by `FatArch64` so that both `FAT_MAGIC` and
+//! `FAT_MAGIC_64` files can be handled, and `FAT_MAGIC_64` is written only if some slice does not
+//! fit in 32-bit offsets.
+
+use crate::{
+    error::{Error, Result},
+    header::{CpuType, FatArch, FatArch64, FatHeader, FatHeader64, Header},
+    layout::page_size,
+};
+use std::io::Cursor;
+
+/// The largest `FatArch64::align`, which is the largest alignment `lipo` accepts.
+const MAX_ALIGN: u32 = 15;
+
+/// Derives the `FatArch64` of a thin Mach-O file from its header.
+///
+/// `offset` is left 0, and `align` is the page size of the cpu type.
+pub fn fat_arch_of(slice: &[u8]) -> Result<FatArch64> {
+    let cpu_type = match Header::read_from(&mut Cursor::new(slice))? {
+        Header::Mach(header) => header.cpu_type,
+        Header::Mach32(header) => header.cpu_type,
+        header => {
+            return Err(Error::BadMagic {
+                offset: 0,
+                magic: header.magic().to_u32(),
+            })
+        }
+    };
+
+    Ok(FatArch64 {
+        cpu_type,
+        offset: 0,
+        size: slice.len() as u64,
+        align: page_size(cpu_type).trailing_zeros(),
+        reserved: 0,
+    })
+}
+
+/// Creates a fat file from thin Mach-O files.
+pub fn create(slices: &[&[u8]]) -> Result<Vec<u8>> {
+    let slices = slices
+        .iter()
+        .map(|slice| Ok((fat_arch_of(slice)?, *slice)))
+        .collect::<Result<Vec<_>>>()?;
+    build(&slices)
+}
+
+/// Returns the `FatArch64` and the contents of every slice.
+pub fn slices(data: &[u8]) -> Result<Vec<(FatArch64, &[u8])>> {
+    // file offset of `align` of the first fat_arch, and the size of a fat_arch.
+    let (fat_archs, align_offset, arch_size) = match Header::read_from(&mut Cursor::new(data))? {
+        Header::Fat(header) => {
+            let fat_archs = header.fat_archs.into_iter().map(FatArch64::from).collect();
+            (fat_archs, FatHeader::SIZE + 16, FatArch::SIZE)
+        }
+        Header::Fat64(header) => (header.fat_archs, FatHeader64::SIZE + 24, FatArch64::SIZE),
+        header => {
+            return Err(Error::BadMagic {
+                offset: 0,
+                magic: header.magic().to_u32(),
+            })
+        }
+    };
+
+    fat_archs
+        .into_iter()
+        .enumerate()
+        .map(|(i, arch)| {
+            if arch.align > MAX_ALIGN {
+                return Err(Error::UnknownValue {
+                    offset: u64::from(align_offset) + i as u64 * u64::from(arch_size),
+                    kind: "fat arch alignment",
+                    value: arch.align.into(),
+                });
+            }
+            let slice = arch
+                .offset
+                .checked_add(arch.size)
+                .and_then(|end| usize::try_from(end).ok())
+                .zip(usize::try_from(arch.offset).ok())
+                .and_then(|(end, start)| data.get(start..end));
+            let slice = slice.ok_or(Error::Truncated {
+                offset: data.len() as u64,
+            })?;
+            Ok((arch, slice))
+        })
+        .collect()
+}
+
+/// Returns the thin Mach-O file for `cpu_type`.
+pub fn thin(data: &[u8], cpu_type: CpuType) -> Result<&[u8]> {
+    let slices = slices(data)?;
+    let idx = find(&slices, cpu_type)?;
+    Ok(slices[idx].1)
+}
+
+/// Creates a fat file which only has the slices for `cpu_types`.
+pub fn extract(data: &[u8], cpu_types: &[CpuType]) -> Result<Vec<u8>> {
+    let slices = slices(data)?;
+    let extracted = cpu_types
+        .iter()
+        .map(|cpu_type| Ok(slices[find(&slices, *cpu_type)?]))
+        .collect::<Result<Vec<_>>>()?;
+    build(&extracted)
+}
+
+/// Creates a fat file without the slices for `cpu_types`.
+pub fn remove(data: &[u8], cpu_types: &[CpuType]) -> Result<Vec<u8>> {
+    let mut slices = slices(data)?;
+    for cpu_type in cpu_types {
+        let idx = find(&slices, *cpu_type)?;
+        slices.remove(idx);
+    }
+    build(&slices)
+}
+
+/// Creates a fat file whose slice for the cpu type of `slice` is replaced with `slice`.
+pub fn replace(data: &[u8], slice: &[u8]) -> Result<Vec<u8>> {
+    let new_arch = fat_arch_of(slice)?;
+    let mut slices = slices(data)?;
+    let idx = find(&slices, new_arch.cpu_type)?;
+    // keep the alignment of the original slice.
+    let align = slices[idx].0.align;
+    slices[idx] = (FatArch64 { align, ..new_arch }, slice);
+    build(&slices)
+}
+
+/// Finds the slice for `cpu_type`, ignoring the capability bits of the cpu subtype.
+fn find(slices: &[(FatArch64, &[u8])], cpu_type: CpuType) -> Result<usize> {
+    let cpu_type = cpu_type.without_capabilities();
+    slices
+        .iter()
+        .position(|(arch, _)| arch.cpu_type.without_capabilities() == cpu_type)
+        .ok_or(Error::ArchNotFound { cpu_type })
+}
+
+/// Writes the slices with their `offset` and `size` recomputed.
+fn build(slices: &[(FatArch64, &[u8])]) -> Result<Vec<u8>> {
+    for (i, (arch, _)) in slices.iter().enumerate() {
+        if find(&slices[..i], arch.cpu_type).is_ok() {
+            return Err(Error::DuplicateArch {
+                cpu_type: arch.cpu_type,
+            });
+        }
+    }
+
+    let fat_archs = layout_slices(slices, FatHeader::SIZE, FatArch::SIZE);
+    let fits_in_32bit = fat_archs
+        .iter()
+        .all(|arch| u32::try_from(arch.offset + arch.size).is_ok());
+
+    let mut buf = Vec::new();
+    let fat_archs = if fits_in_32bit {
+        let archs32 = fat_archs.iter().map(|arch| FatArch {
+            cpu_type: arch.cpu_type,
+            offset: arch.offset as u32,
+            size: arch.size as u32,
+            align: arch.align,
+        });
+        FatHeader::new(archs32.collect()).write_into(&mut buf)?;
+        fat_archs
+    } else {
+        let fat_archs = layout_slices(slices, FatHeader64::SIZE, FatArch64::SIZE);
+        FatHeader64::new(fat_archs.clone()).write_into(&mut buf)?;
+        fat_archs
+    };
+
+    for (arch, (_, slice)) in fat_archs.iter().zip(slices.iter()) {
+        buf.resize(arch.offset as usize, 0);
+        buf.extend_from_slice(slice);
+    }
+    Ok(buf)
+}
+
+/// Computes the aligned offset of every slice placed after the fat header.
+fn layout_slices(
+    slices: &[(FatArch64, &[u8])],
+    header_size: u32,
+    arch_size: u32,
+) -> Vec<FatArch64> {
+    let mut offset = header_size as u64 + arch_size as u64 * slices.len() as u64;
+    let mut fat_archs = Vec::with_capacity(slices.len());
+    for (arch, slice) in slices.iter() {
+        offset = offset.div_ceil(1 << arch.align) << arch.align;
+        fat_archs.push(FatArch64 {
+            offset,
+            size: slice.len() as u64,
+            ..*arch
+        });
+        offset += slice.len() as u64;
+    }
+    fat_archs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::ObjectBuilder,
+        header::{CpuCapabilities, CpuSubTypeArm64, CpuSubTypeX86_64},
+    };
+
+    const X86_64: CpuType = CpuType::X86_64(CpuSubTypeX86_64::All, CpuCapabilities::NONE);
+    const ARM64: CpuType = CpuType::Arm64(CpuSubTypeArm64::All, CpuCapabilities::NONE);
+
+    fn object(cpu_type: CpuType) -> Vec<u8> {
+        let mut file = ObjectBuilder::new().build().unwrap();
+        file.header.cpu_type = cpu_type;
+        let mut buf = Vec::new();
+        file.write(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn create_and_thin() {
+        let x86_64 = object(X86_64);
+        let arm64 = object(ARM64);
+        let fat = create(&[&x86_64, &arm64]).unwrap();
+
+        // fat headers are big endian.
+        assert_eq!(fat[..4], [0xca, 0xfe, 0xba, 0xbe]);
+
+        let slices = slices(&fat).unwrap();
+        assert_eq!(slices.len(), 2);
+        assert_eq!((slices[0].0.offset, slices[0].0.align), (0x1000, 12));
+        assert_eq!((slices[1].0.offset, slices[1].0.align), (0x4000, 14));
+
+        assert_eq!(thin(&fat, X86_64).unwrap(), x86_64.as_slice());
+        assert_eq!(thin(&fat, ARM64).unwrap(), arm64.as_slice());
+        assert!(matches!(
+            create(&[&x86_64, &x86_64]),
+            Err(Error::DuplicateArch { .. })
+        ));
+    }
+
+    #[test]
+    fn extract_remove_and_replace() {
+        let x86_64 = object(X86_64);
+        let arm64 = object(ARM64);
+        let fat = create(&[&x86_64, &arm64]).unwrap();
+
+        let extracted = extract(&fat, &[ARM64]).unwrap();
+        assert_eq!(extracted, create(&[&arm64]).unwrap());
+        assert_eq!(remove(&fat, &[X86_64]).unwrap(), extracted);
+        assert!(matches!(
+            thin(&extracted, X86_64),
+            Err(Error::ArchNotFound { .. })
+        ));
+
+        let mut new_x86_64 = x86_64.clone();
+        new_x86_64.extend_from_slice(&[0; 8]);
+        let replaced = replace(&fat, &new_x86_64).unwrap();
+        assert_eq!(thin(&replaced, X86_64).unwrap(), new_x86_64.as_slice());
+        assert_eq!(thin(&replaced, ARM64).unwrap(), arm64.as_slice());
+    }
+
+    #[test]
+    fn thin_fat64() {
+        let arm64 = object(ARM64);
+        let offset = (FatHeader64::SIZE + FatArch64::SIZE) as u64;
+        let mut fat = Vec::new();
+        FatHeader64::new(vec![FatArch64 {
+            offset,
+            ..fat_arch_of(&arm64).unwrap()
+        }])
+        .write_into(&mut fat)
+        .unwrap();
+        fat.extend_from_slice(&arm64);
+
+        assert_eq!(thin(&fat, ARM64).unwrap(), arm64.as_slice());
+        // rewritten as `FAT_MAGIC` since every slice fits in 32-bit offsets.
+        assert_eq!(extract(&fat, &[ARM64]).unwrap(), create(&[&arm64]).unwrap());
+
+        // a slice whose end overflows
+        let mut overflow = fat.clone();
+        overflow[16..24].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(matches!(slices(&overflow), Err(Error::Truncated { .. })));
+
+        // an alignment which does not fit in u64
+        let mut align = fat;
+        align[32..36].copy_from_slice(&64u32.to_be_bytes());
+        assert!(matches!(
+            extract(&align, &[ARM64]),
+            Err(Error::UnknownValue {
+                offset: 32,
+                value: 64,
+                ..
+            })
+        ));
+    }
+}
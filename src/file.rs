@@ -0,0 +1,497 @@
+use crate::{
+    error::{Error, Result},
+    header::Header64,
+    io::{Endian, ReadExt as _},
+    load_command::{unix_thread::ThreadState, LoadCommand, Section64, SymtabCommand},
+    nlist::NList64,
+    reloc::RelocationInfo,
+    string_table::StringTable,
+};
+use std::io::{Cursor, Write};
+
+/// A whole 64-bit Mach-O file.
+///
+/// `parse` followed by `write` reproduces the input byte-for-byte. Bytes which are not
+/// described by the header, load commands, sections, relocations or the symbol table (such as
+/// padding or data referred by unsupported load commands) are kept in `extra` at their original
+/// offsets.
+#[derive(Debug, Clone)]
+pub struct MachOFile {
+    pub header: Header64,
+    pub load_commands: Vec<LoadCommand>,
+    /// Contents of every section, in the order they appear in the load commands.
+    pub sections: Vec<SectionData>,
+    /// Entries of the symbol table referred by `LC_SYMTAB`.
+    pub symbols: Vec<NList64>,
+    pub string_table: StringTable,
+    /// Chunks of the file not covered by any other field, keyed by their file offset.
+    pub extra: Vec<(u64, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionData {
+    /// Empty for zero-fill sections.
+    pub data: Vec<u8>,
+    pub relocs: Vec<RelocationInfo>,
+}
+
+impl MachOFile {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut read = Cursor::new(data);
+
+        let header = Header64::read_from(&mut read)?;
+        let endian = header.endian();
+
+        let load_commands = (0..header.n_cmds)
+            .map(|_| LoadCommand::read_from_in(&mut read, endian, header.cpu_type))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut covered = Vec::new();
+        let cmds_end = Header64::SIZE as u64 + header.size_of_cmds as u64;
+        cover(&mut covered, data, 0, cmds_end)?;
+
+        let mut sections = Vec::new();
+        for sect in load_commands.iter().flat_map(sections_of) {
+            let contents = if is_zerofill(sect) {
+                Vec::new()
+            } else {
+                read.set_position(sect.offset as u64);
+                cover(&mut covered, data, sect.offset as u64, sect.size)?;
+                read.read_bytes(sect.size as usize)?
+            };
+
+            read.set_position(sect.reloff as u64);
+            let relocs = (0..sect.nreloc)
+                .map(|_| RelocationInfo::read_from_in(&mut read, endian))
+                .collect::<Result<Vec<_>>>()?;
+            cover(
+                &mut covered,
+                data,
+                sect.reloff as u64,
+                sect.nreloc as u64 * RelocationInfo::SIZE as u64,
+            )?;
+
+            sections.push(SectionData {
+                data: contents,
+                relocs,
+            });
+        }
+
+        let mut symbols = Vec::new();
+        let mut string_table = StringTable::with_null();
+        if let Some(symtab) = symtab_of(&load_commands) {
+            read.set_position(symtab.symoff as u64);
+            symbols = (0..symtab.nsyms)
+                .map(|_| NList64::read_from_in(&mut read, endian))
+                .collect::<Result<Vec<_>>>()?;
+            cover(
+                &mut covered,
+                data,
+                symtab.symoff as u64,
+                symtab.nsyms as u64 * NList64::SIZE as u64,
+            )?;
+
+            read.set_position(symtab.stroff as u64);
+            let strings = read.read_bytes(symtab.strsize as usize)?;
+            string_table = StringTable::from_raw(strings);
+            cover(
+                &mut covered,
+                data,
+                symtab.stroff as u64,
+                symtab.strsize as u64,
+            )?;
+        }
+
+        let extra = uncovered(data, covered);
+
+        Ok(MachOFile {
+            header,
+            load_commands,
+            sections,
+            symbols,
+            string_table,
+            extra,
+        })
+    }
+
+    /// Writes every piece at the offset recorded in the header and load commands.
+    ///
+    /// Regions not covered by anything are filled with zeros.
+    pub fn write<W: Write>(&self, write: &mut W) -> Result<()> {
+        let endian = self.header.endian();
+
+        let mut head = Vec::new();
+        self.header.write_into(&mut head)?;
+        for lc in self.load_commands.iter() {
+            lc.write_into_in(&mut head, endian)?;
+        }
+
+        let mut out = FileBuf::default();
+        for (offset, data) in self.extra.iter() {
+            out.put(*offset, data);
+        }
+        out.put(0, &head);
+
+        let sects = self.load_commands.iter().flat_map(sections_of);
+        for (sect, sect_data) in sects.zip(self.sections.iter()) {
+            if !is_zerofill(sect) {
+                out.put(sect.offset as u64, &sect_data.data);
+            }
+
+            let mut relocs = Vec::new();
+            for reloc in sect_data.relocs.iter() {
+                reloc.write_into_in(&mut relocs, endian)?;
+            }
+            out.put(sect.reloff as u64, &relocs);
+        }
+
+        if let Some(symtab) = symtab_of(&self.load_commands) {
+            let mut symbols = Vec::new();
+            for symbol in self.symbols.iter() {
+                symbol.write_into_in(&mut symbols, endian)?;
+            }
+            out.put(symtab.symoff as u64, &symbols);
+            out.put(symtab.stroff as u64, self.string_table.as_ref());
+        }
+
+        Ok(write.write_all(&out.0)?)
+    }
+
+    /// Returns the section header and contents of every section.
+    pub fn sections(&self) -> impl Iterator<Item = (&Section64, &SectionData)> {
+        self.load_commands
+            .iter()
+            .flat_map(sections_of)
+            .zip(self.sections.iter())
+    }
+
+    pub fn endian(&self) -> Endian {
+        self.header.endian()
+    }
+
+    /// Returns the VM address where the program starts.
+    ///
+    /// It comes from `entryoff` of `LC_MAIN`, which is resolved through the `__TEXT` segment, or
+    /// the program counter of `LC_UNIXTHREAD` or `LC_THREAD`. Returns `None` if there is no such
+    /// command, `entryoff` is not in `__TEXT` or the thread flavor is not supported.
+    pub fn entry_point(&self) -> Option<u64> {
+        self.load_commands.iter().find_map(|lc| match lc {
+            LoadCommand::EntryPoint(cmd) => {
+                let text = self.load_commands.iter().find_map(|lc| match lc {
+                    LoadCommand::Segment64(seg, _) if seg.segname == "__TEXT" => Some(seg),
+                    _ => None,
+                })?;
+                let offset = cmd.entryoff.checked_sub(text.fileoff)?;
+                (offset < text.filesize).then(|| text.vmaddr + offset)
+            }
+            LoadCommand::UnixThread(cmd) => cmd.states.iter().find_map(ThreadState::pc),
+            _ => None,
+        })
+    }
+}
+
+fn sections_of(lc: &LoadCommand) -> &[Section64] {
+    match lc {
+        LoadCommand::Segment64(_, sects) => sects.as_slice(),
+        _ => &[],
+    }
+}
+
+fn symtab_of(lcs: &[LoadCommand]) -> Option<&SymtabCommand> {
+    lcs.iter().find_map(|lc| match lc {
+        LoadCommand::Symtab(symtab) => Some(symtab),
+        _ => None,
+    })
+}
+
+pub(crate) fn is_zerofill(sect: &Section64) -> bool {
+    sect.flags.1.is_zerofill()
+}
+
+/// Records the `size` bytes at `offset` as covered, checking that they are in `data`.
+///
+/// Empty regions are skipped, since their offsets are meaningless (e.g. `reloff` of a section
+/// without relocations) and may point past the end of the file.
+fn cover(covered: &mut Vec<(u64, u64)>, data: &[u8], offset: u64, size: u64) -> Result<()> {
+    if size == 0 {
+        return Ok(());
+    }
+    match offset.checked_add(size) {
+        Some(end) if end <= data.len() as u64 => {
+            covered.push((offset, size));
+            Ok(())
+        }
+        _ => Err(Error::Truncated { offset }),
+    }
+}
+
+/// Collects the bytes of `data` which are not in any of the `(offset, size)` ranges, which must
+/// be non-empty and in `data`.
+fn uncovered(data: &[u8], mut covered: Vec<(u64, u64)>) -> Vec<(u64, Vec<u8>)> {
+    covered.sort_unstable();
+
+    let mut extra = Vec::new();
+    let mut pos = 0;
+    for (offset, size) in covered {
+        if offset > pos {
+            extra.push((pos, data[pos as usize..offset as usize].to_vec()));
+        }
+        pos = pos.max(offset + size);
+    }
+    if (pos as usize) < data.len() {
+        extra.push((pos, data[pos as usize..].to_vec()));
+    }
+    extra
+}
+
+/// A growable buffer which zero-fills the space skipped by `put`.
+#[derive(Default)]
+struct FileBuf(Vec<u8>);
+
+impl FileBuf {
+    /// Empty data is ignored, so that meaningless offsets of empty regions don't grow the file.
+    fn put(&mut self, offset: u64, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        let offset = offset as usize;
+        let end = offset + data.len();
+        if self.0.len() < end {
+            self.0.resize(end, 0);
+        }
+        self.0[offset..end].copy_from_slice(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::ExecutableBuilder,
+        header::{CpuCapabilities, CpuSubTypeX86_64, CpuType, FileType, Flags, Magic},
+        load_command::{
+            segment64::{SectionAttrs, SectionType},
+            EntryPointCommand, SegmentCommand64,
+        },
+        nlist::{NType, NTypeField},
+        reloc::{RelocLength, X86_64RelocType},
+    };
+
+    /// Builds an object file with one `__TEXT,__text` section which has a relocation against
+    /// `_foo`, and symbols `_main` and `_foo`.
+    fn build_object(endian: Endian) -> Vec<u8> {
+        let text = [0xe8, 0x00, 0x00, 0x00, 0x00, 0xc3];
+        let cmds_size = SegmentCommand64::SIZE + Section64::SIZE + SymtabCommand::SIZE;
+        let text_off = Header64::SIZE + cmds_size;
+        let reloff = text_off + 8;
+        let symoff = reloff + RelocationInfo::SIZE;
+        let stroff = symoff + NList64::SIZE * 2;
+
+        let mut buf = Vec::new();
+        Header64 {
+            magic: Magic::Magic64,
+            cpu_type: CpuType::X86_64(CpuSubTypeX86_64::All, CpuCapabilities::NONE),
+            file_type: FileType::Object,
+            n_cmds: 2,
+            size_of_cmds: cmds_size,
+            flags: Flags::new(),
+            reserved: 0,
+        }
+        .write_into_in(&mut buf, endian)
+        .unwrap();
+        LoadCommand::Segment64(
+            SegmentCommand64 {
+                cmd: SegmentCommand64::TYPE,
+                cmdsize: SegmentCommand64::SIZE + Section64::SIZE,
+                segname: String::new(),
+                vmaddr: 0,
+                vmsize: text.len() as u64,
+                fileoff: text_off.into(),
+                filesize: text.len() as u64,
+                maxprot: 7,
+                initprot: 7,
+                nsects: 1,
+                flags: 0,
+            },
+            vec![Section64 {
+                sectname: "__text".to_string(),
+                segname: "__TEXT".to_string(),
+                addr: 0,
+                size: text.len() as u64,
+                offset: text_off,
+                align: 0,
+                reloff,
+                nreloc: 1,
+                flags: (SectionAttrs::new(), SectionType::Regular),
+                reserved1: 0,
+                reserved2: 0,
+                reserved3: 0,
+            }],
+        )
+        .write_into_in(&mut buf, endian)
+        .unwrap();
+        SymtabCommand {
+            cmd: SymtabCommand::TYPE,
+            cmdsize: SymtabCommand::SIZE,
+            symoff,
+            nsyms: 2,
+            stroff,
+            strsize: 16,
+        }
+        .write_into_in(&mut buf, endian)
+        .unwrap();
+
+        buf.extend_from_slice(&text);
+        // non-zero padding must survive a round trip
+        buf.extend_from_slice(&[0x90, 0x90]);
+        RelocationInfo {
+            r_address: 1,
+            r_symbolnum: 1,
+            r_pcrel: true,
+            r_length: RelocLength::Long,
+            r_extern: true,
+            r_type: X86_64RelocType::Branch.to_u8(),
+        }
+        .write_into_in(&mut buf, endian)
+        .unwrap();
+        for (n_strx, n_type, n_sect) in [(1, NType::Sect, 1), (7, NType::Undf, 0)] {
+            NList64 {
+                n_strx,
+                n_type: NTypeField::Norm {
+                    n_pext: false,
+                    n_type,
+                    n_ext: true,
+                },
+                n_sect,
+                n_desc: 0,
+                n_value: 0,
+            }
+            .write_into_in(&mut buf, endian)
+            .unwrap();
+        }
+        buf.extend_from_slice(b"\0_main\0_foo\0\0\0\0\0");
+        // trailing bytes which nothing refers to
+        buf.extend_from_slice(b"tail");
+
+        buf
+    }
+
+    #[test]
+    fn parse_and_write_object() {
+        let buf = build_object(Endian::NATIVE);
+        let file = MachOFile::parse(&buf).unwrap();
+
+        assert_eq!(file.sections.len(), 1);
+        assert_eq!(file.sections[0].data, [0xe8, 0x00, 0x00, 0x00, 0x00, 0xc3]);
+        assert_eq!(file.sections[0].relocs.len(), 1);
+        assert_eq!(file.symbols.len(), 2);
+        assert_eq!(file.string_table.get(7), Some("_foo"));
+
+        let mut written = Vec::new();
+        file.write(&mut written).unwrap();
+        assert_eq!(written, buf);
+    }
+
+    #[test]
+    fn parse_and_write_reverse_endian_object() {
+        let buf = build_object(Endian::REVERSE);
+        let file = MachOFile::parse(&buf).unwrap();
+
+        assert_eq!(file.endian(), Endian::REVERSE);
+        assert_eq!(file.string_table.get(1), Some("_main"));
+
+        let mut written = Vec::new();
+        file.write(&mut written).unwrap();
+        assert_eq!(written, buf);
+    }
+
+    #[test]
+    fn parse_and_write_non_ascii_strings() {
+        let mut buf = build_object(Endian::NATIVE);
+        // a UTF-8 symbol name of the same length, and padding which is not UTF-8.
+        let pos = buf.windows(5).position(|w| w == b"_foo\0").unwrap();
+        buf[pos..pos + 4].copy_from_slice("_fö".as_bytes());
+        buf[pos + 6] = 0xff;
+
+        let file = MachOFile::parse(&buf).unwrap();
+        assert_eq!(file.string_table.get(7), Some("_fö"));
+
+        let mut written = Vec::new();
+        file.write(&mut written).unwrap();
+        assert_eq!(written, buf);
+    }
+
+    #[test]
+    fn parse_and_write_empty_regions_past_eof() {
+        let mut buf = build_object(Endian::NATIVE);
+        // no relocations at a garbage offset, as some tools leave them.
+        let sect = (Header64::SIZE + SegmentCommand64::SIZE) as usize;
+        buf[sect + 56..sect + 60].copy_from_slice(&0xffff_fff0u32.to_ne_bytes());
+        buf[sect + 60..sect + 64].copy_from_slice(&0u32.to_ne_bytes());
+
+        let file = MachOFile::parse(&buf).unwrap();
+        assert!(file.sections[0].relocs.is_empty());
+
+        let mut written = Vec::new();
+        file.write(&mut written).unwrap();
+        assert_eq!(written, buf);
+
+        // a non-empty region past the end is an error rather than a panic.
+        buf[sect + 60..sect + 64].copy_from_slice(&1u32.to_ne_bytes());
+        assert!(matches!(
+            MachOFile::parse(&buf),
+            Err(Error::Truncated { .. })
+        ));
+    }
+
+    #[test]
+    fn parse_and_write_unsupported_command() {
+        let buf = build_object(Endian::NATIVE);
+        let mut file = MachOFile::parse(&buf).unwrap();
+        file.load_commands
+            .push(LoadCommand::Unsupported(0x7fff_0001, vec![0xab; 24]));
+        file.layout();
+
+        let mut buf = Vec::new();
+        file.write(&mut buf).unwrap();
+        let file = MachOFile::parse(&buf).unwrap();
+        assert_eq!(
+            file.load_commands.last(),
+            Some(&LoadCommand::Unsupported(0x7fff_0001, vec![0xab; 24]))
+        );
+        assert_eq!(file.header.size_of_cmds, {
+            let sizes = file.load_commands.iter().map(|lc| lc.cmd_size());
+            sizes.sum::<u32>()
+        });
+
+        let mut written = Vec::new();
+        file.write(&mut written).unwrap();
+        assert_eq!(written, buf);
+    }
+
+    #[test]
+    fn entry_point_of_main_and_thread() {
+        let mut builder = ExecutableBuilder::new();
+        let text = builder.add_text_section("__text", 4, vec![0x90, 0x90, 0xc3]);
+        builder.define_symbol("start", text, 2, true).unwrap();
+        builder.entry_symbol("start");
+        let mut file = builder.build().unwrap();
+
+        let text_addr = file.sections().next().unwrap().0.addr;
+        assert_eq!(file.entry_point(), Some(text_addr + 2));
+
+        // replace LC_UNIXTHREAD with LC_MAIN which points at the same instruction
+        let text_seg = file.load_commands.iter().find_map(|lc| match lc {
+            LoadCommand::Segment64(seg, _) if seg.segname == "__TEXT" => Some(seg.clone()),
+            _ => None,
+        });
+        let text_seg = text_seg.unwrap();
+        let entryoff = text_addr + 2 - text_seg.vmaddr + text_seg.fileoff;
+        for lc in file.load_commands.iter_mut() {
+            if let LoadCommand::UnixThread(_) = lc {
+                *lc = LoadCommand::EntryPoint(EntryPointCommand::new(entryoff, 0));
+            }
+        }
+        assert_eq!(file.entry_point(), Some(text_addr + 2));
+    }
+}
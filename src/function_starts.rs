@@ -0,0 +1,83 @@
+//! The function starts table, which lists the start address of every function in `__TEXT`.
+//!
+//! The table is referred by `LC_FUNCTION_STARTS`. It is a sequence of ULEB128 deltas, the first
+//! one from the start of `__TEXT` and the others from the previous function, terminated by a
+//! zero delta. Since it does not depend on the symbol table, it is available even in stripped
+//! images.
+
+use crate::{
+    error::Result,
+    leb128::{write_uleb, ByteReader},
+};
+
+/// Parses the table and returns the VM addresses of the functions, `text_vmaddr` being the
+/// vmaddr of the `__TEXT` segment.
+pub fn parse(data: &[u8], text_vmaddr: u64) -> Result<Vec<u64>> {
+    parse_at(data, 0, text_vmaddr)
+}
+
+pub(crate) fn parse_at(data: &[u8], base: u64, text_vmaddr: u64) -> Result<Vec<u64>> {
+    let mut reader = ByteReader::new(data, base);
+    let mut addrs = Vec::new();
+    let mut addr = text_vmaddr;
+    // the table is padded with zeros, which also terminate it.
+    while !reader.is_empty() {
+        let delta = reader.read_uleb()?;
+        if delta == 0 {
+            break;
+        }
+        addr = addr.wrapping_add(delta);
+        addrs.push(addr);
+    }
+    Ok(addrs)
+}
+
+/// Encodes the VM addresses of functions into the table, padded to 8 bytes as ld64 does.
+///
+/// `addrs` must be sorted in ascending order without duplicates, and must be above
+/// `text_vmaddr` since `__TEXT` starts with the mach header.
+pub fn encode(addrs: &[u64], text_vmaddr: u64) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut prev = text_vmaddr;
+    for &addr in addrs.iter() {
+        // a zero delta would terminate the table.
+        assert!(addr > prev, "function starts must be ascending from __TEXT");
+        write_uleb(&mut buf, addr - prev);
+        prev = addr;
+    }
+    buf.push(0);
+    buf.resize(buf.len().next_multiple_of(8), 0);
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn parse_function_starts() {
+        // 0x100003f50, 0x100003f80, 0x100004000
+        let data = [0xd0, 0x7e, 0x30, 0x80, 0x01, 0x00, 0x00, 0x00];
+        assert_eq!(
+            parse(&data, 0x100000000).unwrap(),
+            [0x100003f50, 0x100003f80, 0x100004000]
+        );
+
+        assert!(matches!(
+            parse_at(&data[..1], 0x8000, 0x100000000),
+            Err(Error::Truncated { offset: 0x8001 })
+        ));
+    }
+
+    #[test]
+    fn encode_and_parse_function_starts() {
+        let addrs = [0x100003f50, 0x100003f80, 0x100004000, 0x100123456];
+        let data = encode(&addrs, 0x100000000);
+        assert_eq!(data.len() % 8, 0);
+        assert_eq!(&data[..5], [0xd0, 0x7e, 0x30, 0x80, 0x01]);
+        assert_eq!(parse(&data, 0x100000000).unwrap(), addrs);
+
+        assert_eq!(encode(&[], 0x1000), [0; 8]);
+    }
+}
@@ -1,45 +1,57 @@
-use crate::io::{Endian, ReadExt as _, WriteExt as _};
-use byteorder::{BigEndian, NativeEndian, ReadBytesExt as _, WriteBytesExt as _};
+use crate::{
+    error::Result,
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use std::{
     fmt,
-    io::{Read, Write},
+    io::{Read, Seek, Write},
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Header {
     Mach(Header64),
+    Mach32(Header32),
     Fat(FatHeader),
+    Fat64(FatHeader64),
 }
 
 impl Header {
     pub fn magic(&self) -> Magic {
         match self {
             Header::Mach(mach_header) => mach_header.magic,
+            Header::Mach32(mach_header) => mach_header.magic,
             Header::Fat(fat_header) => fat_header.magic,
+            Header::Fat64(fat_header) => fat_header.magic,
         }
     }
 
-    pub fn read_from<R: Read>(read: &mut R) -> Self {
-        let magic_n = read.read_u32::<NativeEndian>().unwrap();
-        let magic = Magic::from_u32(magic_n);
+    pub fn read_from<R: Read + Seek>(read: &mut R) -> Result<Self> {
+        let magic = Magic::read_from(read)?;
 
         match magic {
             Magic::Magic64 | Magic::Cigam64 => {
-                Header::Mach(Header64::read_after_magic(read, magic))
+                Ok(Header::Mach(Header64::read_after_magic(read, magic)?))
+            }
+            Magic::Magic | Magic::Cigam => {
+                Ok(Header::Mach32(Header32::read_after_magic(read, magic)?))
             }
-            Magic::Magic | Magic::Cigam => unimplemented!(),
             Magic::FatMagic | Magic::FatCigam => {
-                Header::Fat(FatHeader::read_after_magic(read, magic))
+                Ok(Header::Fat(FatHeader::read_after_magic(read, magic)?))
+            }
+            Magic::FatMagic64 | Magic::FatCigam64 => {
+                Ok(Header::Fat64(FatHeader64::read_after_magic(read, magic)?))
             }
         }
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
         match self {
             Header::Mach(mach_header) => mach_header.write_into(write),
+            Header::Mach32(mach_header) => mach_header.write_into(write),
             Header::Fat(fat_header) => fat_header.write_into(write),
+            Header::Fat64(fat_header) => fat_header.write_into(write),
         }
     }
 }
@@ -58,35 +70,32 @@ pub struct Header64 {
 impl Header64 {
     pub const SIZE: u32 = 0x20; // 32 bytes
 
-    pub fn read_from<R: Read>(read: &mut R) -> Self {
-        let magic_n = read.read_u32::<NativeEndian>().unwrap();
-        let magic = Magic::from_u32(magic_n);
+    pub fn read_from<R: Read + Seek>(read: &mut R) -> Result<Self> {
+        let magic = Magic::read_from(read)?;
 
         Self::read_after_magic(read, magic)
     }
 
-    fn read_after_magic<R: Read>(read: &mut R, magic: Magic) -> Self {
-        assert!(magic == Magic::Magic64 || magic == Magic::Cigam64);
+    fn read_after_magic<R: Read + Seek>(read: &mut R, magic: Magic) -> Result<Self> {
+        if magic != Magic::Magic64 && magic != Magic::Cigam64 {
+            return Err(read.bad_magic(magic.to_u32()));
+        }
 
         let endian = magic.endian();
 
-        let cpu_type_n = read.read_i32_in(endian);
-        let cpu_subtype_n = read.read_i32_in(endian);
-        let cpu_type = CpuType::from_i32_i32(cpu_type_n, cpu_subtype_n);
+        let cpu_type = CpuType::read_from_in(read, endian)?;
 
-        let file_type_n = read.read_u32_in(endian);
-        let file_type = FileType::from_u32(file_type_n);
+        let file_type = FileType::from_u32(read.read_u32_in(endian)?);
 
-        let n_cmds = read.read_u32_in(endian);
+        let n_cmds = read.read_u32_in(endian)?;
 
-        let size_of_cmds = read.read_u32_in(endian);
+        let size_of_cmds = read.read_u32_in(endian)?;
 
-        let flags_n = read.read_u32_in(endian);
-        let flags = Flags::from_u32(flags_n);
+        let flags = Flags::from_u32(read.read_u32_in(endian)?);
 
-        let reserved = read.read_u32_in(endian);
+        let reserved = read.read_u32_in(endian)?;
 
-        Header64 {
+        Ok(Header64 {
             magic,
             cpu_type,
             file_type,
@@ -94,19 +103,103 @@ impl Header64 {
             size_of_cmds,
             flags,
             reserved,
+        })
+    }
+
+    /// Writes this header in the byte order indicated by its `magic`, so that a header read
+    /// from a file is written back byte-identically.
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, self.endian())
+    }
+
+    /// Writes this header in the given byte order.
+    ///
+    /// The magic number is chosen to match `endian`, regardless of `self.magic`.
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(Magic::Magic64.to_u32(), endian)?;
+        let (cpu_type_n, cpu_subtype_n) = self.cpu_type.to_i32_i32();
+        write.write_i32_in(cpu_type_n, endian)?;
+        write.write_i32_in(cpu_subtype_n, endian)?;
+        write.write_u32_in(self.file_type.to_u32(), endian)?;
+        write.write_u32_in(self.n_cmds, endian)?;
+        write.write_u32_in(self.size_of_cmds, endian)?;
+        write.write_u32_in(self.flags.to_u32(), endian)?;
+        write.write_u32_in(self.reserved, endian)?;
+        Ok(())
+    }
+
+    pub fn endian(&self) -> Endian {
+        self.magic.endian()
+    }
+}
+
+/// The mach header of a file intended for use on a 32bit CPU.
+/// Same as `Header64` except that it does not have the `reserved` field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header32 {
+    pub magic: Magic,
+    pub cpu_type: CpuType,
+    pub file_type: FileType,
+    pub n_cmds: u32,
+    pub size_of_cmds: u32,
+    pub flags: Flags,
+}
+
+impl Header32 {
+    pub const SIZE: u32 = 0x1c; // 28
+
+    pub fn read_from<R: Read + Seek>(read: &mut R) -> Result<Self> {
+        let magic = Magic::read_from(read)?;
+
+        Self::read_after_magic(read, magic)
+    }
+
+    fn read_after_magic<R: Read + Seek>(read: &mut R, magic: Magic) -> Result<Self> {
+        if magic != Magic::Magic && magic != Magic::Cigam {
+            return Err(read.bad_magic(magic.to_u32()));
         }
+
+        let endian = magic.endian();
+
+        let cpu_type = CpuType::read_from_in(read, endian)?;
+
+        let file_type = FileType::from_u32(read.read_u32_in(endian)?);
+
+        let n_cmds = read.read_u32_in(endian)?;
+
+        let size_of_cmds = read.read_u32_in(endian)?;
+
+        let flags = Flags::from_u32(read.read_u32_in(endian)?);
+
+        Ok(Header32 {
+            magic,
+            cpu_type,
+            file_type,
+            n_cmds,
+            size_of_cmds,
+            flags,
+        })
+    }
+
+    /// Writes this header in the byte order indicated by its `magic`, so that a header read
+    /// from a file is written back byte-identically.
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, self.endian())
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
-        write.write_u32_native(self.magic.to_u32());
+    /// Writes this header in the given byte order.
+    ///
+    /// The magic number is chosen to match `endian`, regardless of `self.magic`.
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(Magic::Magic.to_u32(), endian)?;
         let (cpu_type_n, cpu_subtype_n) = self.cpu_type.to_i32_i32();
-        write.write_i32_native(cpu_type_n);
-        write.write_i32_native(cpu_subtype_n);
-        write.write_u32_native(self.file_type.to_u32());
-        write.write_u32_native(self.n_cmds);
-        write.write_u32_native(self.size_of_cmds);
-        write.write_u32_native(self.flags.to_u32());
-        write.write_u32_native(self.reserved);
+        write.write_i32_in(cpu_type_n, endian)?;
+        write.write_i32_in(cpu_subtype_n, endian)?;
+        write.write_u32_in(self.file_type.to_u32(), endian)?;
+        write.write_u32_in(self.n_cmds, endian)?;
+        write.write_u32_in(self.size_of_cmds, endian)?;
+        write.write_u32_in(self.flags.to_u32(), endian)?;
+        Ok(())
     }
 
     pub fn endian(&self) -> Endian {
@@ -116,6 +209,7 @@ impl Header64 {
 
 /// An integer containing a value identifying this file as a Mach-O file.
 #[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
 pub enum Magic {
     /// use if the file is intended for use on a 64bit CPU with the **same** endianness as the host
     /// computer.
@@ -135,15 +229,21 @@ pub enum Magic {
     /// use if the file contains code for more than one architecture and is intended for use on a
     /// CPU with the **reverse** endianness as the host computer.
     FatCigam = 0xbebafeca,
+    /// same as `FatMagic` but followed by `FatArch64`s, which can describe slices beyond 4 GiB.
+    FatMagic64 = 0xcafebabf,
+    /// same as `FatCigam` but followed by `FatArch64`s.
+    FatCigam64 = 0xbfbafeca,
 }
 
 impl Magic {
-    pub fn from_u32_checked(n: u32) -> Option<Self> {
+    pub fn from_u32(n: u32) -> Option<Self> {
         FromPrimitive::from_u32(n)
     }
 
-    pub fn from_u32(n: u32) -> Self {
-        Magic::from_u32_checked(n).unwrap()
+    /// Reads a magic number in the host byte order.
+    pub fn read_from<R: Read + Seek>(read: &mut R) -> Result<Self> {
+        let magic_n = read.read_u32_in(Endian::NATIVE)?;
+        Magic::from_u32(magic_n).ok_or_else(|| read.bad_magic(magic_n))
     }
 
     pub fn to_u32(&self) -> u32 {
@@ -152,103 +252,313 @@ impl Magic {
 
     pub fn endian(&self) -> Endian {
         match self {
-            Magic::Magic64 | Magic::Magic | Magic::FatMagic => Endian::NATIVE,
-            Magic::Cigam64 | Magic::Cigam | Magic::FatCigam => Endian::REVERSE,
+            Magic::Magic64 | Magic::Magic | Magic::FatMagic | Magic::FatMagic64 => Endian::NATIVE,
+            Magic::Cigam64 | Magic::Cigam | Magic::FatCigam | Magic::FatCigam64 => Endian::REVERSE,
         }
     }
 }
 
+/// A pair of cpu type and cpu subtype.
+///
+/// The lower 24 bits of the cpu subtype are decoded into the subtype enum of each cpu type, and
+/// the higher 8 bits into `CpuCapabilities`. Any pair which is not known is kept as `Unknown`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CpuType {
-    X86(CpuSubTypeX86),
-    X86_64(CpuSubTypeX86_64),
+    X86(CpuSubTypeX86, CpuCapabilities),
+    X86_64(CpuSubTypeX86_64, CpuCapabilities),
+    Arm(CpuSubTypeArm, CpuCapabilities),
+    Arm64(CpuSubTypeArm64, CpuCapabilities),
+    /// ARM64 with 32-bit pointers, used on watchOS.
+    Arm64_32(CpuSubTypeArm64_32, CpuCapabilities),
+    PowerPC(CpuSubTypePowerPC, CpuCapabilities),
+    PowerPC64(CpuSubTypePowerPC, CpuCapabilities),
+    /// cpu type and cpu subtype as is.
+    Unknown(i32, i32),
 }
 
 #[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CpuSubTypeX86 {
     All = 0x3,
+    I486 = 0x4,
+    I486Sx = 0x84,
+    Pentium = 0x5,
+    PentiumPro = 0x16,
+    PentiumIIM3 = 0x36,
+    PentiumIIM5 = 0x56,
+    Pentium4 = 0xa,
 }
 
 #[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CpuSubTypeX86_64 {
     All = 0x3,
+    Haswell = 0x8,
+}
+
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuSubTypeArm {
+    All = 0,
+    V4T = 5,
+    V6 = 6,
+    V5Tej = 7,
+    XScale = 8,
+    V7 = 9,
+    V7F = 10,
+    V7S = 11,
+    V7K = 12,
+    V8 = 13,
+    V6M = 14,
+    V7M = 15,
+    V7Em = 16,
+}
+
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuSubTypeArm64 {
+    All = 0,
+    V8 = 1,
+    /// ARM64E, which has pointer authentication. See `CpuCapabilities::ptrauth_abi`.
+    E = 2,
+}
+
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuSubTypeArm64_32 {
+    All = 0,
+    V8 = 1,
+}
+
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuSubTypePowerPC {
+    All = 0,
+    P601 = 1,
+    P602 = 2,
+    P603 = 3,
+    P603e = 4,
+    P603ev = 5,
+    P604 = 6,
+    P604e = 7,
+    P620 = 8,
+    P750 = 9,
+    P7400 = 10,
+    P7450 = 11,
+    P970 = 100,
+}
+
+/// The higher 8 bits of a cpu subtype, which represent features of the cpu rather than a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CpuCapabilities(u8);
+
+impl CpuCapabilities {
+    pub const MASK: u32 = 0xff000000;
+
+    /// 64-bit libraries (`CPU_SUBTYPE_LIB64`). Set by the linker on 64-bit executables.
+    pub const LIB64: u8 = 0x80;
+    /// ARM64E pointer authentication ABI is versioned (`CPU_SUBTYPE_PTRAUTH_ABI`).
+    pub const PTRAUTH_ABI: u8 = 0x80;
+    const PTRAUTH_VERSION_MASK: u8 = 0x0f;
+
+    pub const NONE: CpuCapabilities = CpuCapabilities(0);
+
+    pub fn from_bits(bits: u8) -> Self {
+        CpuCapabilities(bits)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Extracts the capability bits from a raw cpu subtype.
+    pub fn from_subtype(cpu_subtype_n: i32) -> Self {
+        CpuCapabilities(((cpu_subtype_n as u32 & Self::MASK) >> 24) as u8)
+    }
+
+    pub fn lib64(self) -> bool {
+        self.0 & Self::LIB64 != 0
+    }
+
+    /// Only meaningful for `CpuSubTypeArm64::E`.
+    pub fn ptrauth_abi(self) -> bool {
+        self.0 & Self::PTRAUTH_ABI != 0
+    }
+
+    /// Version of the pointer authentication ABI of ARM64E.
+    pub fn ptrauth_version(self) -> u8 {
+        self.0 & Self::PTRAUTH_VERSION_MASK
+    }
 }
 
 impl CpuType {
     const CPU_ARCH_ABI64: i32 = 0x01000000;
+    const CPU_ARCH_ABI64_32: i32 = 0x02000000;
     const CPU_TYPE_X86: i32 = 0x7;
     const CPU_TYPE_X86_64: i32 = Self::CPU_TYPE_X86 | Self::CPU_ARCH_ABI64;
+    const CPU_TYPE_ARM: i32 = 0xc;
+    const CPU_TYPE_ARM64: i32 = Self::CPU_TYPE_ARM | Self::CPU_ARCH_ABI64;
+    const CPU_TYPE_ARM64_32: i32 = Self::CPU_TYPE_ARM | Self::CPU_ARCH_ABI64_32;
+    const CPU_TYPE_POWERPC: i32 = 0x12;
+    const CPU_TYPE_POWERPC64: i32 = Self::CPU_TYPE_POWERPC | Self::CPU_ARCH_ABI64;
 
+    /// Returns `Unknown` if the cpu type or the cpu subtype is unknown.
     pub fn from_i32_i32(cpu_type_n: i32, cpu_subtype_n: i32) -> Self {
-        // x86
-        if cpu_type_n == Self::CPU_TYPE_X86 {
-            let cpu_subtype = CpuSubTypeX86::from_i32(cpu_subtype_n).unwrap();
-            CpuType::X86(cpu_subtype)
-        // x86_64
-        } else if cpu_type_n == Self::CPU_TYPE_X86_64 {
-            let cpu_subtype = CpuSubTypeX86_64::from_i32(cpu_subtype_n).unwrap();
-            CpuType::X86_64(cpu_subtype)
-        } else {
-            panic!("Unsupported cpu type {}", cpu_type_n)
-        }
+        let caps = CpuCapabilities::from_subtype(cpu_subtype_n);
+        let sub = cpu_subtype_n & !(CpuCapabilities::MASK as i32);
+
+        let cpu_type = match cpu_type_n {
+            Self::CPU_TYPE_X86 => CpuSubTypeX86::from_i32(sub).map(|sub| CpuType::X86(sub, caps)),
+            Self::CPU_TYPE_X86_64 => {
+                CpuSubTypeX86_64::from_i32(sub).map(|sub| CpuType::X86_64(sub, caps))
+            }
+            Self::CPU_TYPE_ARM => CpuSubTypeArm::from_i32(sub).map(|sub| CpuType::Arm(sub, caps)),
+            Self::CPU_TYPE_ARM64 => {
+                CpuSubTypeArm64::from_i32(sub).map(|sub| CpuType::Arm64(sub, caps))
+            }
+            Self::CPU_TYPE_ARM64_32 => {
+                CpuSubTypeArm64_32::from_i32(sub).map(|sub| CpuType::Arm64_32(sub, caps))
+            }
+            Self::CPU_TYPE_POWERPC => {
+                CpuSubTypePowerPC::from_i32(sub).map(|sub| CpuType::PowerPC(sub, caps))
+            }
+            Self::CPU_TYPE_POWERPC64 => {
+                CpuSubTypePowerPC::from_i32(sub).map(|sub| CpuType::PowerPC64(sub, caps))
+            }
+            _ => None,
+        };
+
+        cpu_type.unwrap_or(CpuType::Unknown(cpu_type_n, cpu_subtype_n))
+    }
+
+    /// Reads a pair of cpu type and cpu subtype.
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let cpu_type_n = read.read_i32_in(endian)?;
+        let cpu_subtype_n = read.read_i32_in(endian)?;
+        Ok(CpuType::from_i32_i32(cpu_type_n, cpu_subtype_n))
     }
 
     pub fn to_i32_i32(&self) -> (i32, i32) {
+        let (cpu_type_n, sub, caps) = match *self {
+            CpuType::X86(sub, caps) => (CpuType::CPU_TYPE_X86, sub as i32, caps),
+            CpuType::X86_64(sub, caps) => (CpuType::CPU_TYPE_X86_64, sub as i32, caps),
+            CpuType::Arm(sub, caps) => (CpuType::CPU_TYPE_ARM, sub as i32, caps),
+            CpuType::Arm64(sub, caps) => (CpuType::CPU_TYPE_ARM64, sub as i32, caps),
+            CpuType::Arm64_32(sub, caps) => (CpuType::CPU_TYPE_ARM64_32, sub as i32, caps),
+            CpuType::PowerPC(sub, caps) => (CpuType::CPU_TYPE_POWERPC, sub as i32, caps),
+            CpuType::PowerPC64(sub, caps) => (CpuType::CPU_TYPE_POWERPC64, sub as i32, caps),
+            CpuType::Unknown(cpu_type_n, cpu_subtype_n) => return (cpu_type_n, cpu_subtype_n),
+        };
+        (cpu_type_n, sub | ((caps.bits() as u32) << 24) as i32)
+    }
+
+    /// Returns the cpu type with `CpuCapabilities::NONE`, which is used to pick a slice of a
+    /// fat file regardless of capability bits.
+    pub fn without_capabilities(self) -> Self {
         match self {
-            CpuType::X86(sub) => (CpuType::CPU_TYPE_X86, *sub as i32),
-            CpuType::X86_64(sub) => (CpuType::CPU_TYPE_X86_64, *sub as i32),
+            CpuType::X86(sub, _) => CpuType::X86(sub, CpuCapabilities::NONE),
+            CpuType::X86_64(sub, _) => CpuType::X86_64(sub, CpuCapabilities::NONE),
+            CpuType::Arm(sub, _) => CpuType::Arm(sub, CpuCapabilities::NONE),
+            CpuType::Arm64(sub, _) => CpuType::Arm64(sub, CpuCapabilities::NONE),
+            CpuType::Arm64_32(sub, _) => CpuType::Arm64_32(sub, CpuCapabilities::NONE),
+            CpuType::PowerPC(sub, _) => CpuType::PowerPC(sub, CpuCapabilities::NONE),
+            CpuType::PowerPC64(sub, _) => CpuType::PowerPC64(sub, CpuCapabilities::NONE),
+            CpuType::Unknown(cpu_type_n, cpu_subtype_n) => {
+                CpuType::Unknown(cpu_type_n, cpu_subtype_n & !(CpuCapabilities::MASK as i32))
+            }
         }
     }
 }
 
-#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
 /// Declared in /usr/include/mach-o/loader.h
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FileType {
-    Object = 0x1,
-    Execute = 0x2,
-    FVMLib = 0x3,
-    Core = 0x4,
-    Preload = 0x5,
-    Dylib = 0x6,
-    Dylinker = 0x7,
-    Bundle = 0x8,
-    Dsym = 0xA,
+    Object,
+    Execute,
+    FVMLib,
+    Core,
+    Preload,
+    Dylib,
+    Dylinker,
+    Bundle,
+    DylibStub,
+    Dsym,
+    KextBundle,
+    Fileset,
+    /// Keeps the value as is.
+    Unknown(u32),
 }
 
 impl FileType {
     pub fn from_u32(n: u32) -> Self {
-        FromPrimitive::from_u32(n).unwrap()
+        match n {
+            0x1 => FileType::Object,
+            0x2 => FileType::Execute,
+            0x3 => FileType::FVMLib,
+            0x4 => FileType::Core,
+            0x5 => FileType::Preload,
+            0x6 => FileType::Dylib,
+            0x7 => FileType::Dylinker,
+            0x8 => FileType::Bundle,
+            0x9 => FileType::DylibStub,
+            0xA => FileType::Dsym,
+            0xB => FileType::KextBundle,
+            0xC => FileType::Fileset,
+            n => FileType::Unknown(n),
+        }
     }
 
     pub fn to_u32(self) -> u32 {
-        self as u32
+        match self {
+            FileType::Object => 0x1,
+            FileType::Execute => 0x2,
+            FileType::FVMLib => 0x3,
+            FileType::Core => 0x4,
+            FileType::Preload => 0x5,
+            FileType::Dylib => 0x6,
+            FileType::Dylinker => 0x7,
+            FileType::Bundle => 0x8,
+            FileType::DylibStub => 0x9,
+            FileType::Dsym => 0xA,
+            FileType::KextBundle => 0xB,
+            FileType::Fileset => 0xC,
+            FileType::Unknown(n) => n,
+        }
     }
 }
 
 #[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
 #[rustfmt::skip]
 pub enum Flag {
-    NoUndefs                = 0x000001,
-    IncrLink                = 0x000002,
-    DyldLink                = 0x000004,
-    BindAtLoad              = 0x000008,
-    PreBound                = 0x000010,
-    SplitSegs               = 0x000020,
-    TwoLevel                = 0x000080,
-    ForceFlat               = 0x000100,
-    NoMultiDefs             = 0x000200,
-    NoFixPreBinding         = 0x000400,
-    PreBindable             = 0x000800,
-    AllModsBound            = 0x001000,
-    SubsectionsViaSymbols   = 0x002000,
-    Canonical               = 0x004000,
-    Pie                     = 0x200000,
-    HasTlvDescriptors       = 0x800000,
+    NoUndefs                    = 0x00000001,
+    IncrLink                    = 0x00000002,
+    DyldLink                    = 0x00000004,
+    BindAtLoad                  = 0x00000008,
+    PreBound                    = 0x00000010,
+    SplitSegs                   = 0x00000020,
+    LazyInit                    = 0x00000040,
+    TwoLevel                    = 0x00000080,
+    ForceFlat                   = 0x00000100,
+    NoMultiDefs                 = 0x00000200,
+    NoFixPreBinding             = 0x00000400,
+    PreBindable                 = 0x00000800,
+    AllModsBound                = 0x00001000,
+    SubsectionsViaSymbols       = 0x00002000,
+    Canonical                   = 0x00004000,
+    WeakDefines                 = 0x00008000,
+    BindsToWeak                 = 0x00010000,
+    AllowStackExecution         = 0x00020000,
+    RootSafe                    = 0x00040000,
+    SetuidSafe                  = 0x00080000,
+    NoReexportedDylibs          = 0x00100000,
+    Pie                         = 0x00200000,
+    DeadStrippableDylib         = 0x00400000,
+    HasTlvDescriptors           = 0x00800000,
+    NoHeapExecution             = 0x01000000,
+    AppExtensionSafe            = 0x02000000,
+    NlistOutofsyncWithDyldinfo  = 0x04000000,
+    SimSupport                  = 0x08000000,
+    DylibInCache                = 0x80000000,
 }
 
 impl Flag {
-    pub fn from_u32(n: u32) -> Self {
-        FromPrimitive::from_u32(n).unwrap()
+    pub fn from_u32(n: u32) -> Option<Self> {
+        FromPrimitive::from_u32(n)
     }
 
     pub fn to_u32(self) -> u32 {
@@ -256,14 +566,19 @@ impl Flag {
     }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Default)]
 pub struct Flags {
     flags: Vec<Flag>,
+    /// Bits which do not correspond to any `Flag`.
+    unknown: u32,
 }
 
 impl Flags {
     pub fn new() -> Flags {
-        Flags { flags: Vec::new() }
+        Flags {
+            flags: Vec::new(),
+            unknown: 0,
+        }
     }
 
     pub fn push(&mut self, flag: Flag) {
@@ -271,20 +586,28 @@ impl Flags {
     }
 
     pub fn is_empty(&self) -> bool {
-        self.flags.is_empty()
+        self.flags.is_empty() && self.unknown == 0
     }
 
-    pub fn iter<'a>(&'a self) -> impl Iterator<Item = Flag> + 'a {
+    pub fn iter(&self) -> impl Iterator<Item = Flag> + '_ {
         self.flags.iter().copied()
     }
 
+    /// Bits which do not correspond to any `Flag`. They are written back as is.
+    pub fn unknown(&self) -> u32 {
+        self.unknown
+    }
+
+    /// Unknown bits are kept and can be retrieved by `unknown`.
     pub fn from_u32(flags_n: u32) -> Self {
         let mut flags = Flags::new();
         for i in 0..=31 {
             let flag_n = flags_n & (1 << i);
             if flag_n != 0 {
-                let flag = Flag::from_u32(flag_n);
-                flags.push(flag);
+                match Flag::from_u32(flag_n) {
+                    Some(flag) => flags.push(flag),
+                    None => flags.unknown |= flag_n,
+                }
             }
         }
 
@@ -292,7 +615,7 @@ impl Flags {
     }
 
     pub fn to_u32(&self) -> u32 {
-        let mut flag_n = 0u32;
+        let mut flag_n = self.unknown;
 
         for flag in self.flags.iter() {
             flag_n |= flag.to_u32();
@@ -304,7 +627,12 @@ impl Flags {
 
 impl fmt::Debug for Flags {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.debug_set().entries(self.flags.iter()).finish()
+        let mut set = fmt.debug_set();
+        set.entries(self.flags.iter());
+        if self.unknown != 0 {
+            set.entry(&format_args!("{:#x}", self.unknown));
+        }
+        set.finish()
     }
 }
 
@@ -329,27 +657,43 @@ pub struct FatArch {
     pub align: u32,
 }
 
+impl FatArch {
+    pub const SIZE: u32 = 0x14; // 20 bytes
+}
+
 impl FatHeader {
-    pub fn read_from<R: Read>(read: &mut R) -> Self {
-        let magic_n = read.read_u32::<NativeEndian>().unwrap();
-        let magic = Magic::from_u32(magic_n);
+    /// Size of `magic` and the number of `fat_archs`.
+    pub const SIZE: u32 = 0x8;
+
+    /// Creates a header whose magic is written as `FAT_MAGIC` in big endian, as every fat file
+    /// is, regardless of the host byte order.
+    pub fn new(fat_archs: Vec<FatArch>) -> Self {
+        let magic = match Endian::NATIVE {
+            Endian::Big => Magic::FatMagic,
+            Endian::Little => Magic::FatCigam,
+        };
+        FatHeader { magic, fat_archs }
+    }
+
+    pub fn read_from<R: Read + Seek>(read: &mut R) -> Result<Self> {
+        let magic = Magic::read_from(read)?;
         Self::read_after_magic(read, magic)
     }
 
-    fn read_after_magic<R: Read>(read: &mut R, magic: Magic) -> Self {
-        assert!(magic == Magic::FatMagic || magic == Magic::FatCigam);
+    fn read_after_magic<R: Read + Seek>(read: &mut R, magic: Magic) -> Result<Self> {
+        if magic != Magic::FatMagic && magic != Magic::FatCigam {
+            return Err(read.bad_magic(magic.to_u32()));
+        }
 
-        let n_fat_archs = read.read_u32::<BigEndian>().unwrap();
+        let n_fat_archs = read.read_u32_in(Endian::Big)?;
 
-        let mut fat_archs = Vec::with_capacity(n_fat_archs as usize);
+        let mut fat_archs = Vec::new();
 
         for _ in 0..n_fat_archs {
-            let cpu_type_n = read.read_i32::<BigEndian>().unwrap();
-            let cpu_subtype_n = read.read_i32::<BigEndian>().unwrap();
-            let cpu_type = CpuType::from_i32_i32(cpu_type_n, cpu_subtype_n);
-            let offset = read.read_u32::<BigEndian>().unwrap();
-            let size = read.read_u32::<BigEndian>().unwrap();
-            let align = read.read_u32::<BigEndian>().unwrap();
+            let cpu_type = CpuType::read_from_in(read, Endian::Big)?;
+            let offset = read.read_u32_in(Endian::Big)?;
+            let size = read.read_u32_in(Endian::Big)?;
+            let align = read.read_u32_in(Endian::Big)?;
             fat_archs.push(FatArch {
                 cpu_type,
                 offset,
@@ -358,35 +702,141 @@ impl FatHeader {
             });
         }
 
-        FatHeader { magic, fat_archs }
+        Ok(FatHeader { magic, fat_archs })
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
-        write.write_u32::<BigEndian>(self.magic.to_u32()).unwrap();
-        write
-            .write_u32::<BigEndian>(self.fat_archs.len() as u32)
-            .unwrap();
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        // `Magic` is relative to the host byte order, so writing it natively always produces
+        // the bytes it was read from.
+        write.write_u32_in(self.magic.to_u32(), Endian::NATIVE)?;
+        write.write_u32_in(self.fat_archs.len() as u32, Endian::Big)?;
 
         for fat_arch in self.fat_archs.iter() {
             let (cpu_type_n, cpu_subtype_n) = fat_arch.cpu_type.to_i32_i32();
-            write.write_i32::<BigEndian>(cpu_type_n).unwrap();
-            write.write_i32::<BigEndian>(cpu_subtype_n).unwrap();
-            write.write_u32::<BigEndian>(fat_arch.offset).unwrap();
-            write.write_u32::<BigEndian>(fat_arch.size).unwrap();
-            write.write_u32::<BigEndian>(fat_arch.align).unwrap();
+            write.write_i32_in(cpu_type_n, Endian::Big)?;
+            write.write_i32_in(cpu_subtype_n, Endian::Big)?;
+            write.write_u32_in(fat_arch.offset, Endian::Big)?;
+            write.write_u32_in(fat_arch.size, Endian::Big)?;
+            write.write_u32_in(fat_arch.align, Endian::Big)?;
         }
+
+        Ok(())
+    }
+}
+
+/// The header of a fat file whose slices are described by `FatArch64`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FatHeader64 {
+    pub magic: Magic,
+    pub fat_archs: Vec<FatArch64>,
+}
+
+/// Same as `FatArch` except that `offset` and `size` are 64-bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FatArch64 {
+    /// Specifies the CPU family.
+    pub cpu_type: CpuType,
+    /// Offset to the beginning of the data for this CPU.
+    pub offset: u64,
+    /// Size of the data for this CPU.
+    pub size: u64,
+    /// See `FatArch::align`.
+    pub align: u32,
+    pub reserved: u32,
+}
+
+impl FatArch64 {
+    pub const SIZE: u32 = 0x20; // 32 bytes
+}
+
+impl From<FatArch> for FatArch64 {
+    fn from(arch: FatArch) -> Self {
+        FatArch64 {
+            cpu_type: arch.cpu_type,
+            offset: arch.offset.into(),
+            size: arch.size.into(),
+            align: arch.align,
+            reserved: 0,
+        }
+    }
+}
+
+impl FatHeader64 {
+    /// Size of `magic` and the number of `fat_archs`.
+    pub const SIZE: u32 = 0x8;
+
+    /// Creates a header whose magic is written as `FAT_MAGIC_64` in big endian.
+    /// See `FatHeader::new`.
+    pub fn new(fat_archs: Vec<FatArch64>) -> Self {
+        let magic = match Endian::NATIVE {
+            Endian::Big => Magic::FatMagic64,
+            Endian::Little => Magic::FatCigam64,
+        };
+        FatHeader64 { magic, fat_archs }
+    }
+
+    pub fn read_from<R: Read + Seek>(read: &mut R) -> Result<Self> {
+        let magic = Magic::read_from(read)?;
+        Self::read_after_magic(read, magic)
+    }
+
+    fn read_after_magic<R: Read + Seek>(read: &mut R, magic: Magic) -> Result<Self> {
+        if magic != Magic::FatMagic64 && magic != Magic::FatCigam64 {
+            return Err(read.bad_magic(magic.to_u32()));
+        }
+
+        let n_fat_archs = read.read_u32_in(Endian::Big)?;
+
+        let mut fat_archs = Vec::new();
+
+        for _ in 0..n_fat_archs {
+            let cpu_type = CpuType::read_from_in(read, Endian::Big)?;
+            let offset = read.read_u64_in(Endian::Big)?;
+            let size = read.read_u64_in(Endian::Big)?;
+            let align = read.read_u32_in(Endian::Big)?;
+            let reserved = read.read_u32_in(Endian::Big)?;
+            fat_archs.push(FatArch64 {
+                cpu_type,
+                offset,
+                size,
+                align,
+                reserved,
+            });
+        }
+
+        Ok(FatHeader64 { magic, fat_archs })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        // See `FatHeader::write_into`.
+        write.write_u32_in(self.magic.to_u32(), Endian::NATIVE)?;
+        write.write_u32_in(self.fat_archs.len() as u32, Endian::Big)?;
+
+        for fat_arch in self.fat_archs.iter() {
+            let (cpu_type_n, cpu_subtype_n) = fat_arch.cpu_type.to_i32_i32();
+            write.write_i32_in(cpu_type_n, Endian::Big)?;
+            write.write_i32_in(cpu_subtype_n, Endian::Big)?;
+            write.write_u64_in(fat_arch.offset, Endian::Big)?;
+            write.write_u64_in(fat_arch.size, Endian::Big)?;
+            write.write_u32_in(fat_arch.align, Endian::Big)?;
+            write.write_u32_in(fat_arch.reserved, Endian::Big)?;
+        }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::Error;
+    use std::io::Cursor;
 
     #[test]
     fn write_and_read_header64() {
         let header = Header64 {
             magic: Magic::Magic64,
-            cpu_type: CpuType::X86_64(CpuSubTypeX86_64::All),
+            cpu_type: CpuType::X86_64(CpuSubTypeX86_64::All, CpuCapabilities::NONE),
             file_type: FileType::Object,
             n_cmds: 2,
             size_of_cmds: 42,
@@ -396,11 +846,172 @@ mod tests {
 
         let mut buf = Vec::new();
 
-        header.write_into(&mut buf);
+        header.write_into(&mut buf).unwrap();
 
         assert_eq!(buf.len(), Header64::SIZE as usize);
 
-        let read = Header64::read_from(&mut buf.as_slice());
+        let read = Header64::read_from(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(read, header);
+    }
+
+    #[test]
+    fn write_and_read_header32() {
+        let header = Header32 {
+            magic: Magic::Magic,
+            cpu_type: CpuType::X86(CpuSubTypeX86::All, CpuCapabilities::NONE),
+            file_type: FileType::Execute,
+            n_cmds: 3,
+            size_of_cmds: 168,
+            flags: Flags::new(),
+        };
+
+        let mut buf = Vec::new();
+
+        header.write_into(&mut buf).unwrap();
+
+        assert_eq!(buf.len(), Header32::SIZE as usize);
+
+        let read = Header::read_from(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(read, Header::Mach32(header));
+    }
+
+    #[test]
+    fn write_and_read_header64_reverse_endian() {
+        let header = Header64 {
+            magic: Magic::Cigam64,
+            cpu_type: CpuType::X86_64(CpuSubTypeX86_64::All, CpuCapabilities::NONE),
+            file_type: FileType::Execute,
+            n_cmds: 5,
+            size_of_cmds: 0x200,
+            flags: Flags::new(),
+            reserved: 0,
+        };
+
+        let mut buf = Vec::new();
+        header.write_into(&mut buf).unwrap();
+
+        let mut reversed = Vec::new();
+        Header64 {
+            magic: Magic::Magic64,
+            ..header.clone()
+        }
+        .write_into_in(&mut reversed, Endian::REVERSE)
+        .unwrap();
+        assert_eq!(buf, reversed);
+
+        let read = Header64::read_from(&mut Cursor::new(buf)).unwrap();
         assert_eq!(read, header);
     }
+
+    #[test]
+    fn read_truncated_header64() {
+        let mut buf = Vec::new();
+        Header64 {
+            magic: Magic::Magic64,
+            cpu_type: CpuType::X86_64(CpuSubTypeX86_64::All, CpuCapabilities::NONE),
+            file_type: FileType::Object,
+            n_cmds: 0,
+            size_of_cmds: 0,
+            flags: Flags::new(),
+            reserved: 0,
+        }
+        .write_into(&mut buf)
+        .unwrap();
+        buf.truncate(18);
+
+        let err = Header64::read_from(&mut Cursor::new(buf)).unwrap_err();
+        assert!(matches!(err, Error::Truncated { offset: 16 }));
+    }
+
+    #[test]
+    fn read_bad_magic() {
+        let buf = 0xdeadbeef_u32.to_ne_bytes().to_vec();
+
+        let err = Header::read_from(&mut Cursor::new(buf)).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::BadMagic {
+                offset: 0,
+                magic: 0xdeadbeef
+            }
+        ));
+    }
+
+    #[test]
+    fn decode_cpu_subtype_capabilities() {
+        // arm64e with ptrauth abi version 0
+        let cpu = CpuType::from_i32_i32(0x0100000c, 0x80000002_u32 as i32);
+        let CpuType::Arm64(CpuSubTypeArm64::E, caps) = cpu else {
+            panic!("unexpected {:?}", cpu);
+        };
+        assert!(caps.ptrauth_abi());
+        assert_eq!(caps.ptrauth_version(), 0);
+        assert_eq!(cpu.to_i32_i32(), (0x0100000c, 0x80000002_u32 as i32));
+
+        // x86_64 executable with CPU_SUBTYPE_LIB64
+        let cpu = CpuType::from_i32_i32(0x01000007, 0x80000003_u32 as i32);
+        assert!(matches!(cpu, CpuType::X86_64(CpuSubTypeX86_64::All, caps) if caps.lib64()));
+        assert_eq!(cpu.to_i32_i32(), (0x01000007, 0x80000003_u32 as i32));
+
+        let cpu = CpuType::from_i32_i32(0x12, 100);
+        assert_eq!(
+            cpu,
+            CpuType::PowerPC(CpuSubTypePowerPC::P970, CpuCapabilities::NONE)
+        );
+    }
+
+    #[test]
+    fn keep_unknown_cpu_type() {
+        let cpu = CpuType::from_i32_i32(0x0100000c, 0x7f);
+        assert_eq!(cpu, CpuType::Unknown(0x0100000c, 0x7f));
+        assert_eq!(cpu.to_i32_i32(), (0x0100000c, 0x7f));
+
+        let cpu = CpuType::from_i32_i32(0x99, 0x80000001_u32 as i32);
+        assert_eq!(cpu.to_i32_i32(), (0x99, 0x80000001_u32 as i32));
+    }
+
+    #[test]
+    fn keep_unknown_file_type_and_flags() {
+        let mut buf = Vec::new();
+        Header64 {
+            magic: Magic::Magic64,
+            cpu_type: CpuType::X86_64(CpuSubTypeX86_64::All, CpuCapabilities::NONE),
+            file_type: FileType::Unknown(0x42),
+            n_cmds: 0,
+            size_of_cmds: 0,
+            flags: Flags::from_u32(0x40000001),
+            reserved: 0,
+        }
+        .write_into(&mut buf)
+        .unwrap();
+
+        let read = Header64::read_from(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(read.file_type, FileType::Unknown(0x42));
+        assert_eq!(read.flags.iter().collect::<Vec<_>>(), [Flag::NoUndefs]);
+        assert_eq!(read.flags.unknown(), 0x40000000);
+
+        let mut written = Vec::new();
+        read.write_into(&mut written).unwrap();
+        assert_eq!(written, buf);
+    }
+
+    #[test]
+    fn write_and_read_fat_header64() {
+        let header = FatHeader64::new(vec![FatArch64 {
+            cpu_type: CpuType::Arm64(CpuSubTypeArm64::All, CpuCapabilities::NONE),
+            offset: 0x1_0000_4000,
+            size: 0x42,
+            align: 14,
+            reserved: 0,
+        }]);
+
+        let mut buf = Vec::new();
+        header.write_into(&mut buf).unwrap();
+
+        assert_eq!(buf.len(), (FatHeader64::SIZE + FatArch64::SIZE) as usize);
+        assert_eq!(buf[..4], [0xca, 0xfe, 0xba, 0xbf]);
+
+        let read = Header::read_from(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(read, Header::Fat64(header));
+    }
 }
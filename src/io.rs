@@ -1,6 +1,8 @@
-use byteorder::{BigEndian, LittleEndian, NativeEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{Read, Write};
+use crate::error::{Error, Result};
+use byteorder::WriteBytesExt;
+use std::io::{ErrorKind, Read, Seek, Write};
 
+/// Byte order of a Mach-O file, which is given to `*_in` readers and writers.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Endian {
     Little,
@@ -20,79 +22,158 @@ impl Endian {
 }
 
 macro_rules! read_in {
-    ($func:ident, $endian:expr) => {
-        match $endian {
-            Endian::Little => ReadBytesExt::$func::<LittleEndian>,
-            Endian::Big => ReadBytesExt::$func::<BigEndian>,
-        }
-    };
+    ($read:expr, $ty:ty, $endian:expr) => {{
+        let bytes = $read.read_byte_array::<{ std::mem::size_of::<$ty>() }>()?;
+        Ok(match $endian {
+            Endian::Little => <$ty>::from_le_bytes(bytes),
+            Endian::Big => <$ty>::from_be_bytes(bytes),
+        })
+    }};
+}
+
+macro_rules! write_in {
+    ($write:expr, $n:expr, $endian:expr) => {{
+        let bytes = match $endian {
+            Endian::Little => $n.to_le_bytes(),
+            Endian::Big => $n.to_be_bytes(),
+        };
+        Ok($write.write_all(&bytes)?)
+    }};
 }
 
-pub trait ReadExt: Read + ReadBytesExt {
-    fn read_u8(&mut self) -> u8 {
-        ReadBytesExt::read_u8(self).unwrap()
+pub trait ReadExt: Read + Seek + Sized {
+    /// Returns the current offset in the underlying stream.
+    fn position(&mut self) -> Result<u64> {
+        Ok(self.stream_position()?)
     }
 
-    fn read_u16_in(&mut self, endian: Endian) -> u16 {
-        read_in!(read_u16, endian)(self).unwrap()
+    /// Creates `Error::UnknownValue` for the `size` bytes value which was just read.
+    fn unknown_value(&mut self, size: u64, kind: &'static str, value: u64) -> Error {
+        match self.position() {
+            Ok(pos) => Error::UnknownValue {
+                offset: pos - size,
+                kind,
+                value,
+            },
+            Err(err) => err,
+        }
     }
 
-    fn read_i32_in(&mut self, endian: Endian) -> i32 {
-        read_in!(read_i32, endian)(self).unwrap()
+    /// Creates `Error::BadMagic` for the magic number which was just read.
+    fn bad_magic(&mut self, magic: u32) -> Error {
+        match self.position() {
+            Ok(pos) => Error::BadMagic {
+                offset: pos - 4,
+                magic,
+            },
+            Err(err) => err,
+        }
     }
 
-    fn read_u32_in(&mut self, endian: Endian) -> u32 {
-        read_in!(read_u32, endian)(self).unwrap()
+    /// Reads exactly `N` bytes.
+    ///
+    /// Unlike `Read::read_exact`, the position of the stream is well defined on failure, so
+    /// that `Error::Truncated` can point to the start of the value.
+    fn read_byte_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        let mut n = 0;
+        while n < N {
+            match self.read(&mut buf[n..]) {
+                Ok(0) => {
+                    let pos = self.position()?;
+                    return Err(Error::Truncated {
+                        offset: pos - n as u64,
+                    });
+                }
+                Ok(k) => n += k,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(buf)
     }
 
-    fn read_u64_in(&mut self, endian: Endian) -> u64 {
-        read_in!(read_u64, endian)(self).unwrap()
+    /// Reads exactly `len` bytes.
+    ///
+    /// The buffer grows as data arrives, so a corrupted length does not cause a huge
+    /// allocation up front.
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        let offset = self.position()?;
+        let mut buf = Vec::new();
+        self.by_ref().take(len as u64).read_to_end(&mut buf)?;
+        if buf.len() < len {
+            return Err(Error::Truncated { offset });
+        }
+        Ok(buf)
     }
 
-    fn read_fixed_size_string(&mut self, size: usize) -> String {
-        let mut buf = vec![0u8; size];
-        self.read_exact(&mut buf).unwrap();
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_byte_array::<1>()?[0])
+    }
+
+    fn read_u16_in(&mut self, endian: Endian) -> Result<u16> {
+        read_in!(self, u16, endian)
+    }
+
+    fn read_i32_in(&mut self, endian: Endian) -> Result<i32> {
+        read_in!(self, i32, endian)
+    }
+
+    fn read_u32_in(&mut self, endian: Endian) -> Result<u32> {
+        read_in!(self, u32, endian)
+    }
+
+    fn read_u64_in(&mut self, endian: Endian) -> Result<u64> {
+        read_in!(self, u64, endian)
+    }
+
+    fn read_fixed_size_string(&mut self, size: usize) -> Result<String> {
+        let offset = self.position()?;
+        let mut buf = self.read_bytes(size)?;
 
         let valid_len = buf.split(|&b| b == 0).next().unwrap().len();
         buf.truncate(valid_len);
-        String::from_utf8(buf).unwrap()
+        String::from_utf8(buf).map_err(|_| Error::InvalidString { offset })
     }
 }
 
-impl<T> ReadExt for T where T: Read {}
+impl<T> ReadExt for T where T: Read + Seek {}
 
 pub trait WriteExt: Write + WriteBytesExt {
-    fn write_u8(&mut self, n: u8) {
-        WriteBytesExt::write_u8(self, n).unwrap()
+    fn write_u8(&mut self, n: u8) -> Result<()> {
+        Ok(WriteBytesExt::write_u8(self, n)?)
     }
 
-    fn write_u16_native(&mut self, n: u16) {
-        self.write_u16::<NativeEndian>(n).unwrap()
+    fn write_u16_in(&mut self, n: u16, endian: Endian) -> Result<()> {
+        write_in!(self, n, endian)
     }
 
-    fn write_i32_native(&mut self, n: i32) {
-        self.write_i32::<NativeEndian>(n).unwrap()
+    fn write_i32_in(&mut self, n: i32, endian: Endian) -> Result<()> {
+        write_in!(self, n, endian)
     }
 
-    fn write_u32_native(&mut self, n: u32) {
-        self.write_u32::<NativeEndian>(n).unwrap()
+    fn write_u32_in(&mut self, n: u32, endian: Endian) -> Result<()> {
+        write_in!(self, n, endian)
     }
 
-    fn write_u64_native(&mut self, n: u64) {
-        self.write_u64::<NativeEndian>(n).unwrap()
+    fn write_u64_in(&mut self, n: u64, endian: Endian) -> Result<()> {
+        write_in!(self, n, endian)
     }
 
-    fn write_fixed_size_string(&mut self, s: &str, size: usize) {
-        assert!(s.is_ascii());
-        assert!(s.len() <= size);
+    /// Writes `s` padded with zeros to `size` bytes. Any UTF-8 string which fits is accepted, as
+    /// `read_fixed_size_string` does.
+    fn write_fixed_size_string(&mut self, s: &str, size: usize) -> Result<()> {
+        if s.len() > size {
+            return Err(Error::StringTooLong {
+                string: s.to_string(),
+                size,
+            });
+        }
 
         let mut buf = vec![0u8; size];
+        buf[..s.len()].copy_from_slice(s.as_bytes());
 
-        for (i, c) in s.chars().enumerate() {
-            buf[i] = c as u8;
-        }
-
-        self.write_all(&buf).unwrap();
+        Ok(self.write_all(&buf)?)
     }
 }
 
@@ -0,0 +1,559 @@
+use crate::{
+    file::{is_zerofill, MachOFile},
+    header::{CpuType, FileType, Header64},
+    load_command::{
+        BuildToolVersion, BuildVersionCommand, DysymtabCommand, LoadCommand, Section64,
+        SegmentCommand64,
+    },
+    nlist::NList64,
+    reloc::RelocationInfo,
+};
+
+/// Free bytes left after the load commands of linked images, which is the default `-headerpad`
+/// of ld64. It leaves room for load commands added later, such as `LC_CODE_SIGNATURE` by
+/// `MachOFile::sign_adhoc`.
+pub(crate) const HEADER_PAD: u64 = 0x20;
+
+impl MachOFile {
+    /// Assigns every count, size and file offset from the logical contents of this file.
+    ///
+    /// The following fields are computed, so their current values are ignored:
+    ///
+    /// - `Header64::n_cmds` and `size_of_cmds`
+    /// - `cmdsize` of segment and build version commands, and `nsects` / `ntools`
+    /// - `Section64::size` (except for zero-fill sections), `offset`, `addr`, `reloff` and
+    ///   `nreloc`
+    /// - `SegmentCommand64::fileoff`, `filesize` and `vmsize`, and `vmaddr` of every segment
+    ///   but the first
+    /// - `SymtabCommand::symoff`, `nsyms`, `stroff` and `strsize`
+    ///
+    /// Sections are placed in load command order and aligned to `2^align`.
+    /// In an object file, all sections are packed into the file right after the load commands.
+    /// In other files, every segment which has sections is aligned to a page, the first one
+    /// covers the header and load commands followed by `HEADER_PAD` free bytes, and segments
+    /// without sections (such as `__PAGEZERO`) keep their `vmsize` but occupy no file space.
+    /// Zero-fill sections are placed after the other sections of their segment.
+    ///
+    /// Relocations, the symbol table and the string table follow the sections, or fill the
+    /// `__LINKEDIT` segment if there is one.
+    ///
+    /// `extra` is cleared, because its offsets are no longer meaningful. The data which a parsed
+    /// linked image keeps in `extra` is dropped along with the load commands which refer to it:
+    /// every `LinkeditData` (such as `LC_CODE_SIGNATURE`, `LC_FUNCTION_STARTS` and
+    /// `LC_DYLD_CHAINED_FIXUPS`) and `DyldInfo` command is removed, and the tables of the
+    /// `Dysymtab` command (such as the indirect symbol table) are emptied. Its symbol ranges are
+    /// kept.
+    pub fn layout(&mut self) {
+        let page_size = page_size(self.header.cpu_type);
+        let is_object = self.header.file_type == FileType::Object;
+
+        self.extra.clear();
+        self.drop_extra_refs();
+        self.layout_sizes();
+
+        let mut offset = Header64::SIZE as u64 + self.header.size_of_cmds as u64;
+        let mut next_vmaddr = None;
+        let mut linkedit_off = None;
+        let mut is_first_mapped = true;
+        let mut sects_data = self.sections.iter();
+
+        let linkedit_size = self.linkedit_size();
+
+        for lc in self.load_commands.iter_mut() {
+            let (seg, sects) = match lc {
+                LoadCommand::Segment64(seg, sects) => (seg, sects),
+                _ => continue,
+            };
+
+            if let Some(vmaddr) = next_vmaddr {
+                seg.vmaddr = vmaddr;
+            }
+
+            if is_object {
+                let mut vmpos = seg.vmaddr;
+                let mut is_first_in_file = true;
+                seg.fileoff = offset;
+                for sect in sects.iter_mut() {
+                    let align = 1 << sect.align;
+                    vmpos = align_to(vmpos, align);
+                    sect.addr = vmpos;
+                    vmpos += sect.size;
+
+                    if is_zerofill(sect) {
+                        sect.offset = 0;
+                    } else {
+                        offset = align_to(offset, align);
+                        if is_first_in_file {
+                            seg.fileoff = offset;
+                            is_first_in_file = false;
+                        }
+                        sect.offset = offset as u32;
+                        offset += sect.size;
+                    }
+                }
+                seg.filesize = offset - seg.fileoff;
+                seg.vmsize = vmpos - seg.vmaddr;
+            } else if seg.segname == "__LINKEDIT" {
+                seg.fileoff = align_to(offset, page_size);
+                seg.filesize = linkedit_size;
+                seg.vmsize = align_to(linkedit_size, page_size);
+                linkedit_off = Some(seg.fileoff);
+                offset = seg.fileoff + linkedit_size;
+            } else if sects.is_empty() {
+                seg.fileoff = 0;
+                seg.filesize = 0;
+            } else {
+                let mut pos = if is_first_mapped {
+                    seg.fileoff = 0;
+                    offset + HEADER_PAD
+                } else {
+                    seg.fileoff = align_to(offset, page_size);
+                    seg.fileoff
+                };
+                is_first_mapped = false;
+
+                for sect in sects.iter_mut().filter(|sect| !is_zerofill(sect)) {
+                    pos = align_to(pos, 1 << sect.align);
+                    sect.offset = pos as u32;
+                    sect.addr = seg.vmaddr + (pos - seg.fileoff);
+                    pos += sect.size;
+                }
+                seg.filesize = align_to(pos - seg.fileoff, page_size);
+
+                let mut vmpos = seg.vmaddr + (pos - seg.fileoff);
+                for sect in sects.iter_mut().filter(|sect| is_zerofill(sect)) {
+                    vmpos = align_to(vmpos, 1 << sect.align);
+                    sect.offset = 0;
+                    sect.addr = vmpos;
+                    vmpos += sect.size;
+                }
+                seg.vmsize = align_to(vmpos - seg.vmaddr, page_size);
+
+                offset = seg.fileoff + seg.filesize;
+            }
+
+            next_vmaddr = Some(seg.vmaddr + seg.vmsize);
+
+            for sect in sects.iter_mut() {
+                let sect_data = sects_data.next().expect("fewer SectionData than sections");
+                sect.nreloc = sect_data.relocs.len() as u32;
+            }
+        }
+
+        let mut pos = linkedit_off.unwrap_or_else(|| align_to(offset, 8));
+        for lc in self.load_commands.iter_mut() {
+            if let LoadCommand::Segment64(_, sects) = lc {
+                for sect in sects.iter_mut() {
+                    if sect.nreloc == 0 {
+                        sect.reloff = 0;
+                    } else {
+                        sect.reloff = pos as u32;
+                        pos += sect.nreloc as u64 * RelocationInfo::SIZE as u64;
+                    }
+                }
+            }
+        }
+
+        for lc in self.load_commands.iter_mut() {
+            if let LoadCommand::Symtab(symtab) = lc {
+                symtab.symoff = pos as u32;
+                pos += symtab.nsyms as u64 * NList64::SIZE as u64;
+                symtab.stroff = pos as u32;
+            }
+        }
+    }
+
+    /// Removes or empties the load commands which refer to the data in `extra`.
+    fn drop_extra_refs(&mut self) {
+        self.load_commands
+            .retain(|lc| !matches!(lc, LoadCommand::LinkeditData(_) | LoadCommand::DyldInfo(_)));
+        for lc in self.load_commands.iter_mut() {
+            if let LoadCommand::Dysymtab(cmd) = lc {
+                *cmd = DysymtabCommand {
+                    tocoff: 0,
+                    ntoc: 0,
+                    modtaboff: 0,
+                    nmodtab: 0,
+                    extrefsymoff: 0,
+                    nextrefsyms: 0,
+                    indirectsymoff: 0,
+                    nindirectsyms: 0,
+                    extreloff: 0,
+                    nextrel: 0,
+                    locreloff: 0,
+                    nlocrel: 0,
+                    ..*cmd
+                };
+            }
+        }
+    }
+
+    /// Updates every count and size which does not depend on file offsets.
+    fn layout_sizes(&mut self) {
+        let mut sects_data = self.sections.iter();
+        let n_syms = self.symbols.len() as u32;
+        let str_size = self.string_table.len() as u32;
+
+        for lc in self.load_commands.iter_mut() {
+            match lc {
+                LoadCommand::Segment64(seg, sects) => {
+                    seg.nsects = sects.len() as u32;
+                    seg.cmdsize = SegmentCommand64::SIZE + Section64::SIZE * seg.nsects;
+                    for sect in sects.iter_mut() {
+                        let sect_data = sects_data.next().expect("fewer SectionData than sections");
+                        if !is_zerofill(sect) {
+                            sect.size = sect_data.data.len() as u64;
+                        }
+                    }
+                }
+                LoadCommand::BuildVersion(cmd, tools) => {
+                    cmd.ntools = tools.len() as u32;
+                    cmd.cmdsize = BuildVersionCommand::SIZE + BuildToolVersion::SIZE * cmd.ntools;
+                }
+                LoadCommand::Symtab(symtab) => {
+                    symtab.nsyms = n_syms;
+                    symtab.strsize = str_size;
+                }
+                _ => {}
+            }
+        }
+
+        self.header.n_cmds = self.load_commands.len() as u32;
+        self.header.size_of_cmds = self.load_commands.iter().map(|lc| lc.cmd_size()).sum();
+    }
+
+    /// Size of relocations, symbol table and string table in bytes.
+    fn linkedit_size(&self) -> u64 {
+        let n_relocs: usize = self.sections.iter().map(|sect| sect.relocs.len()).sum();
+        n_relocs as u64 * RelocationInfo::SIZE as u64
+            + self.symbols.len() as u64 * NList64::SIZE as u64
+            + self.string_table.len() as u64
+    }
+}
+
+pub(crate) fn page_size(cpu_type: CpuType) -> u64 {
+    match cpu_type {
+        CpuType::Arm64(..) | CpuType::Arm64_32(..) => 0x4000,
+        _ => 0x1000,
+    }
+}
+
+fn align_to(n: u64, align: u64) -> u64 {
+    n.div_ceil(align) * align
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::ExecutableBuilder,
+        file::SectionData,
+        header::{CpuCapabilities, CpuSubTypeX86_64, Flags, Magic},
+        load_command::{
+            segment64::SectionAttrs, segment64::SectionType, DyldInfoCommand, SymtabCommand,
+        },
+        nlist::{NType, NTypeField},
+        reloc::RelocLength,
+        string_table::StringTable,
+    };
+
+    fn segment(segname: &str, vmaddr: u64, vmsize: u64, sects: Vec<Section64>) -> LoadCommand {
+        LoadCommand::Segment64(
+            SegmentCommand64 {
+                cmd: SegmentCommand64::TYPE,
+                cmdsize: 0,
+                segname: segname.to_string(),
+                vmaddr,
+                vmsize,
+                fileoff: 0,
+                filesize: 0,
+                maxprot: 7,
+                initprot: 7,
+                nsects: 0,
+                flags: 0,
+            },
+            sects,
+        )
+    }
+
+    fn section(segname: &str, sectname: &str, align: u32, sect_type: SectionType) -> Section64 {
+        Section64 {
+            sectname: sectname.to_string(),
+            segname: segname.to_string(),
+            addr: 0,
+            size: 0,
+            offset: 0,
+            align,
+            reloff: 0,
+            nreloc: 0,
+            flags: (SectionAttrs::new(), sect_type),
+            reserved1: 0,
+            reserved2: 0,
+            reserved3: 0,
+        }
+    }
+
+    fn symtab() -> LoadCommand {
+        LoadCommand::Symtab(SymtabCommand {
+            cmd: SymtabCommand::TYPE,
+            cmdsize: SymtabCommand::SIZE,
+            symoff: 0,
+            nsyms: 0,
+            stroff: 0,
+            strsize: 0,
+        })
+    }
+
+    fn file(file_type: FileType, load_commands: Vec<LoadCommand>) -> MachOFile {
+        let mut string_table = StringTable::with_null();
+        string_table.push_with_null("_main");
+
+        MachOFile {
+            header: Header64 {
+                magic: Magic::Magic64,
+                cpu_type: CpuType::X86_64(CpuSubTypeX86_64::All, CpuCapabilities::NONE),
+                file_type,
+                n_cmds: 0,
+                size_of_cmds: 0,
+                flags: Flags::new(),
+                reserved: 0,
+            },
+            load_commands,
+            sections: Vec::new(),
+            symbols: vec![NList64 {
+                n_strx: 1,
+                n_type: NTypeField::Norm {
+                    n_pext: false,
+                    n_type: NType::Sect,
+                    n_ext: true,
+                },
+                n_sect: 1,
+                n_desc: 0,
+                n_value: 0,
+            }],
+            string_table,
+            extra: Vec::new(),
+        }
+    }
+
+    fn segments(file: &MachOFile) -> Vec<(&SegmentCommand64, &[Section64])> {
+        file.load_commands
+            .iter()
+            .filter_map(|lc| match lc {
+                LoadCommand::Segment64(seg, sects) => Some((seg, sects.as_slice())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn layout_object() {
+        let mut file = file(
+            FileType::Object,
+            vec![
+                segment(
+                    "",
+                    0,
+                    0,
+                    vec![
+                        section("__TEXT", "__text", 0, SectionType::Regular),
+                        section("__DATA", "__data", 3, SectionType::Regular),
+                        section("__DATA", "__bss", 4, SectionType::Zerofill),
+                    ],
+                ),
+                symtab(),
+            ],
+        );
+        file.sections = vec![
+            SectionData {
+                data: vec![0xc3; 5],
+                relocs: vec![RelocationInfo {
+                    r_address: 1,
+                    r_symbolnum: 0,
+                    r_pcrel: true,
+                    r_length: RelocLength::Long,
+                    r_extern: true,
+                    r_type: 2,
+                }],
+            },
+            SectionData {
+                data: vec![1; 8],
+                relocs: Vec::new(),
+            },
+            SectionData {
+                data: Vec::new(),
+                relocs: Vec::new(),
+            },
+        ];
+        if let LoadCommand::Segment64(_, sects) = &mut file.load_commands[0] {
+            sects[2].size = 0x20;
+        }
+
+        file.layout();
+
+        let cmds_end = Header64::SIZE + SegmentCommand64::SIZE + Section64::SIZE * 3;
+        let cmds_end = cmds_end + SymtabCommand::SIZE;
+        assert_eq!(file.header.n_cmds, 2);
+        assert_eq!(file.header.size_of_cmds, cmds_end - Header64::SIZE);
+
+        let segs = segments(&file);
+        let (seg, sects) = segs[0];
+        assert_eq!(seg.nsects, 3);
+        assert_eq!(sects[0].offset, cmds_end);
+        assert_eq!(sects[0].size, 5);
+        assert_eq!(sects[1].offset, align_to(cmds_end as u64 + 5, 8) as u32);
+        assert_eq!(sects[1].addr, 8);
+        assert_eq!(sects[2].offset, 0);
+        assert_eq!(sects[2].addr, 0x10);
+        assert_eq!(seg.vmsize, 0x30);
+        assert_eq!(seg.filesize, sects[1].offset as u64 + 8 - seg.fileoff);
+        assert_eq!(sects[0].nreloc, 1);
+        assert_eq!(sects[0].reloff, sects[1].offset + 8);
+
+        let mut buf = Vec::new();
+        file.write(&mut buf).unwrap();
+        let parsed = MachOFile::parse(&buf).unwrap();
+        assert_eq!(parsed.load_commands, file.load_commands);
+        assert_eq!(parsed.sections, file.sections);
+        assert_eq!(parsed.symbols, file.symbols);
+        assert!(parsed
+            .extra
+            .iter()
+            .all(|(_, pad)| pad.iter().all(|b| *b == 0)));
+    }
+
+    #[test]
+    fn layout_executable() {
+        let mut file = file(
+            FileType::Execute,
+            vec![
+                segment("__PAGEZERO", 0, 0x1_0000_0000, Vec::new()),
+                segment(
+                    "__TEXT",
+                    0,
+                    0,
+                    vec![section("__TEXT", "__text", 4, SectionType::Regular)],
+                ),
+                segment(
+                    "__DATA",
+                    0,
+                    0,
+                    vec![
+                        section("__DATA", "__data", 3, SectionType::Regular),
+                        section("__DATA", "__bss", 3, SectionType::Zerofill),
+                    ],
+                ),
+                segment("__LINKEDIT", 0, 0, Vec::new()),
+                symtab(),
+            ],
+        );
+        file.sections = vec![
+            SectionData {
+                data: vec![0x90; 0x10],
+                relocs: Vec::new(),
+            },
+            SectionData {
+                data: vec![1; 0x1001],
+                relocs: Vec::new(),
+            },
+            SectionData {
+                data: Vec::new(),
+                relocs: Vec::new(),
+            },
+        ];
+        if let LoadCommand::Segment64(_, sects) = &mut file.load_commands[2] {
+            sects[1].size = 0x100;
+        }
+
+        file.layout();
+
+        let segs = segments(&file);
+        let (pagezero, _) = segs[0];
+        assert_eq!((pagezero.vmaddr, pagezero.vmsize), (0, 0x1_0000_0000));
+        assert_eq!((pagezero.fileoff, pagezero.filesize), (0, 0));
+
+        let (text, text_sects) = segs[1];
+        assert_eq!(text.vmaddr, 0x1_0000_0000);
+        assert_eq!(
+            (text.fileoff, text.filesize, text.vmsize),
+            (0, 0x1000, 0x1000)
+        );
+        assert_eq!(text_sects[0].offset % 0x10, 0);
+        assert!(text_sects[0].offset >= Header64::SIZE + file.header.size_of_cmds);
+        assert_eq!(
+            text_sects[0].addr,
+            text.vmaddr + text_sects[0].offset as u64
+        );
+
+        let (data, data_sects) = segs[2];
+        assert_eq!(data.vmaddr, 0x1_0000_1000);
+        assert_eq!((data.fileoff, data.filesize), (0x1000, 0x2000));
+        assert_eq!(data_sects[0].offset, 0x1000);
+        assert_eq!(data_sects[1].offset, 0);
+        assert_eq!(data_sects[1].addr, 0x1_0000_2008);
+        assert_eq!(data.vmsize, 0x2000);
+
+        let (linkedit, _) = segs[3];
+        assert_eq!(linkedit.vmaddr, 0x1_0000_3000);
+        assert_eq!(linkedit.fileoff, 0x3000);
+        assert_eq!(linkedit.filesize, NList64::SIZE as u64 + 7);
+
+        match &file.load_commands[4] {
+            LoadCommand::Symtab(symtab) => {
+                assert_eq!(symtab.symoff, 0x3000);
+                assert_eq!(symtab.stroff, 0x3000 + NList64::SIZE);
+                assert_eq!(symtab.strsize, 7);
+            }
+            _ => unreachable!(),
+        }
+
+        let mut buf = Vec::new();
+        file.write(&mut buf).unwrap();
+        assert_eq!(buf.len() as u64, linkedit.fileoff + linkedit.filesize);
+    }
+
+    #[test]
+    fn layout_drops_data_in_extra() {
+        let mut builder = ExecutableBuilder::new();
+        let text = builder.add_text_section("__text", 4, vec![0x90, 0xc3]);
+        builder.define_symbol("start", text, 0, true).unwrap();
+        builder.entry_symbol("start");
+        let mut file = builder.build().unwrap();
+        file.sign_adhoc("a.out", 0).unwrap();
+        file.load_commands
+            .push(LoadCommand::DyldInfo(DyldInfoCommand {
+                cmd: DyldInfoCommand::TYPE_ONLY,
+                cmdsize: DyldInfoCommand::SIZE,
+                export_off: 0x8000,
+                export_size: 0x10,
+                ..Default::default()
+            }));
+        let mut dysymtab = None;
+        for lc in file.load_commands.iter_mut() {
+            if let LoadCommand::Dysymtab(cmd) = lc {
+                dysymtab = Some(cmd.clone());
+                cmd.indirectsymoff = 0x8000;
+                cmd.nindirectsyms = 2;
+            }
+        }
+
+        file.layout();
+
+        assert!(file.extra.is_empty());
+        assert!(!file
+            .load_commands
+            .iter()
+            .any(|lc| matches!(lc, LoadCommand::LinkeditData(_) | LoadCommand::DyldInfo(_))));
+        assert!(file
+            .load_commands
+            .contains(&LoadCommand::Dysymtab(dysymtab.unwrap())));
+
+        let mut buf = Vec::new();
+        file.write(&mut buf).unwrap();
+        let parsed = MachOFile::parse(&buf).unwrap();
+        assert_eq!(parsed.load_commands.len(), file.load_commands.len());
+        let mut written = Vec::new();
+        parsed.write(&mut written).unwrap();
+        assert_eq!(written, buf);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::error::{Error, Result};
+
+/// Reads the byte streams of `__LINKEDIT`, such as dyld opcodes and the export trie.
+///
+/// Errors point to `base` plus the position in `data`, so that they carry file offsets when
+/// `base` is the file offset of `data`.
+#[derive(Debug, Clone)]
+pub(crate) struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    base: u64,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(data: &'a [u8], base: u64) -> Self {
+        ByteReader { data, pos: 0, base }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    /// Position in `data`.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn set_pos(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    /// File offset of the current position.
+    pub fn offset(&self) -> u64 {
+        self.base.saturating_add(self.pos as u64)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8> {
+        let byte = *self.data.get(self.pos).ok_or(Error::Truncated {
+            offset: self.offset(),
+        })?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    /// Reads a little-endian u16, as in chained fixups.
+    pub fn read_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    /// Reads a little-endian u32, as in chained fixups.
+    pub fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    /// Reads a little-endian u64, as in chained fixups.
+    pub fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    /// Reads a big-endian u32, as in code signatures.
+    pub fn read_be_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.read_array()?))
+    }
+
+    /// Reads a big-endian u64, as in code signatures.
+    pub fn read_be_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.read_array()?))
+    }
+
+    /// Reads `len` bytes without copying them.
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..)
+            .and_then(|rest| rest.get(..len))
+            .ok_or(Error::Truncated {
+                offset: self.offset(),
+            })?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let bytes = self
+            .data
+            .get(self.pos..)
+            .and_then(|rest| rest.get(..N))
+            .ok_or(Error::Truncated {
+                offset: self.offset(),
+            })?;
+        self.pos += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    pub fn read_uleb(&mut self) -> Result<u64> {
+        let offset = self.offset();
+        let mut n = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            if shift >= 64 {
+                return Err(Error::UnknownValue {
+                    offset,
+                    kind: "uleb128",
+                    value: n,
+                });
+            }
+            n |= u64::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+    }
+
+    pub fn read_sleb(&mut self) -> Result<i64> {
+        let offset = self.offset();
+        let mut n = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            if shift >= 64 {
+                return Err(Error::UnknownValue {
+                    offset,
+                    kind: "sleb128",
+                    value: n as u64,
+                });
+            }
+            n |= i64::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    n |= -1 << shift;
+                }
+                return Ok(n);
+            }
+        }
+    }
+
+    /// Reads a null-terminated string.
+    pub fn read_cstr(&mut self) -> Result<&'a str> {
+        let offset = self.offset();
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        let len = rest.iter().position(|b| *b == 0).ok_or(Error::Truncated {
+            offset: self.base + self.data.len() as u64,
+        })?;
+        self.pos += len + 1;
+        std::str::from_utf8(&rest[..len]).map_err(|_| Error::InvalidString { offset })
+    }
+}
+
+pub(crate) fn write_uleb(buf: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+pub(crate) fn write_sleb(buf: &mut Vec<u8>, mut n: i64) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        let done = (n == 0 && byte & 0x40 == 0) || (n == -1 && byte & 0x40 != 0);
+        if done {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_and_read_leb128() {
+        let mut buf = Vec::new();
+        write_uleb(&mut buf, 624485);
+        write_sleb(&mut buf, -123456);
+        write_sleb(&mut buf, 63);
+        write_sleb(&mut buf, -64);
+        assert_eq!(buf, [0xe5, 0x8e, 0x26, 0xc0, 0xbb, 0x78, 0x3f, 0x40]);
+
+        let mut reader = ByteReader::new(&buf, 0x100);
+        assert_eq!(reader.read_uleb().unwrap(), 624485);
+        assert_eq!(reader.read_sleb().unwrap(), -123456);
+        assert_eq!(reader.read_sleb().unwrap(), 63);
+        assert_eq!(reader.read_sleb().unwrap(), -64);
+        assert!(reader.is_empty());
+        assert!(matches!(
+            reader.read_uleb(),
+            Err(Error::Truncated { offset }) if offset == 0x100 + buf.len() as u64
+        ));
+
+        // truncation points at the value being read, not at the end of the data.
+        reader.set_pos(2);
+        assert!(matches!(
+            reader.read_u64(),
+            Err(Error::Truncated { offset: 0x102 })
+        ));
+    }
+}
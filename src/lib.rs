@@ -1,6 +1,23 @@
+pub mod builder;
+pub mod chained_fixups;
+pub mod code_signature;
+pub mod dyld_info;
+mod error;
+pub mod export_trie;
+pub mod fat;
+pub mod file;
+pub mod function_starts;
 pub mod header;
 mod io;
+mod layout;
+mod leb128;
 pub mod load_command;
 pub mod nlist;
 pub mod reloc;
+mod sign;
 pub mod string_table;
+pub mod view;
+
+pub use error::{Error, Result};
+pub use file::MachOFile;
+pub use io::Endian;
@@ -1,7 +1,11 @@
-use crate::io::{Endian, ReadExt as _, WriteExt as _};
-use num_derive::FromPrimitive;
-use num_traits::FromPrimitive;
-use std::io::{Read, Write};
+use crate::{
+    error::{Error, Result},
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::{
+    fmt,
+    io::{Read, Seek, Write},
+};
 
 /// The build_version_command contains the min OS version on which this
 /// binary was built to run for its platform.  The list of known platforms and
@@ -24,66 +28,121 @@ impl BuildVersionCommand {
 
     pub const SIZE: u32 = 0x18; // 24
 
-    pub fn read_from_in<R: Read>(read: &mut R, endian: Endian) -> Self {
-        let cmd = read.read_u32_in(endian);
-        assert_eq!(cmd, Self::TYPE);
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let offset = read.position()?;
 
-        let cmdsize = read.read_u32_in(endian);
+        let cmd = read.read_u32_in(endian)?;
+        if cmd != Self::TYPE {
+            return Err(read.unknown_value(4, "load command", cmd.into()));
+        }
+
+        let cmdsize = read.read_u32_in(endian)?;
 
-        let platform_n = read.read_u32_in(endian);
-        let platform = Platform::from_u32(platform_n);
+        let platform = Platform::from_u32(read.read_u32_in(endian)?);
 
-        let minos_n = read.read_u32_in(endian);
+        let minos_n = read.read_u32_in(endian)?;
         let minos = Version::from_u32(minos_n);
 
-        let sdk_n = read.read_u32_in(endian);
+        let sdk_n = read.read_u32_in(endian)?;
         let sdk = Version::from_u32(sdk_n);
 
-        let ntools = read.read_u32_in(endian);
-
-        assert_eq!(cmdsize, Self::SIZE + BuildToolVersion::SIZE * ntools);
+        let ntools = read.read_u32_in(endian)?;
+
+        let expected = BuildToolVersion::SIZE
+            .checked_mul(ntools)
+            .and_then(|n| n.checked_add(Self::SIZE));
+        if expected != Some(cmdsize) {
+            return Err(Error::InconsistentCmdSize {
+                offset,
+                cmd,
+                cmdsize,
+            });
+        }
 
-        BuildVersionCommand {
+        Ok(BuildVersionCommand {
             cmd,
             cmdsize,
             platform,
             minos,
             sdk,
             ntools,
-        }
+        })
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
-        write.write_u32_native(self.cmd);
-        write.write_u32_native(self.cmdsize);
-        write.write_u32_native(self.platform.to_u32());
-        write.write_u32_native(self.minos.to_u32());
-        write.write_u32_native(self.sdk.to_u32());
-        write.write_u32_native(self.ntools);
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.cmd, endian)?;
+        write.write_u32_in(self.cmdsize, endian)?;
+        write.write_u32_in(self.platform.to_u32(), endian)?;
+        write.write_u32_in(self.minos.to_u32(), endian)?;
+        write.write_u32_in(self.sdk.to_u32(), endian)?;
+        write.write_u32_in(self.ntools, endian)?;
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Platform {
-    MacOS = 1,
-    IOS = 2,
-    TvOS = 3,
-    WatchOS = 4,
-    BridgeOS = 5,
-    MacCatalyst = 6,
-    IOSSimulator = 7,
-    TvOSSimulator = 8,
-    WatchOSSimulator = 9,
-    Driverkit = 10,
+    MacOS,
+    IOS,
+    TvOS,
+    WatchOS,
+    BridgeOS,
+    MacCatalyst,
+    IOSSimulator,
+    TvOSSimulator,
+    WatchOSSimulator,
+    Driverkit,
+    VisionOS,
+    VisionOSSimulator,
+    Firmware,
+    SepOS,
+    /// Keeps the value as is.
+    Unknown(u32),
 }
 
 impl Platform {
     pub fn from_u32(n: u32) -> Self {
-        FromPrimitive::from_u32(n).unwrap_or_else(|| panic!("Invalid platform number {}", n))
+        match n {
+            1 => Platform::MacOS,
+            2 => Platform::IOS,
+            3 => Platform::TvOS,
+            4 => Platform::WatchOS,
+            5 => Platform::BridgeOS,
+            6 => Platform::MacCatalyst,
+            7 => Platform::IOSSimulator,
+            8 => Platform::TvOSSimulator,
+            9 => Platform::WatchOSSimulator,
+            10 => Platform::Driverkit,
+            11 => Platform::VisionOS,
+            12 => Platform::VisionOSSimulator,
+            13 => Platform::Firmware,
+            14 => Platform::SepOS,
+            n => Platform::Unknown(n),
+        }
     }
 
     pub fn to_u32(self) -> u32 {
-        self as u32
+        match self {
+            Platform::MacOS => 1,
+            Platform::IOS => 2,
+            Platform::TvOS => 3,
+            Platform::WatchOS => 4,
+            Platform::BridgeOS => 5,
+            Platform::MacCatalyst => 6,
+            Platform::IOSSimulator => 7,
+            Platform::TvOSSimulator => 8,
+            Platform::WatchOSSimulator => 9,
+            Platform::Driverkit => 10,
+            Platform::VisionOS => 11,
+            Platform::VisionOSSimulator => 12,
+            Platform::Firmware => 13,
+            Platform::SepOS => 14,
+            Platform::Unknown(n) => n,
+        }
     }
 }
 
@@ -116,6 +175,12 @@ impl Version {
     }
 }
 
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.release)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BuildToolVersion {
     pub tool: Tool,
@@ -125,40 +190,60 @@ pub struct BuildToolVersion {
 impl BuildToolVersion {
     pub const SIZE: u32 = 0x8;
 
-    pub fn read_from_in<R: Read>(read: &mut R, endian: Endian) -> Self {
-        let tool_n = read.read_u32_in(endian);
-        let tool = Tool::from_u32(tool_n);
-        let version = read.read_u32_in(endian);
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let tool = Tool::from_u32(read.read_u32_in(endian)?);
+        let version = read.read_u32_in(endian)?;
 
-        BuildToolVersion { tool, version }
+        Ok(BuildToolVersion { tool, version })
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
-        write.write_u32_native(self.tool.to_u32());
-        write.write_u32_native(self.version);
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.tool.to_u32(), endian)?;
+        write.write_u32_in(self.version, endian)?;
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Tool {
-    Clang = 1,
-    Swift = 2,
-    LD = 3,
+    Clang,
+    Swift,
+    LD,
+    LLD,
+    /// Keeps the value as is.
+    Unknown(u32),
 }
 
 impl Tool {
     pub fn from_u32(n: u32) -> Self {
-        FromPrimitive::from_u32(n).unwrap_or_else(|| panic!("Unsupported tool number {}", n))
+        match n {
+            1 => Tool::Clang,
+            2 => Tool::Swift,
+            3 => Tool::LD,
+            4 => Tool::LLD,
+            n => Tool::Unknown(n),
+        }
     }
 
     pub fn to_u32(self) -> u32 {
-        self as u32
+        match self {
+            Tool::Clang => 1,
+            Tool::Swift => 2,
+            Tool::LD => 3,
+            Tool::LLD => 4,
+            Tool::Unknown(n) => n,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Cursor;
 
     #[test]
     fn write_and_read_build_version_command() {
@@ -181,11 +266,12 @@ mod tests {
 
         let mut buf = Vec::new();
 
-        cmd.write_into(&mut buf);
+        cmd.write_into(&mut buf).unwrap();
 
         assert_eq!(buf.len(), BuildVersionCommand::SIZE as usize);
 
-        let read_cmd = BuildVersionCommand::read_from_in(&mut buf.as_slice(), Endian::NATIVE);
+        let read_cmd =
+            BuildVersionCommand::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
 
         assert_eq!(read_cmd, cmd);
     }
@@ -199,11 +285,12 @@ mod tests {
 
         let mut buf = Vec::new();
 
-        version.write_into(&mut buf);
+        version.write_into(&mut buf).unwrap();
 
         assert_eq!(buf.len(), BuildToolVersion::SIZE as usize);
 
-        let read_version = BuildToolVersion::read_from_in(&mut buf.as_slice(), Endian::NATIVE);
+        let read_version =
+            BuildToolVersion::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
 
         assert_eq!(read_version, version);
     }
@@ -0,0 +1,126 @@
+use crate::{
+    error::{Error, Result},
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, Write};
+
+/// The dyld_info_command contains the file offsets and sizes of the new compressed form of the
+/// information dyld needs to load the image. See `crate::dyld_info` to decode them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DyldInfoCommand {
+    /// `DyldInfoCommand::TYPE` or `DyldInfoCommand::TYPE_ONLY`
+    pub cmd: u32,
+    pub cmdsize: u32,
+    /// file offset to rebase info
+    pub rebase_off: u32,
+    /// size of rebase info
+    pub rebase_size: u32,
+    /// file offset to binding info
+    pub bind_off: u32,
+    /// size of binding info
+    pub bind_size: u32,
+    /// file offset to weak binding info
+    pub weak_bind_off: u32,
+    /// size of weak binding info
+    pub weak_bind_size: u32,
+    /// file offset to lazy binding info
+    pub lazy_bind_off: u32,
+    /// size of lazy binding info
+    pub lazy_bind_size: u32,
+    /// file offset to export info
+    pub export_off: u32,
+    /// size of export info
+    pub export_size: u32,
+}
+
+impl DyldInfoCommand {
+    /// LC_DYLD_INFO
+    pub const TYPE: u32 = 0x22;
+    /// LC_DYLD_INFO_ONLY, which tells older dyld not to load the image without this command.
+    pub const TYPE_ONLY: u32 = 0x22 | super::LC_REQ_DYLD;
+
+    pub const SIZE: u32 = 0x30; // 48
+
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let offset = read.position()?;
+
+        let cmd = read.read_u32_in(endian)?;
+        if cmd != Self::TYPE && cmd != Self::TYPE_ONLY {
+            return Err(read.unknown_value(4, "load command", cmd.into()));
+        }
+
+        let cmdsize = read.read_u32_in(endian)?;
+        if cmdsize != Self::SIZE {
+            return Err(Error::InconsistentCmdSize {
+                offset,
+                cmd,
+                cmdsize,
+            });
+        }
+
+        Ok(DyldInfoCommand {
+            cmd,
+            cmdsize,
+            rebase_off: read.read_u32_in(endian)?,
+            rebase_size: read.read_u32_in(endian)?,
+            bind_off: read.read_u32_in(endian)?,
+            bind_size: read.read_u32_in(endian)?,
+            weak_bind_off: read.read_u32_in(endian)?,
+            weak_bind_size: read.read_u32_in(endian)?,
+            lazy_bind_off: read.read_u32_in(endian)?,
+            lazy_bind_size: read.read_u32_in(endian)?,
+            export_off: read.read_u32_in(endian)?,
+            export_size: read.read_u32_in(endian)?,
+        })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.cmd, endian)?;
+        write.write_u32_in(self.cmdsize, endian)?;
+        write.write_u32_in(self.rebase_off, endian)?;
+        write.write_u32_in(self.rebase_size, endian)?;
+        write.write_u32_in(self.bind_off, endian)?;
+        write.write_u32_in(self.bind_size, endian)?;
+        write.write_u32_in(self.weak_bind_off, endian)?;
+        write.write_u32_in(self.weak_bind_size, endian)?;
+        write.write_u32_in(self.lazy_bind_off, endian)?;
+        write.write_u32_in(self.lazy_bind_size, endian)?;
+        write.write_u32_in(self.export_off, endian)?;
+        write.write_u32_in(self.export_size, endian)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn write_and_read_dyld_info_command() {
+        let cmd = DyldInfoCommand {
+            cmd: DyldInfoCommand::TYPE_ONLY,
+            cmdsize: DyldInfoCommand::SIZE,
+            rebase_off: 0x4000,
+            rebase_size: 8,
+            bind_off: 0x4008,
+            bind_size: 24,
+            lazy_bind_off: 0x4020,
+            lazy_bind_size: 16,
+            export_off: 0x4030,
+            export_size: 48,
+            ..DyldInfoCommand::default()
+        };
+
+        let mut buf = Vec::new();
+        cmd.write_into(&mut buf).unwrap();
+        assert_eq!(buf.len(), DyldInfoCommand::SIZE as usize);
+
+        let read = DyldInfoCommand::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
+        assert_eq!(read, cmd);
+    }
+}
@@ -0,0 +1,161 @@
+use super::{build_version::Version, lc_str::LcStr};
+use crate::{
+    error::Result,
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, Write};
+
+/// A dynamically linked shared library is identified by its install name, and the commands
+/// which refer to it (`LC_LOAD_DYLIB` and so on) or define it (`LC_ID_DYLIB`) share this layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DylibCommand {
+    /// One of `DylibCommand::TYPES`.
+    pub cmd: u32,
+    /// includes the pathname string and its padding.
+    pub cmdsize: u32,
+    /// library's path name
+    pub name: LcStr,
+    /// library's build time stamp
+    pub timestamp: u32,
+    /// library's current version number
+    pub current_version: Version,
+    /// library's compatibility version number
+    pub compatibility_version: Version,
+}
+
+impl DylibCommand {
+    /// dynamically linked shared lib ident
+    pub const ID_DYLIB: u32 = 0xd;
+    /// load a dynamically linked shared library
+    pub const LOAD_DYLIB: u32 = 0xc;
+    /// load a dynamically linked shared library that is allowed to be missing
+    pub const LOAD_WEAK_DYLIB: u32 = 0x18 | super::LC_REQ_DYLD;
+    /// load and re-export dylib
+    pub const REEXPORT_DYLIB: u32 = 0x1f | super::LC_REQ_DYLD;
+    /// delay load of dylib until first use
+    pub const LAZY_LOAD_DYLIB: u32 = 0x20;
+    /// load upward dylib
+    pub const LOAD_UPWARD_DYLIB: u32 = 0x23 | super::LC_REQ_DYLD;
+
+    pub const TYPES: [u32; 6] = [
+        Self::ID_DYLIB,
+        Self::LOAD_DYLIB,
+        Self::LOAD_WEAK_DYLIB,
+        Self::REEXPORT_DYLIB,
+        Self::LAZY_LOAD_DYLIB,
+        Self::LOAD_UPWARD_DYLIB,
+    ];
+
+    /// Size of the fixed part, which the name follows.
+    pub const SIZE: u32 = 0x18;
+
+    /// Creates a command whose name follows the fixed part, and computes `cmdsize`.
+    pub fn new(
+        cmd: u32,
+        name: &str,
+        timestamp: u32,
+        current_version: Version,
+        compatibility_version: Version,
+    ) -> Self {
+        let name = LcStr::new(Self::SIZE, name);
+        DylibCommand {
+            cmd,
+            cmdsize: name.cmd_size(),
+            name,
+            timestamp,
+            current_version,
+            compatibility_version,
+        }
+    }
+
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let offset = read.position()?;
+
+        let cmd = read.read_u32_in(endian)?;
+        if !Self::TYPES.contains(&cmd) {
+            return Err(read.unknown_value(4, "load command", cmd.into()));
+        }
+
+        let cmdsize = read.read_u32_in(endian)?;
+        let name_offset = read.read_u32_in(endian)?;
+        let timestamp = read.read_u32_in(endian)?;
+        let current_version = Version::from_u32(read.read_u32_in(endian)?);
+        let compatibility_version = Version::from_u32(read.read_u32_in(endian)?);
+        let name = LcStr::read_from(read, offset, cmd, cmdsize, name_offset)?;
+
+        Ok(DylibCommand {
+            cmd,
+            cmdsize,
+            name,
+            timestamp,
+            current_version,
+            compatibility_version,
+        })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.cmd, endian)?;
+        write.write_u32_in(self.cmdsize, endian)?;
+        write.write_u32_in(self.name.offset, endian)?;
+        write.write_u32_in(self.timestamp, endian)?;
+        write.write_u32_in(self.current_version.to_u32(), endian)?;
+        write.write_u32_in(self.compatibility_version.to_u32(), endian)?;
+        self.name
+            .write_into(write, self.cmd, Self::SIZE, self.cmdsize)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use std::io::Cursor;
+
+    #[test]
+    fn write_and_read_dylib_command() {
+        let cmd = DylibCommand::new(
+            DylibCommand::LOAD_DYLIB,
+            "/usr/lib/libSystem.B.dylib",
+            2,
+            Version::from_u32(0x050c_3c01),
+            Version::from_u32(0x0001_0000),
+        );
+        assert_eq!(cmd.cmdsize, 56);
+
+        let mut buf = Vec::new();
+        cmd.write_into_in(&mut buf, Endian::REVERSE).unwrap();
+        assert_eq!(buf.len(), cmd.cmdsize as usize);
+
+        let mut cursor = Cursor::new(buf);
+        let read = DylibCommand::read_from_in(&mut cursor, Endian::REVERSE).unwrap();
+        assert_eq!(read, cmd);
+        assert_eq!(cursor.position(), 56);
+        assert_eq!(read.current_version.to_string(), "1292.60.1");
+    }
+
+    #[test]
+    fn read_dylib_command_with_bad_name_offset() {
+        let cmd = DylibCommand::new(
+            DylibCommand::ID_DYLIB,
+            "libfoo.dylib",
+            0,
+            Version::from_u32(0),
+            Version::from_u32(0),
+        );
+
+        let mut buf = Vec::new();
+        cmd.write_into(&mut buf).unwrap();
+        // the writer refuses such a command, so patch name.offset after writing.
+        (&mut buf[8..12])
+            .write_u32_in(0x40, Endian::NATIVE)
+            .unwrap();
+
+        let err = DylibCommand::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap_err();
+        assert!(matches!(err, Error::InconsistentCmdSize { offset: 0, .. }));
+    }
+}
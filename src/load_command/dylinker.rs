@@ -0,0 +1,100 @@
+use super::lc_str::LcStr;
+use crate::{
+    error::Result,
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, Write};
+
+/// A program that uses a dynamic linker contains a dylinker_command to identify the name of the
+/// dynamic linker (`LC_LOAD_DYLINKER`). And a dynamic linker contains a dylinker_command to
+/// identify the dynamic linker (`LC_ID_DYLINKER`). A file can have at most one of these.
+/// This struct is also used for the `LC_DYLD_ENVIRONMENT` load command and contains a string
+/// for dyld to treat like an environment variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DylinkerCommand {
+    /// One of `DylinkerCommand::TYPES`.
+    pub cmd: u32,
+    /// includes the pathname string and its padding.
+    pub cmdsize: u32,
+    /// dynamic linker's path name
+    pub name: LcStr,
+}
+
+impl DylinkerCommand {
+    /// load a dynamic linker
+    pub const LOAD_DYLINKER: u32 = 0xe;
+    /// dynamic linker identification
+    pub const ID_DYLINKER: u32 = 0xf;
+    /// string for dyld to treat like an environment variable
+    pub const DYLD_ENVIRONMENT: u32 = 0x27;
+
+    pub const TYPES: [u32; 3] = [
+        Self::LOAD_DYLINKER,
+        Self::ID_DYLINKER,
+        Self::DYLD_ENVIRONMENT,
+    ];
+
+    /// Size of the fixed part, which the name follows.
+    pub const SIZE: u32 = 0xc;
+
+    /// Creates a command whose name follows the fixed part, and computes `cmdsize`.
+    pub fn new(cmd: u32, name: &str) -> Self {
+        let name = LcStr::new(Self::SIZE, name);
+        DylinkerCommand {
+            cmd,
+            cmdsize: name.cmd_size(),
+            name,
+        }
+    }
+
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let offset = read.position()?;
+
+        let cmd = read.read_u32_in(endian)?;
+        if !Self::TYPES.contains(&cmd) {
+            return Err(read.unknown_value(4, "load command", cmd.into()));
+        }
+
+        let cmdsize = read.read_u32_in(endian)?;
+        let name_offset = read.read_u32_in(endian)?;
+        let name = LcStr::read_from(read, offset, cmd, cmdsize, name_offset)?;
+
+        Ok(DylinkerCommand { cmd, cmdsize, name })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.cmd, endian)?;
+        write.write_u32_in(self.cmdsize, endian)?;
+        write.write_u32_in(self.name.offset, endian)?;
+        self.name
+            .write_into(write, self.cmd, Self::SIZE, self.cmdsize)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn write_and_read_dylinker_command() {
+        let cmd = DylinkerCommand::new(DylinkerCommand::LOAD_DYLINKER, "/usr/lib/dyld");
+        assert_eq!(cmd.cmdsize, 32);
+
+        let mut buf = Vec::new();
+        cmd.write_into_in(&mut buf, Endian::REVERSE).unwrap();
+        assert_eq!(buf.len(), 32);
+        assert_eq!(&buf[12..26], b"/usr/lib/dyld\0");
+        assert!(buf[26..].iter().all(|b| *b == 0));
+
+        let mut cursor = Cursor::new(buf);
+        let read = DylinkerCommand::read_from_in(&mut cursor, Endian::REVERSE).unwrap();
+        assert_eq!(read, cmd);
+        assert_eq!(cursor.position(), 32);
+    }
+}
@@ -1,5 +1,8 @@
-use crate::io::{Endian, ReadExt as _, WriteExt as _};
-use std::io::{Read, Write};
+use crate::{
+    error::{Error, Result},
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, Write};
 
 /// This is the second set of the symbolic information which is used to support
 /// the data structures for the dynamically link editor.
@@ -29,7 +32,7 @@ use std::io::{Read, Write};
 /// tables is determined as follows:
 /// * table of contents - the defined external symbols are sorted by name
 /// * module table - the file contains only one module so everything in the
-/// file is part of the module.
+///   file is part of the module.
 /// * reference symbol table - is the defined and undefined external symbols
 ///
 /// For dynamically linked shared library files this load command also contains
@@ -86,33 +89,43 @@ impl DysymtabCommand {
 
     pub const SIZE: u32 = 0x50;
 
-    pub fn read_from_in<R: Read>(read: &mut R, endian: Endian) -> Self {
-        let cmd = read.read_u32_in(endian);
-        assert_eq!(cmd, Self::TYPE);
-
-        let cmdsize = read.read_u32_in(endian);
-        assert_eq!(cmdsize, Self::SIZE);
-
-        let ilocalsym = read.read_u32_in(endian);
-        let nlocalsym = read.read_u32_in(endian);
-        let iextdefsym = read.read_u32_in(endian);
-        let nextdefsym = read.read_u32_in(endian);
-        let iundefsym = read.read_u32_in(endian);
-        let nundefsym = read.read_u32_in(endian);
-        let tocoff = read.read_u32_in(endian);
-        let ntoc = read.read_u32_in(endian);
-        let modtaboff = read.read_u32_in(endian);
-        let nmodtab = read.read_u32_in(endian);
-        let extrefsymoff = read.read_u32_in(endian);
-        let nextrefsyms = read.read_u32_in(endian);
-        let indirectsymoff = read.read_u32_in(endian);
-        let nindirectsyms = read.read_u32_in(endian);
-        let extreloff = read.read_u32_in(endian);
-        let nextrel = read.read_u32_in(endian);
-        let locreloff = read.read_u32_in(endian);
-        let nlocrel = read.read_u32_in(endian);
-
-        DysymtabCommand {
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let offset = read.position()?;
+
+        let cmd = read.read_u32_in(endian)?;
+        if cmd != Self::TYPE {
+            return Err(read.unknown_value(4, "load command", cmd.into()));
+        }
+
+        let cmdsize = read.read_u32_in(endian)?;
+        if cmdsize != Self::SIZE {
+            return Err(Error::InconsistentCmdSize {
+                offset,
+                cmd,
+                cmdsize,
+            });
+        }
+
+        let ilocalsym = read.read_u32_in(endian)?;
+        let nlocalsym = read.read_u32_in(endian)?;
+        let iextdefsym = read.read_u32_in(endian)?;
+        let nextdefsym = read.read_u32_in(endian)?;
+        let iundefsym = read.read_u32_in(endian)?;
+        let nundefsym = read.read_u32_in(endian)?;
+        let tocoff = read.read_u32_in(endian)?;
+        let ntoc = read.read_u32_in(endian)?;
+        let modtaboff = read.read_u32_in(endian)?;
+        let nmodtab = read.read_u32_in(endian)?;
+        let extrefsymoff = read.read_u32_in(endian)?;
+        let nextrefsyms = read.read_u32_in(endian)?;
+        let indirectsymoff = read.read_u32_in(endian)?;
+        let nindirectsyms = read.read_u32_in(endian)?;
+        let extreloff = read.read_u32_in(endian)?;
+        let nextrel = read.read_u32_in(endian)?;
+        let locreloff = read.read_u32_in(endian)?;
+        let nlocrel = read.read_u32_in(endian)?;
+
+        Ok(DysymtabCommand {
             cmd,
             cmdsize,
             ilocalsym,
@@ -133,36 +146,42 @@ impl DysymtabCommand {
             nextrel,
             locreloff,
             nlocrel,
-        }
+        })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
-        write.write_u32_native(self.cmd);
-        write.write_u32_native(self.cmdsize);
-        write.write_u32_native(self.ilocalsym);
-        write.write_u32_native(self.nlocalsym);
-        write.write_u32_native(self.iextdefsym);
-        write.write_u32_native(self.nextdefsym);
-        write.write_u32_native(self.iundefsym);
-        write.write_u32_native(self.nundefsym);
-        write.write_u32_native(self.tocoff);
-        write.write_u32_native(self.ntoc);
-        write.write_u32_native(self.modtaboff);
-        write.write_u32_native(self.nmodtab);
-        write.write_u32_native(self.extrefsymoff);
-        write.write_u32_native(self.nextrefsyms);
-        write.write_u32_native(self.indirectsymoff);
-        write.write_u32_native(self.nindirectsyms);
-        write.write_u32_native(self.extreloff);
-        write.write_u32_native(self.nextrel);
-        write.write_u32_native(self.locreloff);
-        write.write_u32_native(self.nlocrel);
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.cmd, endian)?;
+        write.write_u32_in(self.cmdsize, endian)?;
+        write.write_u32_in(self.ilocalsym, endian)?;
+        write.write_u32_in(self.nlocalsym, endian)?;
+        write.write_u32_in(self.iextdefsym, endian)?;
+        write.write_u32_in(self.nextdefsym, endian)?;
+        write.write_u32_in(self.iundefsym, endian)?;
+        write.write_u32_in(self.nundefsym, endian)?;
+        write.write_u32_in(self.tocoff, endian)?;
+        write.write_u32_in(self.ntoc, endian)?;
+        write.write_u32_in(self.modtaboff, endian)?;
+        write.write_u32_in(self.nmodtab, endian)?;
+        write.write_u32_in(self.extrefsymoff, endian)?;
+        write.write_u32_in(self.nextrefsyms, endian)?;
+        write.write_u32_in(self.indirectsymoff, endian)?;
+        write.write_u32_in(self.nindirectsyms, endian)?;
+        write.write_u32_in(self.extreloff, endian)?;
+        write.write_u32_in(self.nextrel, endian)?;
+        write.write_u32_in(self.locreloff, endian)?;
+        write.write_u32_in(self.nlocrel, endian)?;
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Cursor;
 
     #[test]
     fn write_and_read_dysymtab_command() {
@@ -191,11 +210,12 @@ mod tests {
 
         let mut buf = Vec::new();
 
-        cmd.write_into(&mut buf);
+        cmd.write_into(&mut buf).unwrap();
 
         assert_eq!(buf.len(), DysymtabCommand::SIZE as usize);
 
-        let read_cmd = DysymtabCommand::read_from_in(&mut buf.as_slice(), Endian::NATIVE);
+        let read_cmd =
+            DysymtabCommand::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
 
         assert_eq!(read_cmd, cmd);
     }
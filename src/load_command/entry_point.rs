@@ -0,0 +1,90 @@
+use crate::{
+    error::{Error, Result},
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, Write};
+
+/// The entry_point_command is a replacement for thread_command. It is used for main executables
+/// to specify the location (file offset) of main(). If `-stack_size` was used at link time, the
+/// `stacksize` field will contain the stack size needed for the main thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryPointCommand {
+    /// `EntryPointCommand::TYPE`
+    pub cmd: u32,
+    pub cmdsize: u32,
+    /// file (`__TEXT`) offset of main()
+    pub entryoff: u64,
+    /// if not zero, initial stack size
+    pub stacksize: u64,
+}
+
+impl EntryPointCommand {
+    /// LC_MAIN
+    pub const TYPE: u32 = 0x28 | super::LC_REQ_DYLD;
+
+    pub const SIZE: u32 = 0x18; // 24
+
+    pub fn new(entryoff: u64, stacksize: u64) -> Self {
+        EntryPointCommand {
+            cmd: Self::TYPE,
+            cmdsize: Self::SIZE,
+            entryoff,
+            stacksize,
+        }
+    }
+
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let offset = read.position()?;
+
+        let cmd = read.read_u32_in(endian)?;
+        if cmd != Self::TYPE {
+            return Err(read.unknown_value(4, "load command", cmd.into()));
+        }
+
+        let cmdsize = read.read_u32_in(endian)?;
+        if cmdsize != Self::SIZE {
+            return Err(Error::InconsistentCmdSize {
+                offset,
+                cmd,
+                cmdsize,
+            });
+        }
+
+        Ok(EntryPointCommand {
+            cmd,
+            cmdsize,
+            entryoff: read.read_u64_in(endian)?,
+            stacksize: read.read_u64_in(endian)?,
+        })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.cmd, endian)?;
+        write.write_u32_in(self.cmdsize, endian)?;
+        write.write_u64_in(self.entryoff, endian)?;
+        write.write_u64_in(self.stacksize, endian)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn write_and_read_entry_point_command() {
+        let cmd = EntryPointCommand::new(0x3f50, 0x80000);
+
+        let mut buf = Vec::new();
+        cmd.write_into_in(&mut buf, Endian::REVERSE).unwrap();
+        assert_eq!(buf.len(), EntryPointCommand::SIZE as usize);
+
+        let read = EntryPointCommand::read_from_in(&mut Cursor::new(buf), Endian::REVERSE).unwrap();
+        assert_eq!(read, cmd);
+    }
+}
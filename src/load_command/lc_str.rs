@@ -0,0 +1,92 @@
+use crate::{
+    error::{Error, Result},
+    io::{ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// A variable length string in a load command (`union lc_str`).
+///
+/// The string is placed at `offset` bytes from the start of the load command, terminated by a
+/// null byte and padded with zeros up to `cmdsize`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LcStr {
+    pub offset: u32,
+    pub string: String,
+}
+
+impl LcStr {
+    /// Creates a string placed right after the fixed size part of a load command, whose size is
+    /// `offset`.
+    pub fn new(offset: u32, string: impl Into<String>) -> Self {
+        LcStr {
+            offset,
+            string: string.into(),
+        }
+    }
+
+    /// The smallest `cmdsize` of the load command which ends with this string, padded to a
+    /// multiple of 8 bytes.
+    pub fn cmd_size(&self) -> u32 {
+        let end = self.offset + self.string.len() as u32 + 1;
+        end.div_ceil(8) * 8
+    }
+
+    /// Reads the string of the load command at `cmd_offset`, after its fixed size part is read.
+    /// The stream is left at the end of the load command.
+    pub(crate) fn read_from<R: Read + Seek>(
+        read: &mut R,
+        cmd_offset: u64,
+        cmd: u32,
+        cmdsize: u32,
+        offset: u32,
+    ) -> Result<Self> {
+        let fixed_size = read.position()? - cmd_offset;
+        if (offset as u64) < fixed_size || offset >= cmdsize {
+            return Err(Error::InconsistentCmdSize {
+                offset: cmd_offset,
+                cmd,
+                cmdsize,
+            });
+        }
+
+        read.seek(SeekFrom::Start(cmd_offset + offset as u64))?;
+        let string = read.read_fixed_size_string((cmdsize - offset) as usize)?;
+
+        Ok(LcStr { offset, string })
+    }
+
+    /// Writes the string and padding of the load command `cmd` whose fixed size part of
+    /// `fixed_size` bytes has been written.
+    ///
+    /// Fails if the string does not fit between `fixed_size` and `cmdsize`, such as after it is
+    /// edited without updating `cmdsize`, since writing it would break the following commands.
+    pub(crate) fn write_into<W: Write>(
+        &self,
+        write: &mut W,
+        cmd: u32,
+        fixed_size: u32,
+        cmdsize: u32,
+    ) -> Result<()> {
+        let end = u32::try_from(self.string.len())
+            .ok()
+            .and_then(|len| self.offset.checked_add(len))
+            .filter(|end| self.offset >= fixed_size && *end <= cmdsize);
+        let end = end.ok_or(Error::CmdSizeMismatch {
+            cmd,
+            cmdsize,
+            size: self.cmd_size(),
+        })?;
+
+        write_zeros(write, self.offset - fixed_size)?;
+        write.write_all(self.string.as_bytes())?;
+        // the terminator is omitted only if the original string filled the command.
+        write_zeros(write, cmdsize - end)
+    }
+}
+
+fn write_zeros<W: Write>(write: &mut W, len: u32) -> Result<()> {
+    for _ in 0..len {
+        write.write_u8(0)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use crate::{
+    error::{Error, Result},
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, Write};
+
+/// The linkedit_data_command contains the offsets and sizes of a blob of data in the
+/// `__LINKEDIT` segment. Which data it refers to depends on `cmd`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkeditDataCommand {
+    /// One of `LinkeditDataCommand::TYPES`
+    pub cmd: u32,
+    pub cmdsize: u32,
+    /// file offset of data in `__LINKEDIT` segment
+    pub dataoff: u32,
+    /// file size of data in `__LINKEDIT` segment
+    pub datasize: u32,
+}
+
+impl LinkeditDataCommand {
+    /// LC_CODE_SIGNATURE, which refers to the code signature.
+    pub const CODE_SIGNATURE: u32 = 0x1d;
+
+    /// LC_FUNCTION_STARTS, which refers to the compressed table of function start addresses.
+    pub const FUNCTION_STARTS: u32 = 0x26;
+
+    /// LC_DYLD_EXPORTS_TRIE, which refers to the export trie.
+    pub const DYLD_EXPORTS_TRIE: u32 = 0x33 | super::LC_REQ_DYLD;
+
+    /// LC_DYLD_CHAINED_FIXUPS, which refers to the chained fixups.
+    pub const DYLD_CHAINED_FIXUPS: u32 = 0x34 | super::LC_REQ_DYLD;
+
+    pub const TYPES: [u32; 4] = [
+        Self::CODE_SIGNATURE,
+        Self::FUNCTION_STARTS,
+        Self::DYLD_EXPORTS_TRIE,
+        Self::DYLD_CHAINED_FIXUPS,
+    ];
+
+    pub const SIZE: u32 = 0x10; // 16
+
+    pub fn new(cmd: u32, dataoff: u32, datasize: u32) -> Self {
+        LinkeditDataCommand {
+            cmd,
+            cmdsize: Self::SIZE,
+            dataoff,
+            datasize,
+        }
+    }
+
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let offset = read.position()?;
+
+        let cmd = read.read_u32_in(endian)?;
+        if !Self::TYPES.contains(&cmd) {
+            return Err(read.unknown_value(4, "load command", cmd.into()));
+        }
+
+        let cmdsize = read.read_u32_in(endian)?;
+        if cmdsize != Self::SIZE {
+            return Err(Error::InconsistentCmdSize {
+                offset,
+                cmd,
+                cmdsize,
+            });
+        }
+
+        Ok(LinkeditDataCommand {
+            cmd,
+            cmdsize,
+            dataoff: read.read_u32_in(endian)?,
+            datasize: read.read_u32_in(endian)?,
+        })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.cmd, endian)?;
+        write.write_u32_in(self.cmdsize, endian)?;
+        write.write_u32_in(self.dataoff, endian)?;
+        write.write_u32_in(self.datasize, endian)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn write_and_read_linkedit_data_command() {
+        let cmd = LinkeditDataCommand::new(LinkeditDataCommand::DYLD_EXPORTS_TRIE, 0x8000, 0x40);
+
+        let mut buf = Vec::new();
+        cmd.write_into_in(&mut buf, Endian::REVERSE).unwrap();
+        assert_eq!(buf.len(), LinkeditDataCommand::SIZE as usize);
+
+        let read =
+            LinkeditDataCommand::read_from_in(&mut Cursor::new(buf), Endian::REVERSE).unwrap();
+        assert_eq!(read, cmd);
+    }
+}
@@ -1,5 +1,13 @@
 pub mod build_version;
+pub mod dyld_info;
+pub mod dylib;
+pub mod dylinker;
 pub mod dysymtab;
+pub mod entry_point;
+pub mod lc_str;
+pub mod linkedit_data;
+pub mod rpath;
+pub mod segment32;
 pub mod segment64;
 pub mod source_version;
 pub mod symtab;
@@ -8,7 +16,15 @@ pub mod uuid;
 
 pub use self::{
     build_version::{BuildToolVersion, BuildVersionCommand},
+    dyld_info::DyldInfoCommand,
+    dylib::DylibCommand,
+    dylinker::DylinkerCommand,
     dysymtab::DysymtabCommand,
+    entry_point::EntryPointCommand,
+    lc_str::LcStr,
+    linkedit_data::LinkeditDataCommand,
+    rpath::RpathCommand,
+    segment32::{Section32, SegmentCommand32},
     segment64::{Section64, SegmentCommand64},
     source_version::SourceVersionCommand,
     symtab::SymtabCommand,
@@ -16,12 +32,19 @@ pub use self::{
     uuid::UuidCommand,
 };
 
-use crate::io::{Endian, ReadExt as _};
-use byteorder::{BigEndian, LittleEndian, WriteBytesExt as _};
-use std::io::{Read, Write};
+use crate::{
+    error::{Error, Result},
+    header::CpuType,
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// Set in `cmd` of load commands which dyld must understand to load the image.
+pub const LC_REQ_DYLD: u32 = 0x80000000;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LoadCommand {
+    Segment(SegmentCommand32, Vec<Section32>),
     Segment64(SegmentCommand64, Vec<Section64>),
     Symtab(SymtabCommand),
     UnixThread(UnixThreadCommand),
@@ -29,6 +52,14 @@ pub enum LoadCommand {
     Uuid(UuidCommand),
     BuildVersion(BuildVersionCommand, Vec<BuildToolVersion>),
     SourceVersion(SourceVersionCommand),
+    Dylib(DylibCommand),
+    DyldInfo(DyldInfoCommand),
+    LinkeditData(LinkeditDataCommand),
+    EntryPoint(EntryPointCommand),
+    Dylinker(DylinkerCommand),
+    Rpath(RpathCommand),
+    /// A load command this crate does not understand, kept as `cmd` and the bytes following
+    /// `cmdsize`. The bytes are written back as is regardless of the endian.
     Unsupported(u32, Vec<u8>),
 }
 
@@ -37,6 +68,7 @@ impl LoadCommand {
         use LoadCommand as LC;
 
         match self {
+            LC::Segment(cmd, _) => cmd.cmd,
             LC::Segment64(cmd, _) => cmd.cmd,
             LC::Symtab(cmd) => cmd.cmd,
             LC::UnixThread(cmd) => cmd.cmd,
@@ -44,6 +76,12 @@ impl LoadCommand {
             LC::Uuid(cmd) => cmd.cmd,
             LC::BuildVersion(cmd, _) => cmd.cmd,
             LC::SourceVersion(cmd) => cmd.cmd,
+            LC::Dylib(cmd) => cmd.cmd,
+            LC::DyldInfo(cmd) => cmd.cmd,
+            LC::LinkeditData(cmd) => cmd.cmd,
+            LC::EntryPoint(cmd) => cmd.cmd,
+            LC::Dylinker(cmd) => cmd.cmd,
+            LC::Rpath(cmd) => cmd.cmd,
             LC::Unsupported(cmd, _) => *cmd,
         }
     }
@@ -52,6 +90,7 @@ impl LoadCommand {
         use LoadCommand as LC;
 
         match self {
+            LC::Segment(cmd, _) => cmd.cmdsize,
             LC::Segment64(cmd, _) => cmd.cmdsize,
             LC::Symtab(cmd) => cmd.cmdsize,
             LC::UnixThread(cmd) => cmd.cmdsize,
@@ -59,108 +98,215 @@ impl LoadCommand {
             LC::Uuid(cmd) => cmd.cmdsize,
             LC::BuildVersion(cmd, _) => cmd.cmdsize,
             LC::SourceVersion(cmd) => cmd.cmdsize,
-            LC::Unsupported(_, data) => data.len() as u32 - 8,
+            LC::Dylib(cmd) => cmd.cmdsize,
+            LC::DyldInfo(cmd) => cmd.cmdsize,
+            LC::LinkeditData(cmd) => cmd.cmdsize,
+            LC::EntryPoint(cmd) => cmd.cmdsize,
+            LC::Dylinker(cmd) => cmd.cmdsize,
+            LC::Rpath(cmd) => cmd.cmdsize,
+            LC::Unsupported(_, data) => data.len() as u32 + 8,
         }
     }
 
-    pub fn read_from_in<R: Read>(read: &mut R, endian: Endian) -> Self {
+    /// `cpu_type` is the one in the mach header, which determines the flavors of thread states.
+    pub fn read_from_in<R: Read + Seek>(
+        read: &mut R,
+        endian: Endian,
+        cpu_type: CpuType,
+    ) -> Result<Self> {
         use LoadCommand as LC;
 
-        let cmd = read.read_u32_in(endian);
+        let offset = read.position()?;
+        let cmd = read.read_u32_in(endian)?;
+        read.seek(SeekFrom::Start(offset))?;
 
-        let mut cmd_bytes = [0u8; 4];
-        match endian {
-            Endian::Little => (&mut cmd_bytes[..]).write_u32::<LittleEndian>(cmd).unwrap(),
-            Endian::Big => (&mut cmd_bytes[..]).write_u32::<BigEndian>(cmd).unwrap(),
-        };
+        match cmd {
+            SegmentCommand32::TYPE => {
+                let cmd = SegmentCommand32::read_from_in(read, endian)?;
 
-        let mut read = cmd_bytes.chain(read);
+                let mut sections = Vec::new();
+                for _ in 0..cmd.nsects {
+                    sections.push(Section32::read_from_in(read, endian)?);
+                }
 
-        match cmd {
+                Ok(LC::Segment(cmd, sections))
+            }
             SegmentCommand64::TYPE => {
-                let cmd = SegmentCommand64::read_from_in(&mut read, endian);
+                let cmd = SegmentCommand64::read_from_in(read, endian)?;
 
-                let mut sections = Vec::with_capacity(cmd.nsects as usize);
+                let mut sections = Vec::new();
                 for _ in 0..cmd.nsects {
-                    sections.push(Section64::read_from_in(&mut read, endian));
+                    sections.push(Section64::read_from_in(read, endian)?);
                 }
 
-                LC::Segment64(cmd, sections)
+                Ok(LC::Segment64(cmd, sections))
             }
             SymtabCommand::TYPE => {
-                let cmd = SymtabCommand::read_from_in(&mut read, endian);
-                LC::Symtab(cmd)
+                let cmd = SymtabCommand::read_from_in(read, endian)?;
+                Ok(LC::Symtab(cmd))
             }
-            UnixThreadCommand::TYPE => {
-                let cmd = UnixThreadCommand::read_from_in(&mut read, endian);
-                LC::UnixThread(cmd)
+            UnixThreadCommand::TYPE | UnixThreadCommand::TYPE_THREAD => {
+                let cmd = UnixThreadCommand::read_from_in(read, endian, cpu_type)?;
+                Ok(LC::UnixThread(cmd))
             }
             DysymtabCommand::TYPE => {
-                let cmd = DysymtabCommand::read_from_in(&mut read, endian);
-                LC::Dysymtab(cmd)
+                let cmd = DysymtabCommand::read_from_in(read, endian)?;
+                Ok(LC::Dysymtab(cmd))
             }
             UuidCommand::TYPE => {
-                let cmd = UuidCommand::read_from_in(&mut read, endian);
-                LC::Uuid(cmd)
+                let cmd = UuidCommand::read_from_in(read, endian)?;
+                Ok(LC::Uuid(cmd))
             }
             BuildVersionCommand::TYPE => {
-                let cmd = BuildVersionCommand::read_from_in(&mut read, endian);
+                let cmd = BuildVersionCommand::read_from_in(read, endian)?;
 
-                let mut tools = Vec::with_capacity(cmd.ntools as usize);
+                let mut tools = Vec::new();
                 for _ in 0..cmd.ntools {
-                    tools.push(BuildToolVersion::read_from_in(&mut read, endian));
+                    tools.push(BuildToolVersion::read_from_in(read, endian)?);
                 }
-                LC::BuildVersion(cmd, tools)
+                Ok(LC::BuildVersion(cmd, tools))
             }
             SourceVersionCommand::TYPE => {
-                let cmd = SourceVersionCommand::read_from_in(&mut read, endian);
-                LC::SourceVersion(cmd)
+                let cmd = SourceVersionCommand::read_from_in(read, endian)?;
+                Ok(LC::SourceVersion(cmd))
+            }
+            DylibCommand::ID_DYLIB
+            | DylibCommand::LOAD_DYLIB
+            | DylibCommand::LOAD_WEAK_DYLIB
+            | DylibCommand::REEXPORT_DYLIB
+            | DylibCommand::LAZY_LOAD_DYLIB
+            | DylibCommand::LOAD_UPWARD_DYLIB => {
+                let cmd = DylibCommand::read_from_in(read, endian)?;
+                Ok(LC::Dylib(cmd))
+            }
+            DyldInfoCommand::TYPE | DyldInfoCommand::TYPE_ONLY => {
+                let cmd = DyldInfoCommand::read_from_in(read, endian)?;
+                Ok(LC::DyldInfo(cmd))
+            }
+            cmd if LinkeditDataCommand::TYPES.contains(&cmd) => {
+                let cmd = LinkeditDataCommand::read_from_in(read, endian)?;
+                Ok(LC::LinkeditData(cmd))
+            }
+            EntryPointCommand::TYPE => {
+                let cmd = EntryPointCommand::read_from_in(read, endian)?;
+                Ok(LC::EntryPoint(cmd))
+            }
+            DylinkerCommand::LOAD_DYLINKER
+            | DylinkerCommand::ID_DYLINKER
+            | DylinkerCommand::DYLD_ENVIRONMENT => {
+                let cmd = DylinkerCommand::read_from_in(read, endian)?;
+                Ok(LC::Dylinker(cmd))
+            }
+            RpathCommand::TYPE => {
+                let cmd = RpathCommand::read_from_in(read, endian)?;
+                Ok(LC::Rpath(cmd))
             }
             _ => {
-                let _cmd = read.read_u32_in(endian);
-                let cmdsize = read.read_u32_in(endian) as usize;
-                let mut data = Vec::with_capacity(cmdsize - 8);
-                data.resize(cmdsize - 8, 0);
-                read.read_exact(&mut data).unwrap();
-                LC::Unsupported(cmd, data)
+                let _cmd = read.read_u32_in(endian)?;
+                let cmdsize = read.read_u32_in(endian)?;
+                if cmdsize < 8 {
+                    return Err(Error::InconsistentCmdSize {
+                        offset,
+                        cmd,
+                        cmdsize,
+                    });
+                }
+                let data = read.read_bytes(cmdsize as usize - 8)?;
+                Ok(LC::Unsupported(cmd, data))
             }
         }
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
         use LoadCommand as LC;
 
         match self {
+            LC::Segment(cmd, sections) => {
+                cmd.write_into_in(write, endian)?;
+                for section in sections.iter() {
+                    section.write_into_in(write, endian)?;
+                }
+            }
             LC::Segment64(cmd, sections) => {
-                cmd.write_into(write);
+                cmd.write_into_in(write, endian)?;
                 for section in sections.iter() {
-                    section.write_into(write);
+                    section.write_into_in(write, endian)?;
                 }
             }
             LC::Symtab(cmd) => {
-                cmd.write_into(write);
+                cmd.write_into_in(write, endian)?;
             }
             LC::UnixThread(cmd) => {
-                cmd.write_into(write);
+                cmd.write_into_in(write, endian)?;
             }
             LC::Dysymtab(cmd) => {
-                cmd.write_into(write);
+                cmd.write_into_in(write, endian)?;
             }
             LC::Uuid(cmd) => {
-                cmd.write_into(write);
+                cmd.write_into_in(write, endian)?;
             }
             LC::BuildVersion(cmd, tools) => {
-                cmd.write_into(write);
+                cmd.write_into_in(write, endian)?;
                 for tool in tools.iter() {
-                    tool.write_into(write);
+                    tool.write_into_in(write, endian)?;
                 }
             }
             LC::SourceVersion(cmd) => {
-                cmd.write_into(write);
+                cmd.write_into_in(write, endian)?;
+            }
+            LC::Dylib(cmd) => {
+                cmd.write_into_in(write, endian)?;
+            }
+            LC::DyldInfo(cmd) => {
+                cmd.write_into_in(write, endian)?;
+            }
+            LC::LinkeditData(cmd) => {
+                cmd.write_into_in(write, endian)?;
+            }
+            LC::EntryPoint(cmd) => {
+                cmd.write_into_in(write, endian)?;
+            }
+            LC::Dylinker(cmd) => {
+                cmd.write_into_in(write, endian)?;
             }
-            LC::Unsupported(_, _) => {
-                panic!("Unsupported LoadCommand is unwritable");
+            LC::Rpath(cmd) => {
+                cmd.write_into_in(write, endian)?;
+            }
+            LC::Unsupported(cmd, data) => {
+                write.write_u32_in(*cmd, endian)?;
+                write.write_u32_in(self.cmd_size(), endian)?;
+                write.write_all(data)?;
             }
         }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn write_and_read_unsupported_command() {
+        let mut buf = Vec::new();
+        buf.write_u32_in(0x7fff_0001, Endian::REVERSE).unwrap();
+        buf.write_u32_in(16, Endian::REVERSE).unwrap();
+        buf.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let cpu_type = CpuType::Unknown(0, 0);
+        let cmd =
+            LoadCommand::read_from_in(&mut Cursor::new(&buf), Endian::REVERSE, cpu_type).unwrap();
+        assert_eq!(cmd.cmd(), 0x7fff_0001);
+        assert_eq!(cmd.cmd_size(), 16);
+
+        let mut written = Vec::new();
+        cmd.write_into_in(&mut written, Endian::REVERSE).unwrap();
+        assert_eq!(written, buf);
     }
 }
@@ -0,0 +1,100 @@
+use super::lc_str::LcStr;
+use crate::{
+    error::Result,
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, Write};
+
+/// The rpath_command contains a path which at runtime should be added to the current run path
+/// used to find `@rpath` prefixed dylibs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpathCommand {
+    /// `RpathCommand::TYPE`
+    pub cmd: u32,
+    /// includes the path string and its padding.
+    pub cmdsize: u32,
+    /// path to add to run path
+    pub path: LcStr,
+}
+
+impl RpathCommand {
+    /// LC_RPATH
+    pub const TYPE: u32 = 0x1c | super::LC_REQ_DYLD;
+
+    /// Size of the fixed part, which the path follows.
+    pub const SIZE: u32 = 0xc;
+
+    /// Creates a command whose path follows the fixed part, and computes `cmdsize`.
+    pub fn new(path: &str) -> Self {
+        let path = LcStr::new(Self::SIZE, path);
+        RpathCommand {
+            cmd: Self::TYPE,
+            cmdsize: path.cmd_size(),
+            path,
+        }
+    }
+
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let offset = read.position()?;
+
+        let cmd = read.read_u32_in(endian)?;
+        if cmd != Self::TYPE {
+            return Err(read.unknown_value(4, "load command", cmd.into()));
+        }
+
+        let cmdsize = read.read_u32_in(endian)?;
+        let path_offset = read.read_u32_in(endian)?;
+        let path = LcStr::read_from(read, offset, cmd, cmdsize, path_offset)?;
+
+        Ok(RpathCommand { cmd, cmdsize, path })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.cmd, endian)?;
+        write.write_u32_in(self.cmdsize, endian)?;
+        write.write_u32_in(self.path.offset, endian)?;
+        self.path
+            .write_into(write, self.cmd, Self::SIZE, self.cmdsize)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use std::io::Cursor;
+
+    #[test]
+    fn write_and_read_rpath_command() {
+        // the string ends right at a multiple of 8 bytes, so a whole padding block is needed for
+        // the null byte.
+        let cmd = RpathCommand::new("@executable_path/../");
+        assert_eq!(cmd.cmdsize, 40);
+
+        let mut buf = Vec::new();
+        cmd.write_into(&mut buf).unwrap();
+        assert_eq!(buf.len(), 40);
+        assert_eq!(buf[32..], [0; 8]);
+
+        let read = RpathCommand::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
+        assert_eq!(read, cmd);
+        assert_eq!(read.path.string, "@executable_path/../");
+
+        // a path edited without updating cmdsize
+        let mut edited = read;
+        edited.path.string.push_str("Frameworks");
+        assert!(matches!(
+            edited.write_into(&mut Vec::new()),
+            Err(Error::CmdSizeMismatch {
+                cmdsize: 40,
+                size: 48,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+use super::segment64::{read_section_flags_in, SectionAttrs, SectionType};
+use crate::{
+    error::{Error, Result},
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, Write};
+
+/// The 32-bit counterpart of `SegmentCommand64`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentCommand32 {
+    /// SegmentCommand32::TYPE
+    pub cmd: u32,
+    /// includes sizeof Section32 structs
+    pub cmdsize: u32,
+    /// segment name. 16byte
+    pub segname: String,
+    /// memory address of this segment
+    pub vmaddr: u32,
+    /// memory size of this segment
+    pub vmsize: u32,
+    /// file offset of this segment
+    pub fileoff: u32,
+    /// amount to map from the file
+    pub filesize: u32,
+    /// maximum VM protection
+    pub maxprot: i32,
+    /// initial VM protection
+    pub initprot: i32,
+    /// number of sections in segment
+    pub nsects: u32,
+    /// flags
+    pub flags: u32,
+}
+
+impl SegmentCommand32 {
+    pub const TYPE: u32 = 0x1;
+
+    /// Byte size of `SegmentCommand32` command.
+    /// This does not include `Section32` command size.
+    /// So this is constant.
+    pub const SIZE: u32 = 0x38; // 56
+
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let offset = read.position()?;
+
+        let cmd = read.read_u32_in(endian)?;
+        if cmd != Self::TYPE {
+            return Err(read.unknown_value(4, "load command", cmd.into()));
+        }
+
+        let cmdsize = read.read_u32_in(endian)?;
+        let segname = read.read_fixed_size_string(16)?;
+        let vmaddr = read.read_u32_in(endian)?;
+        let vmsize = read.read_u32_in(endian)?;
+        let fileoff = read.read_u32_in(endian)?;
+        let filesize = read.read_u32_in(endian)?;
+        let maxprot = read.read_i32_in(endian)?;
+        let initprot = read.read_i32_in(endian)?;
+        let nsects = read.read_u32_in(endian)?;
+        let flags = read.read_u32_in(endian)?;
+
+        let expected = Section32::SIZE
+            .checked_mul(nsects)
+            .and_then(|n| n.checked_add(Self::SIZE));
+        if expected != Some(cmdsize) {
+            return Err(Error::InconsistentCmdSize {
+                offset,
+                cmd,
+                cmdsize,
+            });
+        }
+
+        Ok(SegmentCommand32 {
+            cmd,
+            cmdsize,
+            segname,
+            vmaddr,
+            vmsize,
+            fileoff,
+            filesize,
+            maxprot,
+            initprot,
+            nsects,
+            flags,
+        })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.cmd, endian)?;
+        write.write_u32_in(self.cmdsize, endian)?;
+        write.write_fixed_size_string(self.segname.as_str(), 16)?;
+        write.write_u32_in(self.vmaddr, endian)?;
+        write.write_u32_in(self.vmsize, endian)?;
+        write.write_u32_in(self.fileoff, endian)?;
+        write.write_u32_in(self.filesize, endian)?;
+        write.write_i32_in(self.maxprot, endian)?;
+        write.write_i32_in(self.initprot, endian)?;
+        write.write_u32_in(self.nsects, endian)?;
+        write.write_u32_in(self.flags, endian)?;
+        Ok(())
+    }
+}
+
+/// The 32-bit counterpart of `Section64`.
+/// Same as `Section64` except that `addr` and `size` are 32-bit and `reserved3` does not exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section32 {
+    /// 16-byte string
+    pub sectname: String,
+    /// 16-byte string
+    pub segname: String,
+    /// memory address of this section
+    pub addr: u32,
+    /// size in bytes of this section
+    pub size: u32,
+    /// file offset of this section
+    pub offset: u32,
+    /// section alignment (power of 2)
+    pub align: u32,
+    /// file offset of the first relocation entry for this section
+    pub reloff: u32,
+    /// number of relocation entries for this section
+    pub nreloc: u32,
+    /// represented as u32.
+    /// higher 3 bytes represent SectionAttrs,
+    /// lower 1 byte represent SectionType.
+    pub flags: (SectionAttrs, SectionType),
+    pub reserved1: u32,
+    pub reserved2: u32,
+}
+
+impl Section32 {
+    pub const SIZE: u32 = 0x44; // 68
+
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let sectname = read.read_fixed_size_string(16)?;
+        let segname = read.read_fixed_size_string(16)?;
+        let addr = read.read_u32_in(endian)?;
+        let size = read.read_u32_in(endian)?;
+        let offset = read.read_u32_in(endian)?;
+        let align = read.read_u32_in(endian)?;
+        let reloff = read.read_u32_in(endian)?;
+        let nreloc = read.read_u32_in(endian)?;
+        let flags = read_section_flags_in(read, endian)?;
+        let reserved1 = read.read_u32_in(endian)?;
+        let reserved2 = read.read_u32_in(endian)?;
+
+        Ok(Section32 {
+            sectname,
+            segname,
+            addr,
+            size,
+            offset,
+            align,
+            reloff,
+            nreloc,
+            flags,
+            reserved1,
+            reserved2,
+        })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_fixed_size_string(self.sectname.as_str(), 16)?;
+        write.write_fixed_size_string(self.segname.as_str(), 16)?;
+        write.write_u32_in(self.addr, endian)?;
+        write.write_u32_in(self.size, endian)?;
+        write.write_u32_in(self.offset, endian)?;
+        write.write_u32_in(self.align, endian)?;
+        write.write_u32_in(self.reloff, endian)?;
+        write.write_u32_in(self.nreloc, endian)?;
+
+        let flags_n = self.flags.0.to_u32() | self.flags.1.to_u32();
+        write.write_u32_in(flags_n, endian)?;
+
+        write.write_u32_in(self.reserved1, endian)?;
+        write.write_u32_in(self.reserved2, endian)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn write_and_read_segment32_command() {
+        let cmd = SegmentCommand32 {
+            cmd: SegmentCommand32::TYPE,
+            cmdsize: SegmentCommand32::SIZE + Section32::SIZE,
+            segname: "__TEXT".to_string(),
+            vmaddr: 0x1000,
+            vmsize: 42,
+            fileoff: 100,
+            filesize: 42,
+            maxprot: 5,
+            initprot: 5,
+            nsects: 1,
+            flags: 0,
+        };
+
+        let mut buf = Vec::new();
+
+        cmd.write_into(&mut buf).unwrap();
+
+        assert_eq!(buf.len(), SegmentCommand32::SIZE as usize);
+
+        let read_cmd =
+            SegmentCommand32::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
+
+        assert_eq!(read_cmd, cmd);
+    }
+
+    #[test]
+    fn write_and_read_section32() {
+        let cmd = Section32 {
+            sectname: "__text".to_string(),
+            segname: "__TEXT".to_string(),
+            addr: 0x1000,
+            size: 42,
+            offset: 100,
+            align: 4,
+            reloff: 0,
+            nreloc: 0,
+            flags: (SectionAttrs::new(), SectionType::Regular),
+            reserved1: 0,
+            reserved2: 0,
+        };
+
+        let mut buf = Vec::new();
+
+        cmd.write_into(&mut buf).unwrap();
+
+        assert_eq!(buf.len(), Section32::SIZE as usize);
+
+        let read_cmd = Section32::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
+
+        assert_eq!(read_cmd, cmd);
+    }
+}
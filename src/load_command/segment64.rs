@@ -1,9 +1,12 @@
-use crate::io::{Endian, ReadExt as _, WriteExt as _};
+use crate::{
+    error::{Error, Result},
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use std::{
     fmt,
-    io::{Read, Write},
+    io::{Read, Seek, Write},
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -40,24 +43,37 @@ impl SegmentCommand64 {
     /// So this is constant.
     pub const SIZE: u32 = 0x48; // 72
 
-    pub fn read_from_in<R: Read>(read: &mut R, endian: Endian) -> Self {
-        let cmd = read.read_u32_in(endian);
-        assert_eq!(cmd, Self::TYPE);
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let offset = read.position()?;
 
-        let cmdsize = read.read_u32_in(endian);
-        let segname = read.read_fixed_size_string(16);
-        let vmaddr = read.read_u64_in(endian);
-        let vmsize = read.read_u64_in(endian);
-        let fileoff = read.read_u64_in(endian);
-        let filesize = read.read_u64_in(endian);
-        let maxprot = read.read_i32_in(endian);
-        let initprot = read.read_i32_in(endian);
-        let nsects = read.read_u32_in(endian);
-        let flags = read.read_u32_in(endian);
+        let cmd = read.read_u32_in(endian)?;
+        if cmd != Self::TYPE {
+            return Err(read.unknown_value(4, "load command", cmd.into()));
+        }
 
-        assert_eq!(cmdsize, Self::SIZE + nsects * Section64::SIZE);
+        let cmdsize = read.read_u32_in(endian)?;
+        let segname = read.read_fixed_size_string(16)?;
+        let vmaddr = read.read_u64_in(endian)?;
+        let vmsize = read.read_u64_in(endian)?;
+        let fileoff = read.read_u64_in(endian)?;
+        let filesize = read.read_u64_in(endian)?;
+        let maxprot = read.read_i32_in(endian)?;
+        let initprot = read.read_i32_in(endian)?;
+        let nsects = read.read_u32_in(endian)?;
+        let flags = read.read_u32_in(endian)?;
+
+        let expected = Section64::SIZE
+            .checked_mul(nsects)
+            .and_then(|n| n.checked_add(Self::SIZE));
+        if expected != Some(cmdsize) {
+            return Err(Error::InconsistentCmdSize {
+                offset,
+                cmd,
+                cmdsize,
+            });
+        }
 
-        SegmentCommand64 {
+        Ok(SegmentCommand64 {
             cmd,
             cmdsize,
             segname,
@@ -69,21 +85,26 @@ impl SegmentCommand64 {
             initprot,
             nsects,
             flags,
-        }
+        })
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
-        write.write_u32_native(self.cmd);
-        write.write_u32_native(self.cmdsize);
-        write.write_fixed_size_string(self.segname.as_str(), 16);
-        write.write_u64_native(self.vmaddr);
-        write.write_u64_native(self.vmsize);
-        write.write_u64_native(self.fileoff);
-        write.write_u64_native(self.filesize);
-        write.write_i32_native(self.maxprot);
-        write.write_i32_native(self.initprot);
-        write.write_u32_native(self.nsects);
-        write.write_u32_native(self.flags);
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.cmd, endian)?;
+        write.write_u32_in(self.cmdsize, endian)?;
+        write.write_fixed_size_string(self.segname.as_str(), 16)?;
+        write.write_u64_in(self.vmaddr, endian)?;
+        write.write_u64_in(self.vmsize, endian)?;
+        write.write_u64_in(self.fileoff, endian)?;
+        write.write_u64_in(self.filesize, endian)?;
+        write.write_i32_in(self.maxprot, endian)?;
+        write.write_i32_in(self.initprot, endian)?;
+        write.write_u32_in(self.nsects, endian)?;
+        write.write_u32_in(self.flags, endian)?;
+        Ok(())
     }
 }
 
@@ -117,25 +138,23 @@ pub struct Section64 {
 impl Section64 {
     pub const SIZE: u32 = 0x50; // 80
 
-    pub fn read_from_in<R: Read>(read: &mut R, endian: Endian) -> Self {
-        let sectname = read.read_fixed_size_string(16);
-        let segname = read.read_fixed_size_string(16);
-        let addr = read.read_u64_in(endian);
-        let size = read.read_u64_in(endian);
-        let offset = read.read_u32_in(endian);
-        let align = read.read_u32_in(endian);
-        let reloff = read.read_u32_in(endian);
-        let nreloc = read.read_u32_in(endian);
-
-        let flags_n = read.read_u32_in(endian);
-        let sect_type = SectionType::from_u32(flags_n & SectionType::BIT_MASK);
-        let sect_attrs = SectionAttrs::from_u32(flags_n & SectionAttrs::BIT_MASK);
-
-        let reserved1 = read.read_u32_in(endian);
-        let reserved2 = read.read_u32_in(endian);
-        let reserved3 = read.read_u32_in(endian);
-
-        Section64 {
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let sectname = read.read_fixed_size_string(16)?;
+        let segname = read.read_fixed_size_string(16)?;
+        let addr = read.read_u64_in(endian)?;
+        let size = read.read_u64_in(endian)?;
+        let offset = read.read_u32_in(endian)?;
+        let align = read.read_u32_in(endian)?;
+        let reloff = read.read_u32_in(endian)?;
+        let nreloc = read.read_u32_in(endian)?;
+
+        let flags = read_section_flags_in(read, endian)?;
+
+        let reserved1 = read.read_u32_in(endian)?;
+        let reserved2 = read.read_u32_in(endian)?;
+        let reserved3 = read.read_u32_in(endian)?;
+
+        Ok(Section64 {
             sectname,
             segname,
             addr,
@@ -144,56 +163,149 @@ impl Section64 {
             align,
             reloff,
             nreloc,
-            flags: (sect_attrs, sect_type),
+            flags,
             reserved1,
             reserved2,
             reserved3,
-        }
+        })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
-        write.write_fixed_size_string(self.sectname.as_str(), 16);
-        write.write_fixed_size_string(self.segname.as_str(), 16);
-        write.write_u64_native(self.addr);
-        write.write_u64_native(self.size);
-        write.write_u32_native(self.offset);
-        write.write_u32_native(self.align);
-        write.write_u32_native(self.reloff);
-        write.write_u32_native(self.nreloc);
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_fixed_size_string(self.sectname.as_str(), 16)?;
+        write.write_fixed_size_string(self.segname.as_str(), 16)?;
+        write.write_u64_in(self.addr, endian)?;
+        write.write_u64_in(self.size, endian)?;
+        write.write_u32_in(self.offset, endian)?;
+        write.write_u32_in(self.align, endian)?;
+        write.write_u32_in(self.reloff, endian)?;
+        write.write_u32_in(self.nreloc, endian)?;
 
         let flags_n = self.flags.0.to_u32() | self.flags.1.to_u32();
-        write.write_u32_native(flags_n);
+        write.write_u32_in(flags_n, endian)?;
 
-        write.write_u32_native(self.reserved1);
-        write.write_u32_native(self.reserved2);
-        write.write_u32_native(self.reserved3);
+        write.write_u32_in(self.reserved1, endian)?;
+        write.write_u32_in(self.reserved2, endian)?;
+        write.write_u32_in(self.reserved3, endian)?;
+        Ok(())
     }
 }
 
-#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+/// Reads the `flags` field of a section, which is shared by `Section64` and `Section32`.
+pub(crate) fn read_section_flags_in<R: Read + Seek>(
+    read: &mut R,
+    endian: Endian,
+) -> Result<(SectionAttrs, SectionType)> {
+    let flags_n = read.read_u32_in(endian)?;
+    let sect_type = SectionType::from_u32(flags_n & SectionType::BIT_MASK);
+    let sect_attrs = SectionAttrs::from_u32(flags_n & SectionAttrs::BIT_MASK);
+    Ok((sect_attrs, sect_type))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SectionType {
-    Regular = 0x0,
-    Zerofill = 0x1,
-    CstringLiterals = 0x2,
-    FourByteLiterals = 0x3,
-    EightByteLiterals = 0x4,
-    LiteralPointers = 0x5,
-    Coalesced = 0xB,
+    Regular,
+    Zerofill,
+    CstringLiterals,
+    FourByteLiterals,
+    EightByteLiterals,
+    LiteralPointers,
+    NonLazySymbolPointers,
+    LazySymbolPointers,
+    SymbolStubs,
+    ModInitFuncPointers,
+    ModTermFuncPointers,
+    Coalesced,
+    GbZerofill,
+    Interposing,
+    SixteenByteLiterals,
+    DtraceDof,
+    LazyDylibSymbolPointers,
+    ThreadLocalRegular,
+    ThreadLocalZerofill,
+    ThreadLocalVariables,
+    ThreadLocalVariablePointers,
+    ThreadLocalInitFunctionPointers,
+    InitFuncOffsets,
+    /// Keeps the value as is.
+    Unknown(u32),
 }
 
 impl SectionType {
     pub const BIT_MASK: u32 = 0x000000ff;
 
     pub fn from_u32(n: u32) -> Self {
-        FromPrimitive::from_u32(n).unwrap_or_else(|| panic!("Unsupported section type 0x{:X}", n))
+        match n {
+            0x0 => SectionType::Regular,
+            0x1 => SectionType::Zerofill,
+            0x2 => SectionType::CstringLiterals,
+            0x3 => SectionType::FourByteLiterals,
+            0x4 => SectionType::EightByteLiterals,
+            0x5 => SectionType::LiteralPointers,
+            0x6 => SectionType::NonLazySymbolPointers,
+            0x7 => SectionType::LazySymbolPointers,
+            0x8 => SectionType::SymbolStubs,
+            0x9 => SectionType::ModInitFuncPointers,
+            0xa => SectionType::ModTermFuncPointers,
+            0xb => SectionType::Coalesced,
+            0xc => SectionType::GbZerofill,
+            0xd => SectionType::Interposing,
+            0xe => SectionType::SixteenByteLiterals,
+            0xf => SectionType::DtraceDof,
+            0x10 => SectionType::LazyDylibSymbolPointers,
+            0x11 => SectionType::ThreadLocalRegular,
+            0x12 => SectionType::ThreadLocalZerofill,
+            0x13 => SectionType::ThreadLocalVariables,
+            0x14 => SectionType::ThreadLocalVariablePointers,
+            0x15 => SectionType::ThreadLocalInitFunctionPointers,
+            0x16 => SectionType::InitFuncOffsets,
+            n => SectionType::Unknown(n),
+        }
     }
 
     pub fn to_u32(self) -> u32 {
-        self as u32
+        match self {
+            SectionType::Regular => 0x0,
+            SectionType::Zerofill => 0x1,
+            SectionType::CstringLiterals => 0x2,
+            SectionType::FourByteLiterals => 0x3,
+            SectionType::EightByteLiterals => 0x4,
+            SectionType::LiteralPointers => 0x5,
+            SectionType::NonLazySymbolPointers => 0x6,
+            SectionType::LazySymbolPointers => 0x7,
+            SectionType::SymbolStubs => 0x8,
+            SectionType::ModInitFuncPointers => 0x9,
+            SectionType::ModTermFuncPointers => 0xa,
+            SectionType::Coalesced => 0xb,
+            SectionType::GbZerofill => 0xc,
+            SectionType::Interposing => 0xd,
+            SectionType::SixteenByteLiterals => 0xe,
+            SectionType::DtraceDof => 0xf,
+            SectionType::LazyDylibSymbolPointers => 0x10,
+            SectionType::ThreadLocalRegular => 0x11,
+            SectionType::ThreadLocalZerofill => 0x12,
+            SectionType::ThreadLocalVariables => 0x13,
+            SectionType::ThreadLocalVariablePointers => 0x14,
+            SectionType::ThreadLocalInitFunctionPointers => 0x15,
+            SectionType::InitFuncOffsets => 0x16,
+            SectionType::Unknown(n) => n,
+        }
+    }
+
+    /// Zero-fill sections occupy no space in the file.
+    pub fn is_zerofill(self) -> bool {
+        matches!(
+            self,
+            SectionType::Zerofill | SectionType::GbZerofill | SectionType::ThreadLocalZerofill
+        )
     }
 }
 
 #[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
 pub enum SectionAttr {
     /// This section contains only executable machine instructions. The standard tools set this
     /// flag for the sections __TEXT,__text, __TEXT,__symbol_stub, and __TEXT,__picsymbol_stub.
@@ -203,8 +315,12 @@ pub enum SectionAttr {
     NoToc = 0x40000000,
     /// ok to strip static symbols in this section in files with the MH_DYLDLINK flag
     StripStaticSyms = 0x20000000,
+    /// no dead stripping
+    NoDeadStrip = 0x10000000,
     /// blocks are live if they reference live blocks
     LiveSupport = 0x08000000,
+    /// Used with i386 code stubs written on by dyld
+    SelfModifyingCode = 0x04000000,
     /// If a segment contains any sections marked with S_ATTR_DEBUG then all
     /// sections in that segment must have this attribute.  No section other than
     /// a section marked with this attribute may reference the contents of this
@@ -222,9 +338,8 @@ pub enum SectionAttr {
 }
 
 impl SectionAttr {
-    pub fn from_u32(n: u32) -> Self {
+    pub fn from_u32(n: u32) -> Option<Self> {
         FromPrimitive::from_u32(n)
-            .unwrap_or_else(|| panic!("Unsupported section attribute 0x{:X}", n))
     }
 
     pub fn to_u32(self) -> u32 {
@@ -232,19 +347,24 @@ impl SectionAttr {
     }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Default)]
 pub struct SectionAttrs {
     attrs: Vec<SectionAttr>,
+    /// Bits which do not correspond to any `SectionAttr`.
+    unknown: u32,
 }
 
 impl SectionAttrs {
     pub const BIT_MASK: u32 = 0xffffff00;
 
     pub fn new() -> SectionAttrs {
-        SectionAttrs { attrs: Vec::new() }
+        SectionAttrs {
+            attrs: Vec::new(),
+            unknown: 0,
+        }
     }
 
-    pub fn iter<'a>(&'a self) -> impl Iterator<Item = SectionAttr> + 'a {
+    pub fn iter(&self) -> impl Iterator<Item = SectionAttr> + '_ {
         self.attrs.iter().copied()
     }
 
@@ -252,19 +372,28 @@ impl SectionAttrs {
         self.attrs.push(attr);
     }
 
+    /// Bits which do not correspond to any `SectionAttr`. They are written back as is.
+    pub fn unknown(&self) -> u32 {
+        self.unknown
+    }
+
+    /// Unknown bits are kept and can be retrieved by `unknown`.
     pub fn from_u32(flags: u32) -> Self {
         let mut attrs = SectionAttrs::new();
         for i in 8..=31 {
             let attr_n = flags & (1 << i);
             if attr_n != 0 {
-                attrs.push(SectionAttr::from_u32(attr_n));
+                match SectionAttr::from_u32(attr_n) {
+                    Some(attr) => attrs.push(attr),
+                    None => attrs.unknown |= attr_n,
+                }
             }
         }
         attrs
     }
 
     pub fn to_u32(&self) -> u32 {
-        let mut n = 0;
+        let mut n = self.unknown;
         for attr in self.attrs.iter() {
             n |= attr.to_u32();
         }
@@ -274,13 +403,19 @@ impl SectionAttrs {
 
 impl fmt::Debug for SectionAttrs {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.debug_set().entries(self.attrs.iter()).finish()
+        let mut set = fmt.debug_set();
+        set.entries(self.attrs.iter());
+        if self.unknown != 0 {
+            set.entry(&format_args!("{:#x}", self.unknown));
+        }
+        set.finish()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Cursor;
 
     #[test]
     fn write_and_read_segment64_command() {
@@ -300,13 +435,34 @@ mod tests {
 
         let mut buf = Vec::new();
 
-        cmd.write_into(&mut buf);
+        cmd.write_into(&mut buf).unwrap();
 
         assert_eq!(buf.len(), SegmentCommand64::SIZE as usize);
 
-        let read_cmd = SegmentCommand64::read_from_in(&mut buf.as_slice(), Endian::NATIVE);
+        let read_cmd =
+            SegmentCommand64::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
 
         assert_eq!(read_cmd, cmd);
+
+        // names are written back as is if they fit, and rejected otherwise.
+        let named = SegmentCommand64 {
+            segname: "__DATA_CONSTé".to_string(),
+            ..cmd.clone()
+        };
+        let mut buf = Vec::new();
+        named.write_into(&mut buf).unwrap();
+        let read_cmd =
+            SegmentCommand64::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
+        assert_eq!(read_cmd, named);
+
+        let too_long = SegmentCommand64 {
+            segname: "__SEVENTEEN_BYTES".to_string(),
+            ..cmd
+        };
+        assert!(matches!(
+            too_long.write_into(&mut Vec::new()),
+            Err(Error::StringTooLong { size: 16, .. })
+        ));
     }
 
     #[test]
@@ -328,12 +484,29 @@ mod tests {
 
         let mut buf = Vec::new();
 
-        cmd.write_into(&mut buf);
+        cmd.write_into(&mut buf).unwrap();
 
         assert_eq!(buf.len(), Section64::SIZE as usize);
 
-        let read_cmd = Section64::read_from_in(&mut buf.as_slice(), Endian::NATIVE);
+        let read_cmd = Section64::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
 
         assert_eq!(read_cmd, cmd);
     }
+
+    #[test]
+    fn keep_unknown_section_flags() {
+        let flags_n = 0x0010_0400 | 0x42;
+        let mut buf = Vec::new();
+        buf.write_u32_in(flags_n, Endian::NATIVE).unwrap();
+
+        let (attrs, sect_type) =
+            read_section_flags_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
+        assert_eq!(sect_type, SectionType::Unknown(0x42));
+        assert_eq!(
+            attrs.iter().collect::<Vec<_>>(),
+            [SectionAttr::SomeInstructions]
+        );
+        assert_eq!(attrs.unknown(), 0x0010_0000);
+        assert_eq!(attrs.to_u32() | sect_type.to_u32(), flags_n);
+    }
 }
@@ -1,5 +1,8 @@
-use crate::io::{Endian, ReadExt as _, WriteExt as _};
-use std::io::{Read, Write};
+use crate::{
+    error::{Error, Result},
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, Write};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SourceVersionCommand {
@@ -13,26 +16,41 @@ impl SourceVersionCommand {
 
     pub const SIZE: u32 = 0x10;
 
-    pub fn read_from_in<R: Read>(read: &mut R, endian: Endian) -> Self {
-        let cmd = read.read_u32_in(endian);
-        assert_eq!(cmd, Self::TYPE);
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let offset = read.position()?;
 
-        let cmdsize = read.read_u32_in(endian);
-        assert_eq!(cmdsize, Self::SIZE);
+        let cmd = read.read_u32_in(endian)?;
+        if cmd != Self::TYPE {
+            return Err(read.unknown_value(4, "load command", cmd.into()));
+        }
+
+        let cmdsize = read.read_u32_in(endian)?;
+        if cmdsize != Self::SIZE {
+            return Err(Error::InconsistentCmdSize {
+                offset,
+                cmd,
+                cmdsize,
+            });
+        }
 
-        let version = Version(read.read_u64_in(endian));
+        let version = Version(read.read_u64_in(endian)?);
 
-        SourceVersionCommand {
+        Ok(SourceVersionCommand {
             cmd,
             cmdsize,
             version,
-        }
+        })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
-        write.write_u32_native(self.cmd);
-        write.write_u32_native(self.cmdsize);
-        write.write_u64_native(self.version.0);
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.cmd, endian)?;
+        write.write_u32_in(self.cmdsize, endian)?;
+        write.write_u64_in(self.version.0, endian)?;
+        Ok(())
     }
 }
 
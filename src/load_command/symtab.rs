@@ -1,5 +1,8 @@
-use crate::io::{Endian, ReadExt as _, WriteExt as _};
-use std::io::{Read, Write};
+use crate::{
+    error::{Error, Result},
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, Write};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SymtabCommand {
@@ -21,41 +24,57 @@ impl SymtabCommand {
 
     pub const SIZE: u32 = 0x18; // 24
 
-    pub fn read_from_in<R: Read>(read: &mut R, endian: Endian) -> Self {
-        let cmd = read.read_u32_in(endian);
-        assert_eq!(cmd, SymtabCommand::TYPE);
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let offset = read.position()?;
 
-        let cmdsize = read.read_u32_in(endian);
-        assert_eq!(cmdsize, SymtabCommand::SIZE);
+        let cmd = read.read_u32_in(endian)?;
+        if cmd != SymtabCommand::TYPE {
+            return Err(read.unknown_value(4, "load command", cmd.into()));
+        }
+
+        let cmdsize = read.read_u32_in(endian)?;
+        if cmdsize != SymtabCommand::SIZE {
+            return Err(Error::InconsistentCmdSize {
+                offset,
+                cmd,
+                cmdsize,
+            });
+        }
 
-        let symoff = read.read_u32_in(endian);
-        let nsyms = read.read_u32_in(endian);
-        let stroff = read.read_u32_in(endian);
-        let strsize = read.read_u32_in(endian);
+        let symoff = read.read_u32_in(endian)?;
+        let nsyms = read.read_u32_in(endian)?;
+        let stroff = read.read_u32_in(endian)?;
+        let strsize = read.read_u32_in(endian)?;
 
-        SymtabCommand {
+        Ok(SymtabCommand {
             cmd,
             cmdsize,
             symoff,
             nsyms,
             stroff,
             strsize,
-        }
+        })
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
-        write.write_u32_native(self.cmd);
-        write.write_u32_native(self.cmdsize);
-        write.write_u32_native(self.symoff);
-        write.write_u32_native(self.nsyms);
-        write.write_u32_native(self.stroff);
-        write.write_u32_native(self.strsize);
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.cmd, endian)?;
+        write.write_u32_in(self.cmdsize, endian)?;
+        write.write_u32_in(self.symoff, endian)?;
+        write.write_u32_in(self.nsyms, endian)?;
+        write.write_u32_in(self.stroff, endian)?;
+        write.write_u32_in(self.strsize, endian)?;
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Cursor;
 
     #[test]
     fn write_and_read_symtab_command() {
@@ -70,12 +89,30 @@ mod tests {
 
         let mut buf = Vec::new();
 
-        cmd.write_into(&mut buf);
+        cmd.write_into(&mut buf).unwrap();
 
         assert_eq!(buf.len(), SymtabCommand::SIZE as usize);
 
-        let read_cmd = SymtabCommand::read_from_in(&mut buf.as_slice(), Endian::NATIVE);
+        let read_cmd = SymtabCommand::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
+
+        assert_eq!(read_cmd, cmd);
+    }
+
+    #[test]
+    fn write_and_read_symtab_command_reverse_endian() {
+        let cmd = SymtabCommand {
+            cmd: SymtabCommand::TYPE,
+            cmdsize: SymtabCommand::SIZE,
+            symoff: 0x1000,
+            nsyms: 3,
+            stroff: 0x1030,
+            strsize: 0x20,
+        };
+
+        let mut buf = Vec::new();
+        cmd.write_into_in(&mut buf, Endian::REVERSE).unwrap();
 
+        let read_cmd = SymtabCommand::read_from_in(&mut Cursor::new(buf), Endian::REVERSE).unwrap();
         assert_eq!(read_cmd, cmd);
     }
 }
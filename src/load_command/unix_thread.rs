@@ -1,5 +1,9 @@
-use crate::io::{Endian, ReadExt as _, WriteExt as _};
-use std::io::{Read, Write};
+use crate::{
+    error::{Error, Result},
+    header::CpuType,
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, Write};
 
 /*
  * Thread commands contain machine-specific data structures suitable for
@@ -24,88 +28,174 @@ use std::io::{Read, Write};
  */
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UnixThreadCommand {
+    /// `UnixThreadCommand::TYPE` or `UnixThreadCommand::TYPE_THREAD`
     pub cmd: u32,
     pub cmdsize: u32,
-    pub flavor: Flavor,
-    /// size of the thread state data, in number of 32-bit integers. The thread state data
-    /// structure must be fully padded to 32-bit alignment.
-    pub count: u32,
-    pub state: ThreadState,
+    /// Thread states of every flavor, in the order they appear.
+    pub states: Vec<ThreadState>,
 }
 
 impl UnixThreadCommand {
+    /// LC_UNIXTHREAD
     pub const TYPE: u32 = 0x5;
+    /// LC_THREAD, which is the same as LC_UNIXTHREAD except that no stack is created.
+    pub const TYPE_THREAD: u32 = 0x4;
 
-    pub fn read_from_in<R: Read>(read: &mut R, endian: Endian) -> Self {
-        let cmd = read.read_u32_in(endian);
-        let cmdsize = read.read_u32_in(endian);
-        let flavor = Flavor::read_from_in(read, endian);
-        let count = read.read_u32_in(endian);
+    /// Creates a command of `states`, computing `cmdsize` from them.
+    pub fn new(cmd: u32, states: Vec<ThreadState>) -> Self {
+        UnixThreadCommand {
+            cmd,
+            cmdsize: cmd_size(&states),
+            states,
+        }
+    }
 
-        let state = match flavor {
-            Flavor::ThreadStateX86_64 => {
-                let state = StateX86_64::read_from_in(read, endian);
-                ThreadState::X86_64(state)
-            }
-            Flavor::Unknown(_) => {
-                let mut state = Vec::with_capacity(count as usize * 4);
-                state.resize(count as usize * 4, 0);
-                read.read_exact(&mut state).unwrap();
-                ThreadState::Unknown(state)
-            }
-        };
+    /// Creates a command which has a single x86_64 thread state.
+    pub fn x86_64(state: StateX86_64) -> Self {
+        Self::new(Self::TYPE, vec![ThreadState::X86_64(state)])
+    }
 
-        UnixThreadCommand {
+    /// Creates a command which has a single arm64 thread state.
+    pub fn arm64(state: StateArm64) -> Self {
+        Self::new(Self::TYPE, vec![ThreadState::Arm64(state)])
+    }
+
+    /// Reads flavors until `cmdsize` is used up. Since flavor numbers are specific to each cpu,
+    /// only the thread states of `cpu_type` are decoded and the others are kept as is.
+    pub fn read_from_in<R: Read + Seek>(
+        read: &mut R,
+        endian: Endian,
+        cpu_type: CpuType,
+    ) -> Result<Self> {
+        let offset = read.position()?;
+        let cmd = read.read_u32_in(endian)?;
+        let cmdsize = read.read_u32_in(endian)?;
+        let inconsistent = Error::InconsistentCmdSize {
+            offset,
             cmd,
             cmdsize,
-            flavor,
-            count,
-            state,
+        };
+        if cmdsize < 8 {
+            return Err(inconsistent);
+        }
+
+        let mut states = Vec::new();
+        let mut remaining = cmdsize - 8;
+        while remaining > 0 {
+            if remaining < 8 {
+                return Err(inconsistent);
+            }
+            let flavor = read.read_u32_in(endian)?;
+            let count = read.read_u32_in(endian)?;
+            let size = match count.checked_mul(4) {
+                Some(size) if size <= remaining - 8 => size,
+                _ => return Err(inconsistent),
+            };
+            remaining -= 8 + size;
+
+            let state = match (cpu_type, flavor, count) {
+                (CpuType::X86_64(..), StateX86_64::FLAVOR, StateX86_64::COUNT) => {
+                    ThreadState::X86_64(StateX86_64::read_from_in(read, endian)?)
+                }
+                (CpuType::Arm64(..), StateArm64::FLAVOR, StateArm64::COUNT) => {
+                    ThreadState::Arm64(StateArm64::read_from_in(read, endian)?)
+                }
+                _ => ThreadState::Unknown {
+                    flavor,
+                    data: read.read_bytes(size as usize)?,
+                },
+            };
+            states.push(state);
         }
+
+        Ok(UnixThreadCommand {
+            cmd,
+            cmdsize,
+            states,
+        })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
-        write.write_u32_native(self.cmd);
-        write.write_u32_native(self.cmdsize);
-        self.flavor.write_into(write);
-        write.write_u32_native(self.count);
+    /// Fails if `cmdsize` does not match the thread states, which would break the following
+    /// load commands.
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        let size = cmd_size(&self.states);
+        if size != self.cmdsize {
+            return Err(Error::CmdSizeMismatch {
+                cmd: self.cmd,
+                cmdsize: self.cmdsize,
+                size,
+            });
+        }
 
-        match &self.state {
-            ThreadState::X86_64(state) => state.write_into(write),
-            ThreadState::Unknown(state) => write.write_all(&state).unwrap(),
+        write.write_u32_in(self.cmd, endian)?;
+        write.write_u32_in(self.cmdsize, endian)?;
+        for state in self.states.iter() {
+            write.write_u32_in(state.flavor(), endian)?;
+            write.write_u32_in(state.count(), endian)?;
+            match state {
+                ThreadState::X86_64(state) => state.write_into_in(write, endian)?,
+                ThreadState::Arm64(state) => state.write_into_in(write, endian)?,
+                ThreadState::Unknown { data, .. } => write.write_all(data)?,
+            }
         }
+        Ok(())
     }
 }
 
+/// Size of a thread command, each state being preceded by its flavor and count.
+fn cmd_size(states: &[ThreadState]) -> u32 {
+    8 + states
+        .iter()
+        .map(|state| 8 + state.count() * 4)
+        .sum::<u32>()
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Flavor {
-    ThreadStateX86_64,
-    Unknown(u32),
+pub enum ThreadState {
+    /// x86_THREAD_STATE64 of x86_64
+    X86_64(StateX86_64),
+    /// ARM_THREAD_STATE64 of arm64
+    Arm64(StateArm64),
+    /// Any other flavor, including the states of 32-bit cpus, kept as is. `data` is a multiple
+    /// of 4 bytes.
+    Unknown { flavor: u32, data: Vec<u8> },
 }
 
-impl Flavor {
-    pub fn read_from_in<R: Read>(read: &mut R, endian: Endian) -> Self {
-        match read.read_u32_in(endian) {
-            4 => Flavor::ThreadStateX86_64,
-            n => Flavor::Unknown(n),
+impl ThreadState {
+    pub fn flavor(&self) -> u32 {
+        match self {
+            ThreadState::X86_64(_) => StateX86_64::FLAVOR,
+            ThreadState::Arm64(_) => StateArm64::FLAVOR,
+            ThreadState::Unknown { flavor, .. } => *flavor,
         }
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
+    /// Size of the state in number of 32-bit integers.
+    pub fn count(&self) -> u32 {
         match self {
-            Flavor::ThreadStateX86_64 => write.write_u32_native(4),
-            Flavor::Unknown(n) => write.write_u32_native(*n),
+            ThreadState::X86_64(_) => StateX86_64::COUNT,
+            ThreadState::Arm64(_) => StateArm64::COUNT,
+            ThreadState::Unknown { data, .. } => (data.len() / 4) as u32,
         }
     }
-}
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum ThreadState {
-    X86_64(StateX86_64),
-    Unknown(Vec<u8>),
+    /// Returns the program counter, which is where the thread starts running.
+    ///
+    /// Returns `None` for unknown flavors.
+    pub fn pc(&self) -> Option<u64> {
+        match self {
+            ThreadState::X86_64(state) => Some(state.__rip),
+            ThreadState::Arm64(state) => Some(state.__pc),
+            ThreadState::Unknown { .. } => None,
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct StateX86_64 {
     pub __rax: u64,
     pub __rbx: u64,
@@ -131,53 +221,206 @@ pub struct StateX86_64 {
 }
 
 impl StateX86_64 {
-    pub fn read_from_in<R: Read>(read: &mut R, endian: Endian) -> Self {
-        StateX86_64 {
-            __rax: read.read_u64_in(endian),
-            __rbx: read.read_u64_in(endian),
-            __rcx: read.read_u64_in(endian),
-            __rdx: read.read_u64_in(endian),
-            __rdi: read.read_u64_in(endian),
-            __rsi: read.read_u64_in(endian),
-            __rbp: read.read_u64_in(endian),
-            __rsp: read.read_u64_in(endian),
-            __r8: read.read_u64_in(endian),
-            __r9: read.read_u64_in(endian),
-            __r10: read.read_u64_in(endian),
-            __r11: read.read_u64_in(endian),
-            __r12: read.read_u64_in(endian),
-            __r13: read.read_u64_in(endian),
-            __r14: read.read_u64_in(endian),
-            __r15: read.read_u64_in(endian),
-            __rip: read.read_u64_in(endian),
-            __rflags: read.read_u64_in(endian),
-            __cs: read.read_u64_in(endian),
-            __fs: read.read_u64_in(endian),
-            __gs: read.read_u64_in(endian),
+    /// x86_THREAD_STATE64
+    pub const FLAVOR: u32 = 4;
+
+    /// Size of this state in number of 32-bit integers.
+    pub const COUNT: u32 = 42;
+
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        Ok(StateX86_64 {
+            __rax: read.read_u64_in(endian)?,
+            __rbx: read.read_u64_in(endian)?,
+            __rcx: read.read_u64_in(endian)?,
+            __rdx: read.read_u64_in(endian)?,
+            __rdi: read.read_u64_in(endian)?,
+            __rsi: read.read_u64_in(endian)?,
+            __rbp: read.read_u64_in(endian)?,
+            __rsp: read.read_u64_in(endian)?,
+            __r8: read.read_u64_in(endian)?,
+            __r9: read.read_u64_in(endian)?,
+            __r10: read.read_u64_in(endian)?,
+            __r11: read.read_u64_in(endian)?,
+            __r12: read.read_u64_in(endian)?,
+            __r13: read.read_u64_in(endian)?,
+            __r14: read.read_u64_in(endian)?,
+            __r15: read.read_u64_in(endian)?,
+            __rip: read.read_u64_in(endian)?,
+            __rflags: read.read_u64_in(endian)?,
+            __cs: read.read_u64_in(endian)?,
+            __fs: read.read_u64_in(endian)?,
+            __gs: read.read_u64_in(endian)?,
+        })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u64_in(self.__rax, endian)?;
+        write.write_u64_in(self.__rbx, endian)?;
+        write.write_u64_in(self.__rcx, endian)?;
+        write.write_u64_in(self.__rdx, endian)?;
+        write.write_u64_in(self.__rdi, endian)?;
+        write.write_u64_in(self.__rsi, endian)?;
+        write.write_u64_in(self.__rbp, endian)?;
+        write.write_u64_in(self.__rsp, endian)?;
+        write.write_u64_in(self.__r8, endian)?;
+        write.write_u64_in(self.__r9, endian)?;
+        write.write_u64_in(self.__r10, endian)?;
+        write.write_u64_in(self.__r11, endian)?;
+        write.write_u64_in(self.__r12, endian)?;
+        write.write_u64_in(self.__r13, endian)?;
+        write.write_u64_in(self.__r14, endian)?;
+        write.write_u64_in(self.__r15, endian)?;
+        write.write_u64_in(self.__rip, endian)?;
+        write.write_u64_in(self.__rflags, endian)?;
+        write.write_u64_in(self.__cs, endian)?;
+        write.write_u64_in(self.__fs, endian)?;
+        write.write_u64_in(self.__gs, endian)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StateArm64 {
+    /// general purpose registers x0-x28
+    pub __x: [u64; 29],
+    /// frame pointer x29
+    pub __fp: u64,
+    /// link register x30
+    pub __lr: u64,
+    /// stack pointer x31
+    pub __sp: u64,
+    /// program counter
+    pub __pc: u64,
+    /// current program status register
+    pub __cpsr: u32,
+    /// same size for 32-bit or 64-bit clients
+    pub __pad: u32,
+}
+
+impl StateArm64 {
+    /// ARM_THREAD_STATE64
+    pub const FLAVOR: u32 = 6;
+
+    /// Size of this state in number of 32-bit integers.
+    pub const COUNT: u32 = 68;
+
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let mut __x = [0; 29];
+        for x in __x.iter_mut() {
+            *x = read.read_u64_in(endian)?;
         }
+        Ok(StateArm64 {
+            __x,
+            __fp: read.read_u64_in(endian)?,
+            __lr: read.read_u64_in(endian)?,
+            __sp: read.read_u64_in(endian)?,
+            __pc: read.read_u64_in(endian)?,
+            __cpsr: read.read_u32_in(endian)?,
+            __pad: read.read_u32_in(endian)?,
+        })
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
-        write.write_u64_native(self.__rax);
-        write.write_u64_native(self.__rbx);
-        write.write_u64_native(self.__rcx);
-        write.write_u64_native(self.__rdx);
-        write.write_u64_native(self.__rdi);
-        write.write_u64_native(self.__rsi);
-        write.write_u64_native(self.__rbp);
-        write.write_u64_native(self.__rsp);
-        write.write_u64_native(self.__r8);
-        write.write_u64_native(self.__r9);
-        write.write_u64_native(self.__r10);
-        write.write_u64_native(self.__r11);
-        write.write_u64_native(self.__r12);
-        write.write_u64_native(self.__r13);
-        write.write_u64_native(self.__r14);
-        write.write_u64_native(self.__r15);
-        write.write_u64_native(self.__rip);
-        write.write_u64_native(self.__rflags);
-        write.write_u64_native(self.__cs);
-        write.write_u64_native(self.__fs);
-        write.write_u64_native(self.__gs);
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        for x in self.__x.iter() {
+            write.write_u64_in(*x, endian)?;
+        }
+        write.write_u64_in(self.__fp, endian)?;
+        write.write_u64_in(self.__lr, endian)?;
+        write.write_u64_in(self.__sp, endian)?;
+        write.write_u64_in(self.__pc, endian)?;
+        write.write_u32_in(self.__cpsr, endian)?;
+        write.write_u32_in(self.__pad, endian)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{CpuCapabilities, CpuSubTypeArm64, CpuSubTypeX86_64};
+    use std::io::Cursor;
+
+    const X86_64: CpuType = CpuType::X86_64(CpuSubTypeX86_64::All, CpuCapabilities::NONE);
+    const ARM64: CpuType = CpuType::Arm64(CpuSubTypeArm64::All, CpuCapabilities::NONE);
+
+    #[test]
+    fn write_and_read_thread_command_with_flavors() {
+        let cmd = UnixThreadCommand::new(
+            UnixThreadCommand::TYPE_THREAD,
+            vec![
+                ThreadState::X86_64(StateX86_64 {
+                    __rip: 0x1000,
+                    ..StateX86_64::default()
+                }),
+                // x86_EXCEPTION_STATE64
+                ThreadState::Unknown {
+                    flavor: 6,
+                    data: vec![0xab; 16],
+                },
+            ],
+        );
+        assert_eq!(cmd.cmdsize, 8 + 8 + 168 + 8 + 16);
+
+        let mut buf = Vec::new();
+        cmd.write_into_in(&mut buf, Endian::REVERSE).unwrap();
+        assert_eq!(buf.len(), cmd.cmdsize as usize);
+        // followed by another command, which must not be consumed.
+        buf.extend_from_slice(&[0xff; 8]);
+
+        let mut read = Cursor::new(&buf);
+        let read_cmd = UnixThreadCommand::read_from_in(&mut read, Endian::REVERSE, X86_64).unwrap();
+        assert_eq!(read_cmd, cmd);
+        assert_eq!(read.position(), u64::from(cmd.cmdsize));
+
+        // flavor 4 of arm64 is not x86_THREAD_STATE64.
+        let read_cmd =
+            UnixThreadCommand::read_from_in(&mut Cursor::new(&buf), Endian::REVERSE, ARM64)
+                .unwrap();
+        assert!(matches!(
+            read_cmd.states[0],
+            ThreadState::Unknown { flavor: 4, .. }
+        ));
+        assert_eq!(read_cmd.states[0].pc(), None);
+
+        // a cmdsize which ends in the middle of a state
+        (&mut buf[4..8])
+            .write_u32_in(cmd.cmdsize - 4, Endian::REVERSE)
+            .unwrap();
+        assert!(matches!(
+            UnixThreadCommand::read_from_in(&mut Cursor::new(&buf), Endian::REVERSE, X86_64),
+            Err(Error::InconsistentCmdSize { offset: 0, .. })
+        ));
+
+        let mut edited = cmd;
+        edited.states.pop();
+        assert!(matches!(
+            edited.write_into(&mut Vec::new()),
+            Err(Error::CmdSizeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn write_and_read_arm64_thread_state() {
+        let cmd = UnixThreadCommand::arm64(StateArm64 {
+            __pc: 0x1_0000_3f50,
+            __x: [7; 29],
+            ..StateArm64::default()
+        });
+        assert_eq!(cmd.cmdsize, 16 + 272);
+
+        let mut buf = Vec::new();
+        cmd.write_into(&mut buf).unwrap();
+        let read_cmd =
+            UnixThreadCommand::read_from_in(&mut Cursor::new(buf), Endian::NATIVE, ARM64).unwrap();
+        assert_eq!(read_cmd, cmd);
+        assert_eq!(read_cmd.states[0].pc(), Some(0x1_0000_3f50));
     }
 }
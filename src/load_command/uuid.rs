@@ -1,5 +1,8 @@
-use crate::io::{Endian, ReadExt as _, WriteExt as _};
-use std::io::{Read, Write};
+use crate::{
+    error::{Error, Result},
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, Write};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UuidCommand {
@@ -13,26 +16,36 @@ impl UuidCommand {
 
     pub const SIZE: u32 = 0x18; // 24
 
-    pub fn read_from_in<R: Read>(read: &mut R, endian: Endian) -> Self {
-        let cmd = read.read_u32_in(endian);
-        assert_eq!(cmd, Self::TYPE);
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let offset = read.position()?;
 
-        let cmdsize = read.read_u32_in(endian);
-        assert_eq!(cmdsize, Self::SIZE);
-
-        let mut uuid = [0; 16];
-        read.read_exact(&mut uuid).unwrap();
+        let cmd = read.read_u32_in(endian)?;
+        if cmd != Self::TYPE {
+            return Err(read.unknown_value(4, "load command", cmd.into()));
+        }
 
-        UuidCommand {
-            cmd,
-            cmdsize,
-            uuid,
+        let cmdsize = read.read_u32_in(endian)?;
+        if cmdsize != Self::SIZE {
+            return Err(Error::InconsistentCmdSize {
+                offset,
+                cmd,
+                cmdsize,
+            });
         }
+
+        let uuid = read.read_byte_array::<16>()?;
+
+        Ok(UuidCommand { cmd, cmdsize, uuid })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
-        write.write_u32_native(self.cmd);
-        write.write_u32_native(self.cmdsize);
-        write.write_all(&self.uuid).unwrap();
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.cmd, endian)?;
+        write.write_u32_in(self.cmdsize, endian)?;
+        write.write_all(&self.uuid)?;
+        Ok(())
     }
 }
@@ -1,7 +1,8 @@
-use crate::io::{Endian, ReadExt as _, WriteExt as _};
-use num_derive::FromPrimitive;
-use num_traits::FromPrimitive;
-use std::io::{Read, Write};
+use crate::{
+    error::Result,
+    io::{Endian, ReadExt as _, WriteExt as _},
+};
+use std::io::{Read, Seek, Write};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct NList64 {
@@ -34,29 +35,84 @@ impl NList64 {
     pub const NO_SECT: u8 = 0;
     pub const MAX_SECT: u8 = 255;
 
-    pub fn read_from_in<R: Read>(read: &mut R, endian: Endian) -> Self {
-        let n_strx = read.read_u32_in(endian);
-        let n_type_n = read.read_u8();
-        let n_type = NTypeField::from_u8(n_type_n);
-        let n_sect = read.read_u8();
-        let n_desc = read.read_u16_in(endian);
-        let n_value = read.read_u64_in(endian);
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let n_strx = read.read_u32_in(endian)?;
+        let n_type = NTypeField::from_u8(read.read_u8()?);
+        let n_sect = read.read_u8()?;
+        let n_desc = read.read_u16_in(endian)?;
+        let n_value = read.read_u64_in(endian)?;
 
-        NList64 {
+        Ok(NList64 {
             n_strx,
             n_type,
             n_sect,
             n_desc,
             n_value,
-        }
+        })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
+    }
+
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.n_strx, endian)?;
+        write.write_u8(self.n_type.to_u8())?;
+        write.write_u8(self.n_sect)?;
+        write.write_u16_in(self.n_desc, endian)?;
+        write.write_u64_in(self.n_value, endian)?;
+        Ok(())
+    }
+}
+
+/// The 32-bit counterpart of `NList64`. Only `n_value` differs in size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NList32 {
+    /// An index into the string table. To specify an empty string (""), set this value to 0.
+    pub n_strx: u32,
+    /// this field really contains four fields.
+    pub n_type: NTypeField,
+    /// See `NList64::n_sect`.
+    pub n_sect: u8,
+    /// A 16-bit value providing additional information about the nature of this symbol.
+    pub n_desc: u16,
+    /// See `NList64::n_value`.
+    pub n_value: u32,
+}
+
+impl NList32 {
+    pub const SIZE: u32 = 0xc; // 12
+
+    pub const NO_SECT: u8 = 0;
+    pub const MAX_SECT: u8 = 255;
+
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<Self> {
+        let n_strx = read.read_u32_in(endian)?;
+        let n_type = NTypeField::from_u8(read.read_u8()?);
+        let n_sect = read.read_u8()?;
+        let n_desc = read.read_u16_in(endian)?;
+        let n_value = read.read_u32_in(endian)?;
+
+        Ok(NList32 {
+            n_strx,
+            n_type,
+            n_sect,
+            n_desc,
+            n_value,
+        })
+    }
+
+    pub fn write_into<W: Write>(&self, write: &mut W) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
     }
 
-    pub fn write_into<W: Write>(&self, write: &mut W) {
-        write.write_u32_native(self.n_strx);
-        write.write_u8(self.n_type.to_u8());
-        write.write_u8(self.n_sect);
-        write.write_u16_native(self.n_desc);
-        write.write_u64_native(self.n_value);
+    pub fn write_into_in<W: Write>(&self, write: &mut W, endian: Endian) -> Result<()> {
+        write.write_u32_in(self.n_strx, endian)?;
+        write.write_u8(self.n_type.to_u8())?;
+        write.write_u8(self.n_sect)?;
+        write.write_u16_in(self.n_desc, endian)?;
+        write.write_u32_in(self.n_value, endian)?;
+        Ok(())
     }
 }
 
@@ -80,8 +136,7 @@ impl NTypeField {
     pub const N_EXT_MASK: u8 = 0x01;
 
     pub fn from_u8(n: u8) -> Self {
-        let n_stab = n & Self::N_STAB_MASK;
-        if n_stab == 0 {
+        if n & Self::N_STAB_MASK == 0 {
             let n_pext = n & Self::N_PEXT_MASK == Self::N_PEXT_MASK;
             let n_type = NType::from_u8(n & Self::N_TYPE_MASK);
             let n_ext = n & Self::N_EXT_MASK == Self::N_EXT_MASK;
@@ -91,7 +146,8 @@ impl NTypeField {
                 n_ext,
             }
         } else {
-            NTypeField::Stab(DebugSymbol::from_u8(n_stab))
+            // the whole byte is the type of a debugging entry.
+            NTypeField::Stab(DebugSymbol::from_u8(n))
         }
     }
 
@@ -101,61 +157,206 @@ impl NTypeField {
                 n_pext,
                 n_type,
                 n_ext,
-            } => n_pext as u8 * Self::N_PEXT_MASK | n_type.to_u8() | n_ext as u8 * Self::N_EXT_MASK,
+            } => {
+                (n_pext as u8 * Self::N_PEXT_MASK)
+                    | n_type.to_u8()
+                    | (n_ext as u8 * Self::N_EXT_MASK)
+            }
             NTypeField::Stab(stab) => stab.to_u8(),
         }
     }
 }
 
-#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NType {
     /// Undefined, n_sect == NO_SECT
     /// Undefined symbols are symbols referenced in this module but defined in a different module.
     /// n_value is 0.
-    Undf = 0x0,
+    Undf,
     /// Absolute, n_sect == NO_SECT
-    Abs = 0x2,
+    Abs,
     /// Defined in section number n_sect
-    Sect = 0xe,
+    Sect,
     /// Prebound undefined (defined in a dylib)
-    Pbud = 0xc,
+    Pbud,
     /// Indirect.
     /// If the type is NType::Indr then the symbol is defined to be the same as another symbol. In
     /// this case the n_value field is an index into the string table of the other symbol's name.
     /// When the other symbol is defined then they both take on the defined type and value.
-    Indr = 0xa,
+    Indr,
+    /// Keeps the value as is.
+    Unknown(u8),
 }
 
 impl NType {
     pub fn from_u8(n: u8) -> Self {
-        FromPrimitive::from_u8(n).unwrap_or_else(|| panic!("Invalid n_type number {}", n))
+        match n {
+            0x0 => NType::Undf,
+            0x2 => NType::Abs,
+            0xe => NType::Sect,
+            0xc => NType::Pbud,
+            0xa => NType::Indr,
+            n => NType::Unknown(n),
+        }
     }
 
     pub fn to_u8(self) -> u8 {
-        self as u8
+        match self {
+            NType::Undf => 0x0,
+            NType::Abs => 0x2,
+            NType::Sect => 0xe,
+            NType::Pbud => 0xc,
+            NType::Indr => 0xa,
+            NType::Unknown(n) => n,
+        }
     }
 }
 
-/// TODO : implement all
-#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+/// Declared in /usr/include/mach-o/stab.h
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DebugSymbol {
     /// global symbol
-    Gsym = 0x20,
+    Gsym,
+    /// procedure name (f77 kludge)
+    Fname,
+    /// procedure
+    Fun,
+    /// static symbol
+    Stsym,
+    /// .lcomm symbol
+    Lcsym,
+    /// begin nsect symbol
+    Bnsym,
+    /// AST file path
+    Ast,
+    /// emitted with gcc2_compiled and in gcc source
+    Opt,
+    /// register symbol
+    Rsym,
+    /// src line
+    Sline,
+    /// end nsect symbol
+    Ensym,
+    /// structure elt
+    Ssym,
+    /// main source file name
+    So,
+    /// object file name
+    Oso,
+    /// local symbol
+    Lsym,
+    /// include file beginning
+    Bincl,
+    /// #included file name
+    Sol,
+    /// compiler parameters
+    Params,
+    /// compiler version
+    Version,
+    /// compiler -O level
+    Olevel,
+    /// parameter
+    Psym,
+    /// include file end
+    Eincl,
+    /// alternate entry
+    Entry,
+    /// left bracket
+    Lbrac,
+    /// deleted include file
+    Excl,
+    /// right bracket
+    Rbrac,
+    /// begin common
+    Bcomm,
+    /// end common
+    Ecomm,
+    /// end common (local name)
+    Ecoml,
+    /// second stab entry with length information
+    Leng,
+    /// Keeps the value as is.
+    Unknown(u8),
 }
 
 impl DebugSymbol {
     pub fn from_u8(n: u8) -> Self {
-        FromPrimitive::from_u8(n).unwrap_or_else(|| panic!("Unsupported debug symbol {}", n))
+        match n {
+            0x20 => DebugSymbol::Gsym,
+            0x22 => DebugSymbol::Fname,
+            0x24 => DebugSymbol::Fun,
+            0x26 => DebugSymbol::Stsym,
+            0x28 => DebugSymbol::Lcsym,
+            0x2e => DebugSymbol::Bnsym,
+            0x32 => DebugSymbol::Ast,
+            0x3c => DebugSymbol::Opt,
+            0x40 => DebugSymbol::Rsym,
+            0x44 => DebugSymbol::Sline,
+            0x4e => DebugSymbol::Ensym,
+            0x60 => DebugSymbol::Ssym,
+            0x64 => DebugSymbol::So,
+            0x66 => DebugSymbol::Oso,
+            0x80 => DebugSymbol::Lsym,
+            0x82 => DebugSymbol::Bincl,
+            0x84 => DebugSymbol::Sol,
+            0x86 => DebugSymbol::Params,
+            0x88 => DebugSymbol::Version,
+            0x8a => DebugSymbol::Olevel,
+            0xa0 => DebugSymbol::Psym,
+            0xa2 => DebugSymbol::Eincl,
+            0xa4 => DebugSymbol::Entry,
+            0xc0 => DebugSymbol::Lbrac,
+            0xc2 => DebugSymbol::Excl,
+            0xe0 => DebugSymbol::Rbrac,
+            0xe2 => DebugSymbol::Bcomm,
+            0xe4 => DebugSymbol::Ecomm,
+            0xe8 => DebugSymbol::Ecoml,
+            0xfe => DebugSymbol::Leng,
+            n => DebugSymbol::Unknown(n),
+        }
     }
 
     pub fn to_u8(self) -> u8 {
-        self as u8
+        match self {
+            DebugSymbol::Gsym => 0x20,
+            DebugSymbol::Fname => 0x22,
+            DebugSymbol::Fun => 0x24,
+            DebugSymbol::Stsym => 0x26,
+            DebugSymbol::Lcsym => 0x28,
+            DebugSymbol::Bnsym => 0x2e,
+            DebugSymbol::Ast => 0x32,
+            DebugSymbol::Opt => 0x3c,
+            DebugSymbol::Rsym => 0x40,
+            DebugSymbol::Sline => 0x44,
+            DebugSymbol::Ensym => 0x4e,
+            DebugSymbol::Ssym => 0x60,
+            DebugSymbol::So => 0x64,
+            DebugSymbol::Oso => 0x66,
+            DebugSymbol::Lsym => 0x80,
+            DebugSymbol::Bincl => 0x82,
+            DebugSymbol::Sol => 0x84,
+            DebugSymbol::Params => 0x86,
+            DebugSymbol::Version => 0x88,
+            DebugSymbol::Olevel => 0x8a,
+            DebugSymbol::Psym => 0xa0,
+            DebugSymbol::Eincl => 0xa2,
+            DebugSymbol::Entry => 0xa4,
+            DebugSymbol::Lbrac => 0xc0,
+            DebugSymbol::Excl => 0xc2,
+            DebugSymbol::Rbrac => 0xe0,
+            DebugSymbol::Bcomm => 0xe2,
+            DebugSymbol::Ecomm => 0xe4,
+            DebugSymbol::Ecoml => 0xe8,
+            DebugSymbol::Leng => 0xfe,
+            DebugSymbol::Unknown(n) => n,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Cursor;
 
     #[test]
     fn write_and_read_nlist() {
@@ -173,12 +374,54 @@ mod tests {
 
         let mut buf = Vec::new();
 
-        nlist.write_into(&mut buf);
+        nlist.write_into(&mut buf).unwrap();
 
         assert_eq!(buf.len(), NList64::SIZE as usize);
 
-        let read = NList64::read_from_in(&mut buf.as_slice(), Endian::NATIVE);
+        let read = NList64::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
 
         assert_eq!(read, nlist);
     }
+
+    #[test]
+    fn write_and_read_nlist32() {
+        let nlist = NList32 {
+            n_strx: 1,
+            n_type: NTypeField::Norm {
+                n_pext: false,
+                n_type: NType::Undf,
+                n_ext: true,
+            },
+            n_sect: NList32::NO_SECT,
+            n_desc: 0,
+            n_value: 0,
+        };
+
+        let mut buf = Vec::new();
+
+        nlist.write_into(&mut buf).unwrap();
+
+        assert_eq!(buf.len(), NList32::SIZE as usize);
+
+        let read = NList32::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
+
+        assert_eq!(read, nlist);
+    }
+
+    #[test]
+    fn keep_unknown_n_type() {
+        // N_FUN, an n_type with an unknown type bit and an undeclared debugging entry
+        for n in [0x24, 0x06 | 0x01, 0x3e] {
+            let n_type = NTypeField::from_u8(n);
+            assert_eq!(n_type.to_u8(), n);
+        }
+        assert_eq!(
+            NTypeField::from_u8(0x24),
+            NTypeField::Stab(DebugSymbol::Fun)
+        );
+        assert_eq!(
+            NTypeField::from_u8(0x3e),
+            NTypeField::Stab(DebugSymbol::Unknown(0x3e))
+        );
+    }
 }
@@ -1,7 +1,10 @@
-use crate::io::{Endian, ReadExt, WriteExt};
+use crate::{
+    error::Result,
+    io::{Endian, ReadExt, WriteExt},
+};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
-use std::io::{Read, Write};
+use std::io::{Read, Seek, Write};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RelocationInfo {
@@ -31,10 +34,10 @@ impl RelocationInfo {
     /// size in bytes
     pub const SIZE: u32 = 8;
 
-    pub fn read_from_in<R: Read>(read: &mut R, endian: Endian) -> RelocationInfo {
-        let r_address = read.read_i32_in(endian);
+    pub fn read_from_in<R: Read + Seek>(read: &mut R, endian: Endian) -> Result<RelocationInfo> {
+        let r_address = read.read_i32_in(endian)?;
 
-        let infos = read.read_u32_in(endian);
+        let infos = read.read_u32_in(endian)?;
 
         // Mach-O specification does not clearly specify
         // memory layout of these fields. So we assume that
@@ -68,48 +71,42 @@ impl RelocationInfo {
             )
         };
 
-        RelocationInfo {
+        Ok(RelocationInfo {
             r_address,
             r_symbolnum,
             r_pcrel,
             r_length,
             r_extern,
             r_type,
-        }
+        })
     }
 
-    // Mach-O specification does not clearly specify
-    // memory layout of these fields. So we assume that
-    // order of bit-fields follows ordinary manner
-    // (inverse order if little endian, and vice versa).
-    #[cfg(target_endian = "little")]
-    pub fn write_into(self, write: &mut impl Write) {
-        write.write_i32_native(self.r_address);
-
-        let mut infos: u32 = 0;
-        infos |= self.r_symbolnum;
-        infos |= (self.r_pcrel as u32) * 0x0100_0000;
-        infos |= (self.r_length.to_u32()) << 25;
-        infos |= (self.r_extern as u32) * 0x0800_0000;
-        infos |= (self.r_type as u32) << 28;
-        write.write_u32_native(infos);
+    pub fn write_into(self, write: &mut impl Write) -> Result<()> {
+        self.write_into_in(write, Endian::NATIVE)
     }
 
-    // Mach-O specification does not clearly specify
-    // memory layout of these fields. So we assume that
-    // order of bit-fields follows ordinary manner
-    // (inverse order if little endian, and vice versa).
-    #[cfg(target_endian = "big")]
-    pub fn write_into(self, write: &mut impl Write) {
-        write.write_i32_native(self.r_address);
+    pub fn write_into_in(self, write: &mut impl Write, endian: Endian) -> Result<()> {
+        write.write_i32_in(self.r_address, endian)?;
 
+        // Mach-O specification does not clearly specify
+        // memory layout of these fields. So we assume that
+        // order of bit-fields follows ordinary manner
+        // (inverse order if little endian, and vice versa).
         let mut infos: u32 = 0;
-        infos |= self.r_symbolnum << 8;
-        infos |= (self.r_pcrel as u32) * 0x0000_0080;
-        infos |= (self.r_length.to_u32()) << 5;
-        infos |= (self.r_extern as u32) * 0x0000_0010;
-        infos |= self.r_type as u32;
-        write.write_u32_native(infos);
+        if endian == Endian::Little {
+            infos |= self.r_symbolnum;
+            infos |= (self.r_pcrel as u32) * 0x0100_0000;
+            infos |= (self.r_length.to_u32()) << 25;
+            infos |= (self.r_extern as u32) * 0x0800_0000;
+            infos |= (self.r_type as u32) << 28;
+        } else {
+            infos |= self.r_symbolnum << 8;
+            infos |= (self.r_pcrel as u32) * 0x0000_0080;
+            infos |= (self.r_length.to_u32()) << 5;
+            infos |= (self.r_extern as u32) * 0x0000_0010;
+            infos |= self.r_type as u32;
+        }
+        write.write_u32_in(infos, endian)
     }
 }
 
@@ -130,8 +127,9 @@ impl RelocLength {
         self as u32
     }
 
+    /// Only the lowest 2 bits of `n` are used.
     pub fn from_u32(n: u32) -> RelocLength {
-        FromPrimitive::from_u32(n).unwrap()
+        FromPrimitive::from_u32(n & 0x3).unwrap()
     }
 }
 
@@ -212,6 +210,7 @@ impl X86_64RelocType {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Cursor;
 
     #[test]
     fn write_and_read_relocation_info() {
@@ -226,12 +225,33 @@ mod tests {
 
         let mut buf = Vec::new();
 
-        reloc.write_into(&mut buf);
+        reloc.write_into(&mut buf).unwrap();
 
         assert_eq!(buf.len(), RelocationInfo::SIZE as usize);
 
-        let read_reloc = RelocationInfo::read_from_in(&mut buf.as_slice(), Endian::NATIVE);
+        let read_reloc =
+            RelocationInfo::read_from_in(&mut Cursor::new(buf), Endian::NATIVE).unwrap();
+
+        assert_eq!(read_reloc, reloc);
+    }
+
+    #[test]
+    fn write_and_read_relocation_info_big_endian() {
+        let reloc = RelocationInfo {
+            r_address: 0x10,
+            r_symbolnum: 3,
+            r_pcrel: false,
+            r_length: RelocLength::Long,
+            r_extern: true,
+            r_type: 1,
+        };
+
+        let mut buf = Vec::new();
+        reloc.write_into_in(&mut buf, Endian::Big).unwrap();
+
+        assert_eq!(buf, [0, 0, 0, 0x10, 0, 0, 3, 0x51]);
 
+        let read_reloc = RelocationInfo::read_from_in(&mut Cursor::new(buf), Endian::Big).unwrap();
         assert_eq!(read_reloc, reloc);
     }
 }
@@ -0,0 +1,319 @@
+use crate::{
+    code_signature::{
+        Blob, CodeDirectory, HashType, SuperBlob, CSSLOT_CODEDIRECTORY, CSSLOT_REQUIREMENTS,
+        CSSLOT_SIGNATURESLOT, CS_ADHOC, CS_EXECSEG_MAIN_BINARY, CS_SUPPORTSEXECSEG,
+    },
+    error::{Error, Result},
+    file::{is_zerofill, MachOFile},
+    header::{FileType, Header64},
+    layout::page_size,
+    load_command::{LinkeditDataCommand, LoadCommand, SegmentCommand64},
+};
+
+/// log2 of the size of the pages hashed into code slots, which is 4KiB regardless of the page
+/// size of the architecture.
+const CODE_PAGE_SIZE_LOG2: u8 = 12;
+
+impl MachOFile {
+    /// Signs this file with an ad-hoc signature, like `codesign -s -`.
+    ///
+    /// The signature consists of a SHA-256 CodeDirectory, an empty requirement set and an
+    /// empty CMS blob. The CodeDirectory has `identifier` and `flags | CS_ADHOC`, and its
+    /// exec-segment fields describe `__TEXT`.
+    ///
+    /// An existing signature is replaced in place. Otherwise, the signature is placed at the
+    /// end of `__LINKEDIT` and a new `LC_CODE_SIGNATURE` is added, which needs free space
+    /// after the load commands. `filesize` and `vmsize` of `__LINKEDIT` are updated to cover
+    /// the signature.
+    ///
+    /// The signature covers everything before it, so this should be the last modification of
+    /// the file.
+    pub fn sign_adhoc(&mut self, identifier: &str, flags: u32) -> Result<()> {
+        let linkedit =
+            segment(&self.load_commands, "__LINKEDIT").ok_or(Error::SegmentNotFound {
+                segname: "__LINKEDIT",
+            })?;
+        let dataoff = match self.code_signature_cmd() {
+            Some(cmd) => u64::from(cmd.dataoff),
+            None => {
+                let dataoff = (linkedit.fileoff + linkedit.filesize).next_multiple_of(16);
+                let dataoff32 = u32::try_from(dataoff).map_err(|_| Error::InvalidValue {
+                    kind: "code signature offset",
+                    value: dataoff,
+                })?;
+                let cmd =
+                    LinkeditDataCommand::new(LinkeditDataCommand::CODE_SIGNATURE, dataoff32, 0);
+                self.add_load_command(LoadCommand::LinkeditData(cmd))?;
+                dataoff
+            }
+        };
+        self.truncate_extra(dataoff);
+
+        let (exec_seg_base, exec_seg_limit) = match segment(&self.load_commands, "__TEXT") {
+            Some(text) => (text.fileoff, text.filesize),
+            None => (0, 0),
+        };
+        let exec_seg_flags = match self.header.file_type {
+            FileType::Execute => CS_EXECSEG_MAIN_BINARY,
+            _ => 0,
+        };
+
+        let hash_type = HashType::Sha256;
+        let requirements = Blob::Requirements(Vec::new());
+        let hash = |data: &[u8]| {
+            hash_type.digest(data).ok_or(Error::InvalidValue {
+                kind: "code directory hash type",
+                value: hash_type.to_u8().into(),
+            })
+        };
+        let n_pages = dataoff.div_ceil(1 << CODE_PAGE_SIZE_LOG2) as usize;
+        let mut cd = CodeDirectory {
+            version: CS_SUPPORTSEXECSEG,
+            flags: flags | CS_ADHOC,
+            hash_type,
+            platform: 0,
+            page_size_log2: CODE_PAGE_SIZE_LOG2,
+            code_limit: dataoff,
+            identifier: identifier.to_string(),
+            team_id: None,
+            exec_seg_base,
+            exec_seg_limit,
+            exec_seg_flags,
+            runtime: 0,
+            scatter_offset: 0,
+            pre_encrypt_offset: 0,
+            // slot 1 is for Info.plist, which Mach-O files do not have.
+            special_slots: vec![vec![0; 32], hash(&requirements.to_bytes()?)?],
+            // filled after the size of the signature is settled.
+            code_slots: vec![vec![0; 32]; n_pages],
+        };
+        let signature = |cd: &CodeDirectory| {
+            SuperBlob::new(vec![
+                (CSSLOT_CODEDIRECTORY, Blob::CodeDirectory(cd.clone())),
+                (CSSLOT_REQUIREMENTS, requirements.clone()),
+                (CSSLOT_SIGNATURESLOT, Blob::Cms(Vec::new())),
+            ])
+        };
+        let datasize = signature(&cd)?.length;
+
+        let page_size = page_size(self.header.cpu_type);
+        for lc in self.load_commands.iter_mut() {
+            match lc {
+                LoadCommand::LinkeditData(cmd)
+                    if cmd.cmd == LinkeditDataCommand::CODE_SIGNATURE =>
+                {
+                    cmd.datasize = datasize;
+                }
+                LoadCommand::Segment64(seg, _) if seg.segname == "__LINKEDIT" => {
+                    seg.filesize = dataoff + u64::from(datasize) - seg.fileoff;
+                    seg.vmsize = seg.filesize.next_multiple_of(page_size);
+                }
+                _ => {}
+            }
+        }
+
+        let mut file = Vec::new();
+        self.write(&mut file)?;
+        file.resize(dataoff as usize, 0);
+        cd.code_slots.clear();
+        for page in file.chunks(1 << CODE_PAGE_SIZE_LOG2) {
+            cd.code_slots.push(hash(page)?);
+        }
+
+        self.extra.push((dataoff, signature(&cd)?.to_bytes()?));
+        Ok(())
+    }
+
+    /// Removes the code signature and its `LC_CODE_SIGNATURE`, and shrinks `__LINKEDIT` to
+    /// end where the signature started. Returns `false` if the file is not signed.
+    pub fn remove_signature(&mut self) -> bool {
+        let (idx, dataoff) =
+            match self
+                .load_commands
+                .iter()
+                .enumerate()
+                .find_map(|(i, lc)| match lc {
+                    LoadCommand::LinkeditData(cmd)
+                        if cmd.cmd == LinkeditDataCommand::CODE_SIGNATURE =>
+                    {
+                        Some((i, u64::from(cmd.dataoff)))
+                    }
+                    _ => None,
+                }) {
+                Some(found) => found,
+                None => return false,
+            };
+
+        let cmd = self.load_commands.remove(idx);
+        self.header.n_cmds -= 1;
+        self.header.size_of_cmds -= cmd.cmd_size();
+        self.truncate_extra(dataoff);
+
+        let page_size = page_size(self.header.cpu_type);
+        for lc in self.load_commands.iter_mut() {
+            if let LoadCommand::Segment64(seg, _) = lc {
+                if seg.segname == "__LINKEDIT" && seg.fileoff <= dataoff {
+                    seg.filesize = seg.filesize.min(dataoff - seg.fileoff);
+                    seg.vmsize = seg.filesize.next_multiple_of(page_size);
+                }
+            }
+        }
+        true
+    }
+
+    fn code_signature_cmd(&self) -> Option<&LinkeditDataCommand> {
+        self.load_commands.iter().find_map(|lc| match lc {
+            LoadCommand::LinkeditData(cmd) if cmd.cmd == LinkeditDataCommand::CODE_SIGNATURE => {
+                Some(cmd)
+            }
+            _ => None,
+        })
+    }
+
+    /// Appends `lc` to the load commands if it fits before the first section or segment data.
+    fn add_load_command(&mut self, lc: LoadCommand) -> Result<()> {
+        let cmds_end = u64::from(Header64::SIZE + self.header.size_of_cmds);
+        let sects = self
+            .sections()
+            .filter(|(sect, _)| !is_zerofill(sect) && sect.size > 0)
+            .map(|(sect, _)| u64::from(sect.offset));
+        let segs = self.load_commands.iter().filter_map(|lc| match lc {
+            LoadCommand::Segment64(seg, _) if seg.fileoff > 0 && seg.filesize > 0 => {
+                Some(seg.fileoff)
+            }
+            _ => None,
+        });
+        let data_start = sects.chain(segs).min().unwrap_or(u64::MAX);
+        if cmds_end + u64::from(lc.cmd_size()) > data_start {
+            return Err(Error::NoRoomForLoadCommand { cmd: lc.cmd() });
+        }
+
+        self.header.n_cmds += 1;
+        self.header.size_of_cmds += lc.cmd_size();
+        self.load_commands.push(lc);
+        Ok(())
+    }
+
+    /// Drops the bytes of `extra` at or after `offset`.
+    fn truncate_extra(&mut self, offset: u64) {
+        self.extra.retain(|(start, _)| *start < offset);
+        for (start, data) in self.extra.iter_mut() {
+            data.truncate((offset - *start) as usize);
+        }
+    }
+}
+
+fn segment<'a>(lcs: &'a [LoadCommand], segname: &str) -> Option<&'a SegmentCommand64> {
+    lcs.iter().find_map(|lc| match lc {
+        LoadCommand::Segment64(seg, _) if seg.segname == segname => Some(seg),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::ExecutableBuilder, view::MachOView};
+    use sha2::{Digest as _, Sha256};
+
+    /// Builds an executable whose text section directly follows the header padding.
+    fn build_executable() -> Vec<u8> {
+        let mut builder = ExecutableBuilder::new();
+        let text = builder.add_text_section("__text", 4, vec![0x90; 0x1801]);
+        builder.define_symbol("start", text, 0, true).unwrap();
+        builder.entry_symbol("start");
+
+        let mut buf = Vec::new();
+        builder.write(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn sign_and_remove_signature() {
+        let unsigned = build_executable();
+        let mut file = MachOFile::parse(&unsigned).unwrap();
+        let n_cmds = file.header.n_cmds;
+        let cmds_end = (Header64::SIZE + file.header.size_of_cmds) as usize;
+        file.sign_adhoc("a.out", 0).unwrap();
+
+        let mut signed = Vec::new();
+        file.write(&mut signed).unwrap();
+
+        let view = MachOView::parse(&signed).unwrap();
+        let sig = view.code_signature().unwrap().unwrap();
+        let cd = sig.code_directory().unwrap();
+        let cmd = MachOFile::parse(&signed)
+            .unwrap()
+            .code_signature_cmd()
+            .copied()
+            .unwrap();
+        let dataoff = cmd.dataoff as usize;
+        assert_eq!(dataoff % 16, 0);
+        assert_eq!(dataoff + cmd.datasize as usize, signed.len());
+        assert_eq!(&signed[..Header64::SIZE as usize], {
+            let mut header = Vec::new();
+            file.header.write_into(&mut header).unwrap();
+            header
+        });
+
+        assert_eq!(cd.identifier, "a.out");
+        assert_eq!(cd.flags, CS_ADHOC);
+        assert_eq!(cd.code_limit, dataoff as u64);
+        assert_eq!(cd.exec_seg_flags, CS_EXECSEG_MAIN_BINARY);
+        let pages = signed[..dataoff]
+            .chunks(0x1000)
+            .map(|page| Sha256::digest(page).to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(cd.code_slots, pages);
+
+        let linkedit = view
+            .segments()
+            .map(Result::unwrap)
+            .find(|seg| seg.segname == "__LINKEDIT")
+            .unwrap();
+        assert_eq!(linkedit.fileoff + linkedit.filesize, signed.len() as u64);
+        assert!(linkedit.vmsize >= linkedit.filesize);
+
+        // signing again reproduces the same signature
+        let mut resigned = MachOFile::parse(&signed).unwrap();
+        resigned.sign_adhoc("a.out", 0).unwrap();
+        let mut buf = Vec::new();
+        resigned.write(&mut buf).unwrap();
+        assert_eq!(buf, signed);
+
+        let mut stripped = MachOFile::parse(&signed).unwrap();
+        assert!(stripped.remove_signature());
+        assert!(!stripped.remove_signature());
+        assert_eq!(stripped.header.n_cmds, n_cmds);
+        let mut buf = Vec::new();
+        stripped.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), dataoff);
+        assert!(MachOView::parse(&buf)
+            .unwrap()
+            .code_signature()
+            .unwrap()
+            .is_none());
+        // only filesize of __LINKEDIT may differ, which now includes the padding before the
+        // signature.
+        assert_eq!(buf[cmds_end..unsigned.len()], unsigned[cmds_end..]);
+    }
+
+    #[test]
+    fn sign_beyond_4gib() {
+        let mut file = MachOFile::parse(&build_executable()).unwrap();
+        for lc in file.load_commands.iter_mut() {
+            if let LoadCommand::Segment64(seg, _) = lc {
+                if seg.segname == "__LINKEDIT" {
+                    seg.fileoff = 0x1_0000_0000;
+                }
+            }
+        }
+        assert!(matches!(
+            file.sign_adhoc("a.out", 0),
+            Err(Error::InvalidValue {
+                value: 0x1_0000_0020,
+                ..
+            })
+        ));
+    }
+}
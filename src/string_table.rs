@@ -1,5 +1,6 @@
 use std::fmt;
 
+#[derive(Clone, PartialEq, Eq)]
 pub struct StringTable {
     data: Vec<u8>,
 }
@@ -9,9 +10,19 @@ impl StringTable {
         StringTable { data: vec![0] }
     }
 
-    pub fn get(&self, idx: usize) -> &str {
-        let bytes = self.data[idx..].split(|n| *n == 0).next().unwrap();
-        std::str::from_utf8(bytes).unwrap()
+    /// Wraps the string table of an existing file as is.
+    /// Unlike `From<Vec<u8>>`, it does not require the leading and trailing null, because linkers
+    /// may emit other padding.
+    pub(crate) fn from_raw(data: Vec<u8>) -> Self {
+        StringTable { data }
+    }
+
+    /// Returns the string at `idx`, or `None` if `idx` is out of range or the string is not
+    /// valid UTF-8.
+    pub fn get(&self, idx: usize) -> Option<&str> {
+        let bytes = self.data.get(idx..).filter(|bytes| !bytes.is_empty())?;
+        let bytes = bytes.split(|n| *n == 0).next().unwrap();
+        std::str::from_utf8(bytes).ok()
     }
 
     pub fn push_with_null(&mut self, s: &str) {
@@ -28,11 +39,16 @@ impl StringTable {
         self.data.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Iterates the strings after the leading null, skipping those which are not valid UTF-8.
     pub fn iter(&self) -> impl Iterator<Item = &str> {
         self.as_ref()
             .split(|b| *b == 0)
             .skip(1)
-            .map(|bytes| std::str::from_utf8(bytes).unwrap())
+            .filter_map(|bytes| std::str::from_utf8(bytes).ok())
     }
 }
 
@@ -79,11 +95,11 @@ mod tests {
     fn get_index_0_always_return_empty_string() {
         let data = vec![0x00, 0x5f, 0x6d, 0x61, 0x69, 0x6e, 0x00];
         let table = StringTable::from(data);
-        assert_eq!(table.get(0), "");
+        assert_eq!(table.get(0), Some(""));
 
         let mut table = StringTable::with_null();
         table.push_with_null("hoge");
-        assert_eq!(table.get(0), "");
+        assert_eq!(table.get(0), Some(""));
     }
 
     #[test]
@@ -91,8 +107,13 @@ mod tests {
         let data = vec![0x00, 0x5f, 0x6d, 0x61, 0x69, 0x6e, 0x00];
         let table = StringTable::from(data);
 
-        assert_eq!(table.get(1), "_main");
-        assert_eq!(table.get(2), "main");
+        assert_eq!(table.get(1), Some("_main"));
+        assert_eq!(table.get(2), Some("main"));
+        assert_eq!(table.get(7), None);
+
+        let table = StringTable::from_raw(vec![0x00, 0xff, 0x00, 0x5f, 0x00]);
+        assert_eq!(table.get(1), None);
+        assert_eq!(table.iter().collect::<Vec<_>>(), ["_", ""]);
     }
 
     #[test]
@@ -100,6 +121,6 @@ mod tests {
         let mut table = StringTable::with_null();
         table.push_with_null("hoge");
 
-        assert_eq!(table.get(1), "hoge");
+        assert_eq!(table.get(1), Some("hoge"));
     }
 }
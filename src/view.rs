@@ -0,0 +1,770 @@
+//! Zero-copy parsing over a byte slice.
+//!
+//! `MachOView` borrows the whole file and decodes structures only when they are asked for.
+//! Names and contents are handed out as `&'a str` and `&'a [u8]` pointing into the original
+//! slice, so scanning a file does not allocate. Use the owned types (`Header`, `LoadCommand`,
+//! `NList64`, ...) when the file is going to be edited.
+
+use crate::{
+    chained_fixups::ChainedFixups,
+    code_signature::SuperBlob,
+    dyld_info::DyldInfo,
+    error::{Error, Result},
+    export_trie::{self, Export},
+    function_starts,
+    header::{CpuType, Header},
+    io::{Endian, ReadExt as _},
+    load_command::{
+        segment64::{SectionAttrs, SectionType},
+        DyldInfoCommand, LinkeditDataCommand, LoadCommand, SegmentCommand32, SegmentCommand64,
+        SymtabCommand,
+    },
+    nlist::{NList32, NList64, NTypeField},
+};
+use std::io::Cursor;
+
+/// A borrowed view of a thin (non-fat) Mach-O file.
+#[derive(Debug, Clone)]
+pub struct MachOView<'a> {
+    data: &'a [u8],
+    header: Header,
+    endian: Endian,
+    cpu_type: CpuType,
+    n_cmds: u32,
+    cmds_offset: u64,
+    cmds_size: u64,
+}
+
+impl<'a> MachOView<'a> {
+    /// Parses the mach header and checks that the load commands fit in `data`.
+    ///
+    /// Load commands themselves are not decoded until they are iterated.
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let mut cursor = Cursor::new(data);
+        let header = Header::read_from(&mut cursor)?;
+
+        let (endian, cpu_type, n_cmds, size_of_cmds) = match &header {
+            Header::Mach(h) => (h.endian(), h.cpu_type, h.n_cmds, h.size_of_cmds),
+            Header::Mach32(h) => (h.endian(), h.cpu_type, h.n_cmds, h.size_of_cmds),
+            Header::Fat(_) | Header::Fat64(_) => {
+                return Err(Error::BadMagic {
+                    offset: 0,
+                    magic: header.magic().to_u32(),
+                })
+            }
+        };
+
+        let cmds_offset = cursor.position();
+        slice(data, cmds_offset, size_of_cmds.into())?;
+
+        Ok(MachOView {
+            data,
+            header,
+            endian,
+            cpu_type,
+            n_cmds,
+            cmds_offset,
+            cmds_size: size_of_cmds.into(),
+        })
+    }
+
+    /// The whole file this view borrows.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Either `Header::Mach` or `Header::Mach32`.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn endian(&self) -> Endian {
+        self.endian
+    }
+
+    pub fn is_64(&self) -> bool {
+        matches!(self.header, Header::Mach(_))
+    }
+
+    pub fn load_commands(&self) -> LoadCommands<'a> {
+        LoadCommands {
+            data: self.data,
+            endian: self.endian,
+            cpu_type: self.cpu_type,
+            is_64: self.is_64(),
+            remaining: self.n_cmds,
+            offset: self.cmds_offset,
+            end: self.cmds_offset + self.cmds_size,
+        }
+    }
+
+    /// Iterates over `LC_SEGMENT` and `LC_SEGMENT_64` commands.
+    pub fn segments(&self) -> impl Iterator<Item = Result<SegmentView<'a>>> {
+        self.load_commands()
+            .filter_map(|lc| lc.and_then(|lc| lc.segment()).transpose())
+    }
+
+    /// Returns the symbol table described by the `LC_SYMTAB` command, if any.
+    pub fn symtab(&self) -> Result<Option<SymtabView<'a>>> {
+        for lc in self.load_commands() {
+            if let Some(symtab) = lc?.symtab()? {
+                return Ok(Some(symtab));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the exports in the trie of `LC_DYLD_EXPORTS_TRIE`, or in the export range of
+    /// `LC_DYLD_INFO(_ONLY)`. Images without either have no exports.
+    pub fn exports(&self) -> Result<Vec<Export>> {
+        for lc in self.load_commands() {
+            let lc = lc?;
+            if let Some((cmd, trie)) = lc.linkedit_data()? {
+                if cmd.cmd == LinkeditDataCommand::DYLD_EXPORTS_TRIE {
+                    return export_trie::parse_at(trie, cmd.dataoff.into());
+                }
+            }
+            if let Some(cmd) = lc.dyld_info_command()? {
+                return self.dyld_info_of(cmd)?.exports();
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// Returns the contents of the `LC_DYLD_CHAINED_FIXUPS` command, if any.
+    pub fn chained_fixups(&self) -> Result<Option<ChainedFixups>> {
+        for lc in self.load_commands() {
+            if let Some((cmd, data)) = lc?.linkedit_data()? {
+                if cmd.cmd == LinkeditDataCommand::DYLD_CHAINED_FIXUPS {
+                    return ChainedFixups::parse_at(data, cmd.dataoff.into()).map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the signature referred by the `LC_CODE_SIGNATURE` command, if any.
+    pub fn code_signature(&self) -> Result<Option<SuperBlob>> {
+        for lc in self.load_commands() {
+            if let Some((cmd, data)) = lc?.linkedit_data()? {
+                if cmd.cmd == LinkeditDataCommand::CODE_SIGNATURE {
+                    return SuperBlob::parse_at(data, cmd.dataoff.into()).map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the VM addresses of the functions listed by the `LC_FUNCTION_STARTS` command, if
+    /// any.
+    pub fn function_starts(&self) -> Result<Option<Vec<u64>>> {
+        for lc in self.load_commands() {
+            if let Some((cmd, data)) = lc?.linkedit_data()? {
+                if cmd.cmd == LinkeditDataCommand::FUNCTION_STARTS {
+                    let text_vmaddr = self.text_vmaddr()?;
+                    return function_starts::parse_at(data, cmd.dataoff.into(), text_vmaddr)
+                        .map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn text_vmaddr(&self) -> Result<u64> {
+        for seg in self.segments() {
+            let seg = seg?;
+            if seg.segname == "__TEXT" {
+                return Ok(seg.vmaddr);
+            }
+        }
+        Err(Error::SegmentNotFound { segname: "__TEXT" })
+    }
+
+    /// Returns the payloads of the `LC_DYLD_INFO(_ONLY)` command, if any.
+    pub fn dyld_info(&self) -> Result<Option<DyldInfo<'a>>> {
+        for lc in self.load_commands() {
+            if let Some(cmd) = lc?.dyld_info_command()? {
+                return self.dyld_info_of(cmd).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// The segment sizes bound the rebased and bound pointers, so they are collected from the
+    /// segments of this view.
+    fn dyld_info_of(&self, cmd: DyldInfoCommand) -> Result<DyldInfo<'a>> {
+        let segment_sizes = self
+            .segments()
+            .map(|seg| Ok(seg?.vmsize))
+            .collect::<Result<Vec<_>>>()?;
+        DyldInfo::new(self.data, cmd, self.is_64(), segment_sizes)
+    }
+}
+
+/// An iterator over the load commands of a `MachOView`.
+///
+/// Iteration stops after the first error.
+#[derive(Debug, Clone)]
+pub struct LoadCommands<'a> {
+    data: &'a [u8],
+    endian: Endian,
+    cpu_type: CpuType,
+    is_64: bool,
+    remaining: u32,
+    offset: u64,
+    end: u64,
+}
+
+impl<'a> LoadCommands<'a> {
+    fn read_next(&mut self) -> Result<LoadCommandView<'a>> {
+        let offset = self.offset;
+        let mut cursor = Cursor::new(self.data);
+        cursor.set_position(offset);
+        let cmd = cursor.read_u32_in(self.endian)?;
+        let cmdsize = cursor.read_u32_in(self.endian)?;
+
+        if cmdsize < 8 || offset + u64::from(cmdsize) > self.end {
+            return Err(Error::InconsistentCmdSize {
+                offset,
+                cmd,
+                cmdsize,
+            });
+        }
+
+        self.offset += u64::from(cmdsize);
+
+        Ok(LoadCommandView {
+            data: self.data,
+            endian: self.endian,
+            cpu_type: self.cpu_type,
+            is_64: self.is_64,
+            offset,
+            cmd,
+            bytes: slice(self.data, offset, cmdsize.into())?,
+        })
+    }
+}
+
+impl<'a> Iterator for LoadCommands<'a> {
+    type Item = Result<LoadCommandView<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let result = self.read_next();
+        if result.is_err() {
+            self.remaining = 0;
+        }
+        Some(result)
+    }
+}
+
+/// A load command which has not been decoded yet.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadCommandView<'a> {
+    data: &'a [u8],
+    endian: Endian,
+    cpu_type: CpuType,
+    is_64: bool,
+    offset: u64,
+    cmd: u32,
+    bytes: &'a [u8],
+}
+
+impl<'a> LoadCommandView<'a> {
+    pub fn cmd(&self) -> u32 {
+        self.cmd
+    }
+
+    pub fn cmd_size(&self) -> u32 {
+        self.bytes.len() as u32
+    }
+
+    /// File offset of this load command.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Raw bytes of this load command, including `cmd` and `cmdsize`.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Decodes this load command into the owned `LoadCommand`.
+    pub fn decode(&self) -> Result<LoadCommand> {
+        LoadCommand::read_from_in(&mut self.cursor(), self.endian, self.cpu_type)
+    }
+
+    /// Returns a `SegmentView` if this is a `LC_SEGMENT` or `LC_SEGMENT_64` command.
+    pub fn segment(&self) -> Result<Option<SegmentView<'a>>> {
+        match self.cmd {
+            SegmentCommand64::TYPE | SegmentCommand32::TYPE => SegmentView::read(self).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Decodes the command if this is a `LC_DYLD_INFO` or `LC_DYLD_INFO_ONLY` command. Use
+    /// `MachOView::dyld_info` for its payloads.
+    pub fn dyld_info_command(&self) -> Result<Option<DyldInfoCommand>> {
+        match self.cmd {
+            DyldInfoCommand::TYPE | DyldInfoCommand::TYPE_ONLY => {
+                DyldInfoCommand::read_from_in(&mut self.cursor(), self.endian).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the command and the data it refers to if this is a linkedit_data_command.
+    pub fn linkedit_data(&self) -> Result<Option<(LinkeditDataCommand, &'a [u8])>> {
+        if !LinkeditDataCommand::TYPES.contains(&self.cmd) {
+            return Ok(None);
+        }
+
+        let cmd = LinkeditDataCommand::read_from_in(&mut self.cursor(), self.endian)?;
+        let data = slice(self.data, cmd.dataoff.into(), cmd.datasize.into())?;
+        Ok(Some((cmd, data)))
+    }
+
+    /// Returns a `SymtabView` if this is a `LC_SYMTAB` command.
+    pub fn symtab(&self) -> Result<Option<SymtabView<'a>>> {
+        if self.cmd != SymtabCommand::TYPE {
+            return Ok(None);
+        }
+
+        let cmd = SymtabCommand::read_from_in(&mut self.cursor(), self.endian)?;
+        let entry_size = if self.is_64 {
+            NList64::SIZE
+        } else {
+            NList32::SIZE
+        };
+        slice(
+            self.data,
+            cmd.symoff.into(),
+            u64::from(cmd.nsyms) * u64::from(entry_size),
+        )?;
+        let strings = slice(self.data, cmd.stroff.into(), cmd.strsize.into())?;
+
+        Ok(Some(SymtabView {
+            data: self.data,
+            endian: self.endian,
+            is_64: self.is_64,
+            symoff: cmd.symoff.into(),
+            nsyms: cmd.nsyms,
+            stroff: cmd.stroff.into(),
+            strings,
+        }))
+    }
+
+    /// A cursor over the whole file positioned at this load command, so that errors carry
+    /// file offsets.
+    fn cursor(&self) -> Cursor<&'a [u8]> {
+        let mut cursor = Cursor::new(self.data);
+        cursor.set_position(self.offset);
+        cursor
+    }
+}
+
+/// A borrowed `LC_SEGMENT` or `LC_SEGMENT_64` command.
+/// Fields of 32-bit segments are widened to `u64`.
+#[derive(Debug, Clone, Copy)]
+pub struct SegmentView<'a> {
+    data: &'a [u8],
+    endian: Endian,
+    is_64: bool,
+    sects_offset: u64,
+    pub segname: &'a str,
+    pub vmaddr: u64,
+    pub vmsize: u64,
+    pub fileoff: u64,
+    pub filesize: u64,
+    pub maxprot: i32,
+    pub initprot: i32,
+    pub nsects: u32,
+    pub flags: u32,
+}
+
+impl<'a> SegmentView<'a> {
+    fn read(lc: &LoadCommandView<'a>) -> Result<Self> {
+        let is_64 = lc.cmd == SegmentCommand64::TYPE;
+        let endian = lc.endian;
+        let mut cursor = lc.cursor();
+        cursor.set_position(lc.offset + 8);
+
+        let segname = read_name(&mut cursor)?;
+        let (vmaddr, vmsize, fileoff, filesize) = if is_64 {
+            (
+                cursor.read_u64_in(endian)?,
+                cursor.read_u64_in(endian)?,
+                cursor.read_u64_in(endian)?,
+                cursor.read_u64_in(endian)?,
+            )
+        } else {
+            (
+                cursor.read_u32_in(endian)?.into(),
+                cursor.read_u32_in(endian)?.into(),
+                cursor.read_u32_in(endian)?.into(),
+                cursor.read_u32_in(endian)?.into(),
+            )
+        };
+        let maxprot = cursor.read_i32_in(endian)?;
+        let initprot = cursor.read_i32_in(endian)?;
+        let nsects = cursor.read_u32_in(endian)?;
+        let flags = cursor.read_u32_in(endian)?;
+
+        let (cmd_size, sect_size) = if is_64 {
+            (SegmentCommand64::SIZE, SectionView::SIZE_64)
+        } else {
+            (SegmentCommand32::SIZE, SectionView::SIZE_32)
+        };
+        let expected = u64::from(nsects) * u64::from(sect_size) + u64::from(cmd_size);
+        if expected != lc.bytes.len() as u64 {
+            return Err(Error::InconsistentCmdSize {
+                offset: lc.offset,
+                cmd: lc.cmd,
+                cmdsize: lc.cmd_size(),
+            });
+        }
+
+        Ok(SegmentView {
+            data: lc.data,
+            endian,
+            is_64,
+            sects_offset: cursor.position(),
+            segname,
+            vmaddr,
+            vmsize,
+            fileoff,
+            filesize,
+            maxprot,
+            initprot,
+            nsects,
+            flags,
+        })
+    }
+
+    /// The file contents mapped by this segment.
+    pub fn data(&self) -> Result<&'a [u8]> {
+        slice(self.data, self.fileoff, self.filesize)
+    }
+
+    pub fn sections(&self) -> impl Iterator<Item = Result<SectionView<'a>>> {
+        let seg = *self;
+        let sect_size = if seg.is_64 {
+            SectionView::SIZE_64
+        } else {
+            SectionView::SIZE_32
+        };
+        (0..seg.nsects).map(move |i| {
+            let offset = seg.sects_offset + u64::from(i) * u64::from(sect_size);
+            SectionView::read(seg.data, offset, seg.endian, seg.is_64)
+        })
+    }
+}
+
+/// A borrowed `Section64` or `Section32`.
+/// Fields of 32-bit sections are widened to `u64`.
+#[derive(Debug, Clone, Copy)]
+pub struct SectionView<'a> {
+    data: &'a [u8],
+    pub sectname: &'a str,
+    pub segname: &'a str,
+    pub addr: u64,
+    pub size: u64,
+    pub offset: u32,
+    pub align: u32,
+    pub reloff: u32,
+    pub nreloc: u32,
+    /// Raw flags. Use `section_type` and `attrs` to decode.
+    pub flags: u32,
+    pub reserved1: u32,
+    pub reserved2: u32,
+}
+
+impl<'a> SectionView<'a> {
+    const SIZE_64: u32 = 0x50;
+    const SIZE_32: u32 = 0x44;
+
+    fn read(data: &'a [u8], offset: u64, endian: Endian, is_64: bool) -> Result<Self> {
+        let mut cursor = Cursor::new(data);
+        cursor.set_position(offset);
+
+        let sectname = read_name(&mut cursor)?;
+        let segname = read_name(&mut cursor)?;
+        let (addr, size) = if is_64 {
+            (cursor.read_u64_in(endian)?, cursor.read_u64_in(endian)?)
+        } else {
+            (
+                cursor.read_u32_in(endian)?.into(),
+                cursor.read_u32_in(endian)?.into(),
+            )
+        };
+
+        Ok(SectionView {
+            data,
+            sectname,
+            segname,
+            addr,
+            size,
+            offset: cursor.read_u32_in(endian)?,
+            align: cursor.read_u32_in(endian)?,
+            reloff: cursor.read_u32_in(endian)?,
+            nreloc: cursor.read_u32_in(endian)?,
+            flags: cursor.read_u32_in(endian)?,
+            reserved1: cursor.read_u32_in(endian)?,
+            reserved2: cursor.read_u32_in(endian)?,
+        })
+    }
+
+    pub fn section_type(&self) -> SectionType {
+        SectionType::from_u32(self.flags & SectionType::BIT_MASK)
+    }
+
+    pub fn attrs(&self) -> SectionAttrs {
+        SectionAttrs::from_u32(self.flags & SectionAttrs::BIT_MASK)
+    }
+
+    /// The contents of this section in the file.
+    /// Zero-fill sections occupy no space in the file, so `None` is returned for them.
+    pub fn data(&self) -> Result<Option<&'a [u8]>> {
+        if self.section_type().is_zerofill() {
+            return Ok(None);
+        }
+        slice(self.data, self.offset.into(), self.size).map(Some)
+    }
+}
+
+/// The symbol table and string table referred by `LC_SYMTAB`.
+#[derive(Debug, Clone, Copy)]
+pub struct SymtabView<'a> {
+    data: &'a [u8],
+    endian: Endian,
+    is_64: bool,
+    symoff: u64,
+    nsyms: u32,
+    stroff: u64,
+    strings: &'a [u8],
+}
+
+impl<'a> SymtabView<'a> {
+    /// The raw string table.
+    pub fn strings(&self) -> &'a [u8] {
+        self.strings
+    }
+
+    /// Returns the null-terminated string at `idx` in the string table.
+    pub fn string(&self, idx: u32) -> Result<&'a str> {
+        let offset = self.stroff + u64::from(idx);
+        let bytes = self
+            .strings
+            .get(idx as usize..)
+            .ok_or(Error::Truncated { offset })?;
+        let len = bytes
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or(Error::Truncated { offset })?;
+        std::str::from_utf8(&bytes[..len]).map_err(|_| Error::InvalidString { offset })
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = Result<SymbolView<'a>>> {
+        let symtab = *self;
+        let mut cursor = Cursor::new(self.data);
+        cursor.set_position(self.symoff);
+        (0..self.nsyms).map(move |_| {
+            let nlist = if symtab.is_64 {
+                NList64::read_from_in(&mut cursor, symtab.endian)?
+            } else {
+                let nlist = NList32::read_from_in(&mut cursor, symtab.endian)?;
+                NList64 {
+                    n_strx: nlist.n_strx,
+                    n_type: nlist.n_type,
+                    n_sect: nlist.n_sect,
+                    n_desc: nlist.n_desc,
+                    n_value: nlist.n_value.into(),
+                }
+            };
+
+            Ok(SymbolView {
+                name: symtab.string(nlist.n_strx)?,
+                n_type: nlist.n_type,
+                n_sect: nlist.n_sect,
+                n_desc: nlist.n_desc,
+                n_value: nlist.n_value,
+            })
+        })
+    }
+}
+
+/// A symbol table entry with its name resolved.
+/// `n_value` of 32-bit entries is widened to `u64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SymbolView<'a> {
+    pub name: &'a str,
+    pub n_type: NTypeField,
+    pub n_sect: u8,
+    pub n_desc: u16,
+    pub n_value: u64,
+}
+
+/// Returns `data[offset..offset + size]`, or `Error::Truncated` if it is out of bounds.
+pub(crate) fn slice(data: &[u8], offset: u64, size: u64) -> Result<&[u8]> {
+    match offset.checked_add(size) {
+        Some(end) if end <= data.len() as u64 => Ok(&data[offset as usize..end as usize]),
+        _ => Err(Error::Truncated { offset }),
+    }
+}
+
+/// Reads a 16 bytes null-padded name without copying it.
+fn read_name<'a>(cursor: &mut Cursor<&'a [u8]>) -> Result<&'a str> {
+    let offset = Cursor::position(cursor);
+    let data: &'a [u8] = cursor.get_ref();
+    let bytes = slice(data, offset, 16)?;
+    cursor.set_position(offset + 16);
+
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(16);
+    std::str::from_utf8(&bytes[..len]).map_err(|_| Error::InvalidString { offset })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        header::{CpuCapabilities, CpuSubTypeX86_64, CpuType, FileType, Flags, Header64, Magic},
+        load_command::Section64,
+        nlist::NType,
+    };
+
+    /// Builds an object file which has one `__TEXT,__text` section and one symbol `_main`.
+    fn build_object(endian: Endian) -> Vec<u8> {
+        let text = [0x55, 0x48, 0x89, 0xe5, 0xc3];
+        let cmds_size = SegmentCommand64::SIZE + 0x50 + SymtabCommand::SIZE;
+        let text_off = Header64::SIZE + cmds_size;
+        let sym_off = text_off + 8;
+        let str_off = sym_off + NList64::SIZE;
+
+        let mut buf = Vec::new();
+        Header64 {
+            magic: Magic::Magic64,
+            cpu_type: CpuType::X86_64(CpuSubTypeX86_64::All, CpuCapabilities::NONE),
+            file_type: FileType::Object,
+            n_cmds: 2,
+            size_of_cmds: cmds_size,
+            flags: Flags::new(),
+            reserved: 0,
+        }
+        .write_into_in(&mut buf, endian)
+        .unwrap();
+        SegmentCommand64 {
+            cmd: SegmentCommand64::TYPE,
+            cmdsize: SegmentCommand64::SIZE + 0x50,
+            segname: String::new(),
+            vmaddr: 0,
+            vmsize: text.len() as u64,
+            fileoff: text_off.into(),
+            filesize: text.len() as u64,
+            maxprot: 7,
+            initprot: 7,
+            nsects: 1,
+            flags: 0,
+        }
+        .write_into_in(&mut buf, endian)
+        .unwrap();
+        Section64 {
+            sectname: "__text".to_string(),
+            segname: "__TEXT".to_string(),
+            addr: 0,
+            size: text.len() as u64,
+            offset: text_off,
+            align: 0,
+            reloff: 0,
+            nreloc: 0,
+            flags: (SectionAttrs::new(), SectionType::Regular),
+            reserved1: 0,
+            reserved2: 0,
+            reserved3: 0,
+        }
+        .write_into_in(&mut buf, endian)
+        .unwrap();
+        SymtabCommand {
+            cmd: SymtabCommand::TYPE,
+            cmdsize: SymtabCommand::SIZE,
+            symoff: sym_off,
+            nsyms: 1,
+            stroff: str_off,
+            strsize: 8,
+        }
+        .write_into_in(&mut buf, endian)
+        .unwrap();
+
+        buf.extend_from_slice(&text);
+        buf.resize(sym_off as usize, 0);
+        NList64 {
+            n_strx: 1,
+            n_type: NTypeField::Norm {
+                n_pext: false,
+                n_type: NType::Sect,
+                n_ext: true,
+            },
+            n_sect: 1,
+            n_desc: 0,
+            n_value: 0,
+        }
+        .write_into_in(&mut buf, endian)
+        .unwrap();
+        buf.extend_from_slice(b"\0_main\0\0");
+
+        buf
+    }
+
+    #[test]
+    fn view_object() {
+        let buf = build_object(Endian::NATIVE);
+        let view = MachOView::parse(&buf).unwrap();
+
+        assert!(view.is_64());
+        assert_eq!(view.load_commands().count(), 2);
+
+        let segments = view.segments().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(segments.len(), 1);
+
+        let sections = segments[0].sections().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(sections[0].sectname, "__text");
+        assert_eq!(sections[0].segname, "__TEXT");
+        assert_eq!(
+            sections[0].data().unwrap(),
+            Some(&[0x55, 0x48, 0x89, 0xe5, 0xc3][..])
+        );
+
+        let symtab = view.symtab().unwrap().unwrap();
+        let symbols = symtab.symbols().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0].name, "_main");
+        assert_eq!(symbols[0].n_sect, 1);
+    }
+
+    #[test]
+    fn view_reverse_endian_object_matches_owned_parse() {
+        let buf = build_object(Endian::REVERSE);
+        let view = MachOView::parse(&buf).unwrap();
+
+        assert_eq!(view.endian(), Endian::REVERSE);
+        for lc in view.load_commands() {
+            let lc = lc.unwrap();
+            let owned = lc.decode().unwrap();
+            assert_eq!(owned.cmd(), lc.cmd());
+            assert_eq!(owned.cmd_size(), lc.cmd_size());
+        }
+
+        let symtab = view.symtab().unwrap().unwrap();
+        assert_eq!(symtab.string(1).unwrap(), "_main");
+    }
+
+    #[test]
+    fn view_truncated_load_commands() {
+        let buf = build_object(Endian::NATIVE);
+        let err = MachOView::parse(&buf[..0x40]).unwrap_err();
+        assert!(matches!(err, Error::Truncated { offset: 0x20 }));
+    }
+}
@@ -0,0 +1,47 @@
+use atom_macho::{
+    header::{CpuCapabilities, CpuSubTypePowerPC, CpuType, FileType, Flags, Header64, Magic},
+    load_command::SymtabCommand,
+    Endian,
+};
+use std::io::Cursor;
+
+#[test]
+fn write_and_read_big_endian() {
+    let header = Header64 {
+        magic: Magic::Magic64,
+        cpu_type: CpuType::PowerPC64(CpuSubTypePowerPC::All, CpuCapabilities::NONE),
+        file_type: FileType::Object,
+        n_cmds: 1,
+        size_of_cmds: SymtabCommand::SIZE,
+        flags: Flags::new(),
+        reserved: 0,
+    };
+    let symtab = SymtabCommand {
+        cmd: SymtabCommand::TYPE,
+        cmdsize: SymtabCommand::SIZE,
+        symoff: 0x1000,
+        nsyms: 2,
+        stroff: 0x1020,
+        strsize: 16,
+    };
+
+    let mut buf = Vec::new();
+    header.write_into_in(&mut buf, Endian::Big).unwrap();
+    symtab.write_into_in(&mut buf, Endian::Big).unwrap();
+    assert_eq!(buf[..4], [0xfe, 0xed, 0xfa, 0xcf]);
+    assert_eq!(buf[32..36], [0x00, 0x00, 0x00, 0x02]);
+
+    let mut read = Cursor::new(buf);
+    let read_header = Header64::read_from(&mut read).unwrap();
+    assert_eq!(read_header.endian(), Endian::Big);
+    // the magic is read in the host byte order, so only the other fields are compared.
+    assert_eq!(
+        Header64 {
+            magic: header.magic,
+            ..read_header.clone()
+        },
+        header
+    );
+    let read_symtab = SymtabCommand::read_from_in(&mut read, read_header.endian()).unwrap();
+    assert_eq!(read_symtab, symtab);
+}